@@ -62,6 +62,25 @@ mod posix {
 
     const MAP_FAILED: *mut c_void = !0 as *mut c_void;
 
+    /// Miri only supports read/write anonymous mappings, so under it the whole range is
+    /// accessible from the start and the protection changes are skipped.
+    #[cfg(not(miri))]
+    const RESERVE_PROTECTION: i32 = PROT_NONE;
+    #[cfg(miri)]
+    const RESERVE_PROTECTION: i32 = PROT_READ | PROT_WRITE;
+
+    fn set_protection(ptr: *mut c_void, size: usize, protection: i32) -> Result<(), ArenaError> {
+        if cfg!(miri) {
+            return Ok(());
+        }
+
+        let result = unsafe { mprotect(ptr, size, protection) };
+        if result != 0 {
+            return Err(ArenaError::ProtectionFailed(get_last_error_message()));
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_page_size() -> usize {
         unsafe { sysconf(_SC_PAGESIZE) as usize }
     }
@@ -81,7 +100,9 @@ mod posix {
     }
 
     pub(crate) fn reserve_range(size: usize) -> Result<*mut c_void, ArenaError> {
-        let ptr = unsafe { mmap(null_mut(), size, PROT_NONE, MAP_PRIVATE | MAP_ANON, -1, 0) };
+        let ptr = unsafe {
+            mmap(null_mut(), size, RESERVE_PROTECTION, MAP_PRIVATE | MAP_ANON, -1, 0)
+        };
         if ptr == MAP_FAILED {
             return Err(ArenaError::ReserveFailed(get_last_error_message()));
         }
@@ -89,37 +110,22 @@ mod posix {
     }
 
     pub(crate) fn commit_memory(ptr: *mut c_void, size: usize) -> Result<(), ArenaError> {
-        let result = unsafe { mprotect(ptr, size, PROT_READ | PROT_WRITE) };
-        if result != 0 {
-            return Err(ArenaError::ProtectionFailed(get_last_error_message()));
-        }
-        Ok(())
+        set_protection(ptr, size, PROT_READ | PROT_WRITE)
     }
 
     pub(crate) fn decommit_memory(ptr: *mut c_void, size: usize) -> Result<(), ArenaError> {
-        let result = unsafe { mprotect(ptr, size, PROT_NONE) };
-        if result != 0 {
-            return Err(ArenaError::ProtectionFailed(get_last_error_message()));
-        }
-        Ok(())
+        set_protection(ptr, size, PROT_NONE)
     }
 
     #[cfg(debug_assertions)]
     pub(crate) fn protect_memory(ptr: *mut c_void, size: usize) -> Result<(), ArenaError> {
-        let result = unsafe { mprotect(ptr, size, PROT_NONE) };
-        if result != 0 {
-            return Err(ArenaError::ProtectionFailed(get_last_error_message()));
-        }
-        Ok(())
+        set_protection(ptr, size, PROT_NONE)
     }
 
     #[cfg(debug_assertions)]
     pub(crate) fn unprotect_memory(ptr: *mut c_void, size: usize) -> Result<(), ArenaError> {
         if size > 0 {
-            let result = unsafe { mprotect(ptr, size, PROT_READ | PROT_WRITE) };
-            if result != 0 {
-                return Err(ArenaError::ProtectionFailed(get_last_error_message()));
-            }
+            set_protection(ptr, size, PROT_READ | PROT_WRITE)?;
         }
         Ok(())
     }
@@ -0,0 +1,617 @@
+//! Rust stand-in for the Clay C library when running under Miri, which can't call foreign
+//! functions. It only does a simplified layout (fit, grow, percent and fixed sizing, padding,
+//! gaps, alignment and floating offsets) without text wrapping, scrolling or the debug view,
+//! but it goes through the same entry points so the Rust side, including the text measure
+//! callback, can be checked by Miri.
+//!
+//! Only one layout can be used at a time on each thread.
+
+use super::*;
+use core::ffi::c_void;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+type MeasureFunction =
+    unsafe extern "C" fn(Clay_StringSlice, *mut Clay_TextElementConfig, *mut c_void) -> Clay_Dimensions;
+
+struct Node {
+    id: u32,
+    declaration: Clay_ElementDeclaration,
+    text: Option<(Clay_String, *mut Clay_TextElementConfig)>,
+    children: Vec<usize>,
+    size: [f32; 2],
+    position: [f32; 2],
+}
+
+impl Node {
+    fn new(id: u32) -> Self {
+        Self {
+            id,
+            declaration: crate::mem::zeroed_init(),
+            text: None,
+            children: Vec::new(),
+            size: [0.0; 2],
+            position: [0.0; 2],
+        }
+    }
+
+    fn is_floating(&self) -> bool {
+        self.declaration.floating.attachTo != Clay_FloatingAttachToElement_CLAY_ATTACH_TO_NONE
+    }
+
+    fn sizing(&self, axis: usize) -> Clay_SizingAxis {
+        let sizing = self.declaration.layout.sizing;
+        if axis == 0 {
+            sizing.width
+        } else {
+            sizing.height
+        }
+    }
+
+    /// Padding at the start and the total padding along the axis
+    fn padding(&self, axis: usize) -> (f32, f32) {
+        let padding = self.declaration.layout.padding;
+        let (start, end) = if axis == 0 {
+            (padding.left, padding.right)
+        } else {
+            (padding.top, padding.bottom)
+        };
+        (start as f32, start as f32 + end as f32)
+    }
+
+    /// Axis the children are placed along
+    fn main_axis(&self) -> usize {
+        (self.declaration.layout.layoutDirection == Clay_LayoutDirection_CLAY_TOP_TO_BOTTOM) as usize
+    }
+
+    fn bounding_box(&self) -> Clay_BoundingBox {
+        Clay_BoundingBox {
+            x: self.position[0],
+            y: self.position[1],
+            width: self.size[0],
+            height: self.size[1],
+        }
+    }
+}
+
+fn min_max(sizing: &Clay_SizingAxis) -> (f32, f32) {
+    let min_max = unsafe { sizing.size.minMax };
+    let max = if min_max.max <= 0.0 { f32::MAX } else { min_max.max };
+    (min_max.min, max)
+}
+
+fn clamp(value: f32, sizing: &Clay_SizingAxis) -> f32 {
+    let (min, max) = min_max(sizing);
+    value.min(max).max(min)
+}
+
+fn hash_number(offset: u32, seed: u32) -> u32 {
+    let mut hash = seed;
+    hash = hash.wrapping_add(offset + 48);
+    hash = hash.wrapping_add(hash << 10);
+    hash ^= hash >> 6;
+    hash = hash.wrapping_add(hash << 3);
+    hash ^= hash >> 11;
+    hash = hash.wrapping_add(hash << 15);
+    hash.wrapping_add(1)
+}
+
+#[derive(Default)]
+struct State {
+    dimensions: [f32; 2],
+    pointer: [f32; 2],
+    measure: Option<(MeasureFunction, *mut c_void)>,
+    nodes: Vec<Node>,
+    open: Vec<usize>,
+    text_configs: Vec<Box<Clay_TextElementConfig>>,
+    commands: Vec<Clay_RenderCommand>,
+    bounding_boxes: HashMap<u32, Clay_BoundingBox>,
+}
+
+impl State {
+    fn add_child(&mut self, mut node: Node) -> usize {
+        let index = self.nodes.len();
+
+        if let Some(&parent) = self.open.last() {
+            if node.id == 0 {
+                node.id = hash_number(self.nodes[parent].children.len() as u32, self.nodes[parent].id);
+            }
+            self.nodes[parent].children.push(index);
+        }
+
+        self.nodes.push(node);
+        index
+    }
+
+    /// Sizes the element to fit its children, called when the element is closed
+    fn fit(&mut self, index: usize) {
+        let node = &self.nodes[index];
+        let main_axis = node.main_axis();
+        let gap = node.declaration.layout.childGap as f32;
+        let children: Vec<[f32; 2]> = node
+            .children
+            .iter()
+            .map(|&child| &self.nodes[child])
+            .filter(|child| !child.is_floating())
+            .map(|child| child.size)
+            .collect();
+
+        let mut size = [0.0; 2];
+
+        for (axis, size) in size.iter_mut().enumerate() {
+            let sizing = node.sizing(axis);
+            let content = if axis == main_axis {
+                children.iter().map(|child| child[axis]).sum::<f32>()
+                    + gap * children.len().saturating_sub(1) as f32
+            } else {
+                children.iter().map(|child| child[axis]).fold(0.0, f32::max)
+            };
+
+            *size = match sizing.type_ {
+                Clay__SizingType_CLAY__SIZING_TYPE_FIXED => unsafe { sizing.size.minMax.min },
+                Clay__SizingType_CLAY__SIZING_TYPE_PERCENT => 0.0,
+                _ => clamp(content + node.padding(axis).1, &sizing),
+            };
+        }
+
+        self.nodes[index].size = size;
+    }
+
+    /// Resolves grow and percent sizes of the children and places them inside the element
+    fn place_children(&mut self, index: usize) {
+        let node = &self.nodes[index];
+        let main_axis = node.main_axis();
+        let gap = node.declaration.layout.childGap as f32;
+        let alignment = node.declaration.layout.childAlignment;
+        let inner = [0, 1].map(|axis| node.size[axis] - node.padding(axis).1);
+        let children: Vec<usize> = node
+            .children
+            .iter()
+            .copied()
+            .filter(|&child| !self.nodes[child].is_floating())
+            .collect();
+        let gaps = gap * children.len().saturating_sub(1) as f32;
+
+        for &child in &children {
+            let child = &mut self.nodes[child];
+            for axis in 0..2 {
+                let sizing = child.sizing(axis);
+                if sizing.type_ == Clay__SizingType_CLAY__SIZING_TYPE_PERCENT {
+                    let space = if axis == main_axis { inner[axis] - gaps } else { inner[axis] };
+                    child.size[axis] = space * unsafe { sizing.size.percent };
+                } else if sizing.type_ == Clay__SizingType_CLAY__SIZING_TYPE_GROW && axis != main_axis {
+                    child.size[axis] = clamp(inner[axis], &sizing);
+                }
+            }
+        }
+
+        let growing: Vec<usize> = children
+            .iter()
+            .copied()
+            .filter(|&child| {
+                self.nodes[child].sizing(main_axis).type_ == Clay__SizingType_CLAY__SIZING_TYPE_GROW
+            })
+            .collect();
+        let used = |nodes: &[Node]| children.iter().map(|&child| nodes[child].size[main_axis]).sum::<f32>() + gaps;
+        let remaining = inner[main_axis] - used(&self.nodes);
+
+        if remaining > 0.0 && !growing.is_empty() {
+            let extra = remaining / growing.len() as f32;
+            for child in growing {
+                let child = &mut self.nodes[child];
+                child.size[main_axis] = clamp(child.size[main_axis] + extra, &child.sizing(main_axis));
+            }
+        }
+
+        // Alignment offset of the free space, 0 = start, 1 = end and 2 = center for both axes
+        let align = |free: f32, axis: usize| {
+            let value = if axis == 0 { alignment.x } else { alignment.y };
+            match value {
+                1 => free,
+                2 => free / 2.0,
+                _ => 0.0,
+            }
+        };
+
+        let node = &self.nodes[index];
+        let start = [0, 1].map(|axis| node.position[axis] + node.padding(axis).0);
+        let mut cursor = start[main_axis] + align(inner[main_axis] - used(&self.nodes), main_axis);
+        let cross_axis = 1 - main_axis;
+
+        for &child in &children {
+            let cross = start[cross_axis] + align(inner[cross_axis] - self.nodes[child].size[cross_axis], cross_axis);
+            let child = &mut self.nodes[child];
+            child.position[main_axis] = cursor;
+            child.position[cross_axis] = cross;
+            cursor += child.size[main_axis] + gap;
+        }
+
+        for child in children {
+            self.place_children(child);
+        }
+    }
+
+    /// Places the floating elements relative to what they are attached to
+    fn place_floating(&mut self, parents: &[usize]) {
+        for index in 0..self.nodes.len() {
+            if !self.nodes[index].is_floating() {
+                continue;
+            }
+
+            let floating = self.nodes[index].declaration.floating;
+            let target = match floating.attachTo {
+                Clay_FloatingAttachToElement_CLAY_ATTACH_TO_PARENT => parents[index],
+                Clay_FloatingAttachToElement_CLAY_ATTACH_TO_ELEMENT_WITH_ID => self
+                    .nodes
+                    .iter()
+                    .position(|node| node.id == floating.parentId)
+                    .unwrap_or(0),
+                _ => 0,
+            };
+
+            let target_position = self.nodes[target].position;
+            let node = &mut self.nodes[index];
+            node.position = [
+                target_position[0] + floating.offset.x,
+                target_position[1] + floating.offset.y,
+            ];
+
+            self.place_children(index);
+        }
+    }
+
+    fn command(node: &Node, command_type: Clay_RenderCommandType, render_data: Clay_RenderData, z: i16) -> Clay_RenderCommand {
+        Clay_RenderCommand {
+            boundingBox: node.bounding_box(),
+            renderData: render_data,
+            userData: node.declaration.userData,
+            id: node.id,
+            zIndex: z,
+            commandType: command_type,
+        }
+    }
+
+    fn emit(&self, index: usize, z: i16, commands: &mut Vec<Clay_RenderCommand>) {
+        let node = &self.nodes[index];
+        let declaration = &node.declaration;
+        let mut data: Clay_RenderData = crate::mem::zeroed_init();
+
+        if let Some((text, config)) = node.text {
+            let config = unsafe { *config };
+            data.text = Clay_TextRenderData {
+                stringContents: Clay_StringSlice {
+                    length: text.length,
+                    chars: text.chars,
+                    baseChars: text.chars,
+                },
+                textColor: config.textColor,
+                fontId: config.fontId,
+                fontSize: config.fontSize,
+                letterSpacing: config.letterSpacing,
+                lineHeight: config.lineHeight,
+            };
+            let mut command = Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_TEXT, data, z);
+            command.id = hash_number(0, node.id);
+            commands.push(command);
+            return;
+        }
+
+        let scroll = declaration.scroll.horizontal || declaration.scroll.vertical;
+        if scroll {
+            commands.push(Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_SCISSOR_START, data, z));
+        }
+
+        if !declaration.image.imageData.is_null() {
+            data.image = Clay_ImageRenderData {
+                backgroundColor: declaration.backgroundColor,
+                cornerRadius: declaration.cornerRadius,
+                sourceDimensions: declaration.image.sourceDimensions,
+                imageData: declaration.image.imageData,
+            };
+            commands.push(Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_IMAGE, data, z));
+        } else if !declaration.custom.customData.is_null() {
+            data.custom = Clay_CustomRenderData {
+                backgroundColor: declaration.backgroundColor,
+                cornerRadius: declaration.cornerRadius,
+                customData: declaration.custom.customData,
+            };
+            commands.push(Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_CUSTOM, data, z));
+        } else if declaration.backgroundColor.a > 0.0 {
+            data.rectangle = Clay_RectangleRenderData {
+                backgroundColor: declaration.backgroundColor,
+                cornerRadius: declaration.cornerRadius,
+            };
+            commands.push(Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_RECTANGLE, data, z));
+        }
+
+        for &child in &node.children {
+            if !self.nodes[child].is_floating() {
+                self.emit(child, z, commands);
+            }
+        }
+
+        let width = declaration.border.width;
+        if width.left + width.right + width.top + width.bottom + width.betweenChildren > 0 {
+            data.border = Clay_BorderRenderData {
+                color: declaration.border.color,
+                cornerRadius: declaration.cornerRadius,
+                width,
+            };
+            commands.push(Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_BORDER, data, z));
+        }
+
+        if scroll {
+            commands.push(Self::command(node, Clay_RenderCommandType_CLAY_RENDER_COMMAND_TYPE_SCISSOR_END, data, z));
+        }
+    }
+
+    fn end_layout(&mut self) {
+        while let Some(index) = self.open.pop() {
+            self.fit(index);
+        }
+
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut parents = vec![0; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for &child in &node.children {
+                parents[child] = index;
+            }
+        }
+
+        self.place_children(0);
+        self.place_floating(&parents);
+
+        let mut commands = std::mem::take(&mut self.commands);
+        commands.clear();
+        self.emit(0, 0, &mut commands);
+
+        let mut floating: Vec<usize> = (0..self.nodes.len()).filter(|&index| self.nodes[index].is_floating()).collect();
+        floating.sort_by_key(|&index| self.nodes[index].declaration.floating.zIndex);
+        for index in floating {
+            self.emit(index, self.nodes[index].declaration.floating.zIndex, &mut commands);
+        }
+
+        self.commands = commands;
+        self.bounding_boxes = self
+            .nodes
+            .iter()
+            .filter(|node| node.text.is_none())
+            .map(|node| (node.id, node.bounding_box()))
+            .collect();
+    }
+
+    fn contains_pointer(&self, id: u32) -> bool {
+        self.bounding_boxes.get(&id).is_some_and(|bb| {
+            let [x, y] = self.pointer;
+            x >= bb.x && y >= bb.y && x < bb.x + bb.width && y < bb.y + bb.height
+        })
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+    // The arena memory is only used to tell contexts apart, it's never accessed
+    static CONTEXT: Cell<*mut Clay_Context> = const { Cell::new(core::ptr::null_mut()) };
+}
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+pub unsafe fn Clay_MinMemorySize() -> u32 {
+    // The state lives in a thread local so clay doesn't need any memory
+    16
+}
+
+pub unsafe fn Clay_CreateArenaWithCapacityAndMemory(capacity: u32, offset: *mut c_void) -> Clay_Arena {
+    Clay_Arena {
+        nextAllocation: 0,
+        capacity: capacity as usize,
+        memory: offset as _,
+    }
+}
+
+pub unsafe fn Clay_Initialize(
+    arena: Clay_Arena,
+    layout_dimensions: Clay_Dimensions,
+    _error_handler: Clay_ErrorHandler,
+) -> *mut Clay_Context {
+    with_state(|state| {
+        *state = State {
+            dimensions: [layout_dimensions.width, layout_dimensions.height],
+            ..State::default()
+        };
+    });
+
+    let context = arena.memory as *mut Clay_Context;
+    CONTEXT.set(context);
+
+    context
+}
+
+pub unsafe fn Clay_GetCurrentContext() -> *mut Clay_Context {
+    CONTEXT.get()
+}
+
+pub unsafe fn Clay_SetCurrentContext(context: *mut Clay_Context) {
+    CONTEXT.set(context);
+}
+
+pub unsafe fn Clay_SetPointerState(position: Clay_Vector2, _pointer_down: bool) {
+    with_state(|state| state.pointer = [position.x, position.y]);
+}
+
+pub unsafe fn Clay_UpdateScrollContainers(_enable_drag_scrolling: bool, _scroll_delta: Clay_Vector2, _delta_time: f32) {}
+
+pub unsafe fn Clay_SetLayoutDimensions(dimensions: Clay_Dimensions) {
+    with_state(|state| state.dimensions = [dimensions.width, dimensions.height]);
+}
+
+pub unsafe fn Clay_BeginLayout() {
+    with_state(|state| {
+        state.nodes.clear();
+        state.open.clear();
+        state.text_configs.clear();
+
+        let mut root = Node::new(Clay__HashString("Clay__RootContainer".into(), 0, 0).id);
+        root.declaration.layout.sizing.width.type_ = Clay__SizingType_CLAY__SIZING_TYPE_FIXED;
+        root.declaration.layout.sizing.width.size.minMax = Clay_SizingMinMax {
+            min: state.dimensions[0],
+            max: state.dimensions[0],
+        };
+        root.declaration.layout.sizing.height.type_ = Clay__SizingType_CLAY__SIZING_TYPE_FIXED;
+        root.declaration.layout.sizing.height.size.minMax = Clay_SizingMinMax {
+            min: state.dimensions[1],
+            max: state.dimensions[1],
+        };
+
+        let index = state.add_child(root);
+        state.open.push(index);
+    });
+}
+
+pub unsafe fn Clay_EndLayout() -> Clay_RenderCommandArray {
+    with_state(|state| {
+        state.end_layout();
+        Clay_RenderCommandArray {
+            capacity: state.commands.capacity() as _,
+            length: state.commands.len() as _,
+            internalArray: state.commands.as_mut_ptr(),
+        }
+    })
+}
+
+pub unsafe fn Clay_GetElementData(id: Clay_ElementId) -> Clay_ElementData {
+    with_state(|state| match state.bounding_boxes.get(&id.id) {
+        Some(bounding_box) => Clay_ElementData {
+            boundingBox: *bounding_box,
+            found: true,
+        },
+        None => Clay_ElementData {
+            boundingBox: crate::mem::zeroed_init(),
+            found: false,
+        },
+    })
+}
+
+pub unsafe fn Clay_Hovered() -> bool {
+    with_state(|state| {
+        let id = state.open.last().map(|&index| state.nodes[index].id);
+        id.is_some_and(|id| state.contains_pointer(id))
+    })
+}
+
+pub unsafe fn Clay_PointerOver(element_id: Clay_ElementId) -> bool {
+    with_state(|state| state.contains_pointer(element_id.id))
+}
+
+pub unsafe fn Clay_GetScrollContainerData(_id: Clay_ElementId) -> Clay_ScrollContainerData {
+    crate::mem::zeroed_init()
+}
+
+pub unsafe fn Clay__StoreTextElementConfig(config: Clay_TextElementConfig) -> *mut Clay_TextElementConfig {
+    with_state(|state| {
+        let mut config = Box::new(config);
+        let ptr: *mut Clay_TextElementConfig = &mut *config;
+        state.text_configs.push(config);
+        ptr
+    })
+}
+
+pub unsafe fn Clay_SetMeasureTextFunction(
+    measure_text_function: Option<MeasureFunction>,
+    user_data: *mut c_void,
+) {
+    with_state(|state| state.measure = measure_text_function.map(|function| (function, user_data)));
+}
+
+pub unsafe fn Clay_SetDebugModeEnabled(_enabled: bool) {}
+
+pub unsafe fn Clay_SetCullingEnabled(_enabled: bool) {}
+
+pub unsafe fn Clay_SetMaxElementCount(_max_element_count: i32) {}
+
+pub unsafe fn Clay_ResetMeasureTextCache() {}
+
+pub unsafe fn Clay__OpenElement() {
+    with_state(|state| {
+        let index = state.add_child(Node::new(0));
+        state.open.push(index);
+    });
+}
+
+pub unsafe fn Clay__ConfigureOpenElement(config: Clay_ElementDeclaration) {
+    with_state(|state| {
+        let index = *state.open.last().unwrap();
+        let node = &mut state.nodes[index];
+        if config.id.id != 0 {
+            node.id = config.id.id;
+        }
+        node.declaration = config;
+    });
+}
+
+pub unsafe fn Clay__CloseElement() {
+    with_state(|state| {
+        if let Some(index) = state.open.pop() {
+            state.fit(index);
+        }
+    });
+}
+
+pub unsafe fn Clay__HashString(key: Clay_String, offset: u32, seed: u32) -> Clay_ElementId {
+    let chars = core::slice::from_raw_parts(key.chars as *const u8, key.length as usize);
+    let mut base = seed;
+
+    for &c in chars {
+        base = base.wrapping_add(c as i8 as u32);
+        base = base.wrapping_add(base << 10);
+        base ^= base >> 6;
+    }
+
+    let mut hash = base.wrapping_add(offset);
+    hash = hash.wrapping_add(hash << 10);
+    hash ^= hash >> 6;
+
+    hash = hash.wrapping_add(hash << 3);
+    base = base.wrapping_add(base << 3);
+    hash ^= hash >> 11;
+    base ^= base >> 11;
+    hash = hash.wrapping_add(hash << 15);
+    base = base.wrapping_add(base << 15);
+
+    Clay_ElementId {
+        id: hash.wrapping_add(1),
+        offset,
+        baseId: base.wrapping_add(1),
+        stringId: key,
+    }
+}
+
+pub unsafe fn Clay__OpenTextElement(text: Clay_String, text_config: *mut Clay_TextElementConfig) {
+    // The measure function is called without the state borrowed as it may call back into clay
+    let measure = with_state(|state| state.measure);
+    let slice = Clay_StringSlice {
+        length: text.length,
+        chars: text.chars,
+        baseChars: text.chars,
+    };
+    let size = match measure {
+        Some((function, user_data)) => function(slice, text_config, user_data),
+        None => Clay_Dimensions { width: 0.0, height: 0.0 },
+    };
+
+    with_state(|state| {
+        let mut node = Node::new(0);
+        node.text = Some((text, text_config));
+        node.size = [size.width, size.height];
+        state.add_child(node);
+    });
+}
+
+pub unsafe fn Clay__GetParentElementId() -> u32 {
+    with_state(|state| state.open.last().map_or(0, |&index| state.nodes[index].id))
+}
@@ -4,11 +4,28 @@
 #![allow(clippy::module_inception)]
 
 #[cfg(not(feature = "debug"))]
+#[cfg_attr(miri, allow(dead_code))]
 mod bindings;
 #[cfg(feature = "debug")]
+#[cfg_attr(miri, allow(dead_code))]
 mod bindings_debug;
 
 #[cfg(not(feature = "debug"))]
 pub use self::bindings::*;
 #[cfg(feature = "debug")]
 pub use self::bindings_debug::*;
+
+#[cfg(all(miri, feature = "std"))]
+mod miri;
+
+// Shadows the foreign functions from the glob imports above
+#[cfg(all(miri, feature = "std"))]
+pub use self::miri::{
+    Clay_BeginLayout, Clay_CreateArenaWithCapacityAndMemory, Clay_EndLayout, Clay_GetCurrentContext,
+    Clay_GetElementData, Clay_GetScrollContainerData, Clay_Hovered, Clay_Initialize, Clay_MinMemorySize,
+    Clay_PointerOver, Clay_ResetMeasureTextCache, Clay_SetCullingEnabled, Clay_SetCurrentContext,
+    Clay_SetDebugModeEnabled, Clay_SetLayoutDimensions, Clay_SetMaxElementCount, Clay_SetMeasureTextFunction,
+    Clay_SetPointerState, Clay_UpdateScrollContainers, Clay__CloseElement, Clay__ConfigureOpenElement,
+    Clay__GetParentElementId, Clay__HashString, Clay__OpenElement, Clay__OpenTextElement,
+    Clay__StoreTextElementConfig,
+};
@@ -116,6 +116,43 @@ impl ImageBuilder<'_> {
     }
 }
 
+/// Builder for configuring custom element properties using a `Declaration`.
+pub struct CustomBuilder<'a> {
+    parent: &'a mut Declaration,
+}
+
+impl CustomBuilder<'_> {
+    /// Creates a new `CustomBuilder` with the given parent `Declaration`.
+    #[inline]
+    pub fn new(parent: &mut Declaration) -> CustomBuilder<'_> {
+        CustomBuilder { parent }
+    }
+
+    /// Sets the custom data.
+    /// The data must be created using [`Clay::data`].
+    #[inline]
+    pub fn data(&mut self, data: DataRef) -> &mut Self {
+        self.parent.inner.custom.customData = data.ptr as *mut c_void;
+        self
+    }
+
+    /// Sets the custom data using a raw pointer.
+    ///
+    /// # Safety
+    /// This function is unsafe because it accepts a raw pointer.
+    #[inline]
+    pub unsafe fn data_ptr(&mut self, data: *const c_void) -> &mut Self {
+        self.parent.inner.custom.customData = data as _;
+        self
+    }
+
+    /// Returns the modified `Declaration`.
+    #[inline]
+    pub fn end(&mut self) -> &mut Declaration {
+        self.parent
+    }
+}
+
 /// Represents different attachment points for floating elements.
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
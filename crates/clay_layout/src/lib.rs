@@ -68,6 +68,11 @@ impl Declaration {
         elements::ImageBuilder::new(self)
     }
 
+    #[inline]
+    pub fn custom(&mut self) -> elements::CustomBuilder<'_> {
+        elements::CustomBuilder::new(self)
+    }
+
     #[inline]
     pub fn floating(&mut self) -> elements::FloatingBuilder {
         elements::FloatingBuilder::new(self)
@@ -153,7 +158,7 @@ pub struct Clay<'a> {
     /// Phantom data to keep the lifetime of the memory
     _phantom: core::marker::PhantomData<&'a ()>,
     /// Stores the raw pointer to the callback data for later cleanup
-    text_measure_callback: Option<*const core::ffi::c_void>,
+    text_measure_callback: Option<(*const core::ffi::c_void, unsafe fn(*const core::ffi::c_void))>,
 }
 
 impl<'a> Clay<'a> {
@@ -243,7 +248,7 @@ impl<'a> Clay<'a> {
         }
 
         // Store the raw pointer for later cleanup
-        self.text_measure_callback = Some(user_data_ptr as *const core::ffi::c_void);
+        self.text_measure_callback = Some((user_data_ptr as *const core::ffi::c_void, drop_boxed::<(F, T)>));
     }
 
     /// Set the callback for text measurement
@@ -264,7 +269,7 @@ impl<'a> Clay<'a> {
         }
 
         // Store the raw pointer for later cleanup
-        self.text_measure_callback = Some(user_data_ptr as *const core::ffi::c_void);
+        self.text_measure_callback = Some((user_data_ptr as *const core::ffi::c_void, drop_boxed::<F>));
     }
 
     /// Set the callback for text measurement with user data.
@@ -335,6 +340,22 @@ impl<'a> Clay<'a> {
         }
     }
 
+    /// Enables or disables culling of render commands for elements outside the layout
+    /// dimensions. Enabled by default.
+    pub fn enable_culling(&self, enable: bool) {
+        unsafe {
+            Clay_SetCullingEnabled(enable);
+        }
+    }
+
+    /// Clears the cached text measurements. Needed if the result of the measure function
+    /// changes for the same text, for example when a font has finished loading.
+    pub fn reset_measure_text_cache(&self) {
+        unsafe {
+            Clay_ResetMeasureTextCache();
+        }
+    }
+
     /// Sets the dimensions of the global layout, use if, for example the window size you render to
     /// changed
     pub fn layout_dimensions(&self, dimensions: Dimensions) {
@@ -432,12 +453,23 @@ impl<'a> Clay<'a> {
     }
 }
 
+/// Frees a callback boxed by one of the `set_measure_text_function` variants with its real type
+#[cfg(feature = "std")]
+unsafe fn drop_boxed<T>(ptr: *const core::ffi::c_void) {
+    drop(Box::from_raw(ptr as *mut T));
+}
+
 #[cfg(feature = "std")]
 impl Drop for Clay<'_> {
     fn drop(&mut self) {
         unsafe {
-            if let Some(ptr) = self.text_measure_callback {
-                let _ = Box::from_raw(ptr as *mut (usize, usize));
+            // Clay_Initialize reads from the current context so it must not point at freed memory
+            if Clay_GetCurrentContext() == self.context {
+                Clay_SetCurrentContext(core::ptr::null_mut());
+            }
+
+            if let Some((ptr, drop)) = self.text_measure_callback {
+                drop(ptr);
             }
         }
     }
@@ -1,7 +1,10 @@
 use crate::sdl_window::Sdl2Window;
 use core::ptr::null_mut;
 use core::{ffi::c_void, mem::transmute};
-use flowi_core::{input::Input, ApplicationSettings, Renderer, SoftwareRenderData, Ui};
+use flowi_core::{
+    input::Input, ActionResponse, ApplicationSettings, InputAction, Renderer, SoftwareRenderData,
+    Ui,
+};
 use flowi_sw_renderer::Renderer as SoftwareRenderer;
 use tracy_client::span;
 
@@ -17,6 +20,8 @@ pub(crate) trait Window {
     fn should_close(&mut self) -> bool;
     fn update_software_renderer<'a>(&'a mut self, _data: Option<SoftwareRenderData<'a>>) {}
     fn present(&mut self) {}
+    /// Current size of the output in pixels
+    fn size(&self) -> (usize, usize);
     //fn is_focused(&self) -> bool;
     //fn raw_window_handle(&self) -> RawWindowHandle;
 }
@@ -57,19 +62,22 @@ unsafe extern "C" fn mainloop_app<T>(user_data: *mut c_void) {
             let zone = span!("window update");
             zone.emit_color(0x00FF00);
             //state.core.pre_update();
-            state.window.update(state.ui.input());
+            state.window.update(state.ui.input_mut());
             state.ui.update();
         }
 
+        if state.ui.get_input_action(InputAction::Quit) == ActionResponse::Pressed {
+            break;
+        }
+
         {
             let zone = span!("ui begin");
             zone.emit_color(0x0000FF);
 
-            state.ui.begin(
-                state.ui.input().delta_time,
-                state.settings.width,
-                state.settings.height,
-            );
+            let delta_time = state.ui.input().delta_time;
+            let (width, height) = state.window.size();
+
+            state.ui.begin(delta_time, width, height);
         }
 
         {
@@ -121,10 +121,6 @@ impl GlfwWindow {
             match event {
                 glfw::WindowEvent::Key(key, _, action, _) => {
                     if let Some(key) = translate_glfw_to_flowi_key(key) {
-                        // TODO: Hack to close window with esc
-                        if key == Key::Escape && action == Action::Press {
-                            self.should_close = true;
-                        }
                         Input::add_key_event(key, action == Action::Press);
                     } else {
                         println!("Unknown key: {:?}", key);
@@ -1,11 +1,12 @@
 use crate::application::Window;
-use flowi_core::input::{Input, Key};
+use flowi_core::input::{Input, Key, MouseSource};
+use flowi_core::touch::TouchPhase;
 use flowi_core::ApplicationSettings;
 use flowi_core::SoftwareRenderData;
 use tracy_client::span;
 
 use sdl2::{
-    //controller::{Axis, Button, GameController},
+    controller::{Axis, Button, GameController},
     event::Event,
     keyboard::Keycode,
     mouse::MouseButton,
@@ -114,11 +115,16 @@ fn translate_sdl2_to_flowi_key(key: Keycode) -> Option<Key> {
     }
 }
 
+/// Mouse id SDL uses for mouse events generated from touches
+const SDL_TOUCH_MOUSEID: u32 = u32::MAX;
+
 pub(crate) struct Sdl2Window {
     sdl_context: sdl2::Sdl,
     canvas: sdl2::render::Canvas<sdl2::video::Window>,
     event_pump: sdl2::EventPump,
     texture: Texture,
+    controller_subsystem: sdl2::GameControllerSubsystem,
+    controller: Option<GameController>,
     time: f64,
     should_close: bool,
     window_size: (u32, u32),
@@ -134,10 +140,6 @@ impl Sdl2Window {
                     ..
                 } => {
                     if let Some(key) = translate_sdl2_to_flowi_key(keycode) {
-                        // Close window with ESC
-                        if key == Key::Escape {
-                            self.should_close = true;
-                        }
                         input.add_key_event(key, true);
                     } else {
                         println!("Unknown key: {:?}", keycode);
@@ -148,21 +150,68 @@ impl Sdl2Window {
                     ..
                 } => {
                     if let Some(key) = translate_sdl2_to_flowi_key(keycode) {
-                        input.add_key_event(key, true);
+                        input.add_key_event(key, false);
+                    }
+                }
+                Event::TextInput { text, .. } => {
+                    for c in text.chars() {
+                        input.add_char_event(c as i32);
+                    }
+                }
+                Event::MouseWheel {
+                    precise_x,
+                    precise_y,
+                    ..
+                } => {
+                    input.add_mouse_wheel_event(precise_x, precise_y);
+                }
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if self.controller.is_none() {
+                        self.controller = self.controller_subsystem.open(which).ok();
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if self
+                        .controller
+                        .as_ref()
+                        .is_some_and(|c| c.instance_id() == which)
+                    {
+                        self.controller = None;
                     }
                 }
-                Event::MouseMotion { x, y, .. } => {
+                Event::MouseMotion { which, x, y, .. } => {
+                    // SDL also sends mouse events for touches, only real mouse movement switches
+                    // the source back to the mouse
+                    if which != SDL_TOUCH_MOUSEID {
+                        input.add_mouse_source_event(MouseSource::Mouse);
+                    }
                     input.add_mouse_pos_event(Some((x as f32, y as f32)));
                 }
+                Event::FingerDown {
+                    finger_id, x, y, ..
+                } => {
+                    input.add_mouse_source_event(MouseSource::TouchScreen);
+                    Self::add_touch_event(input, self.window_size, finger_id, TouchPhase::Began, x, y);
+                }
+                Event::FingerMotion {
+                    finger_id, x, y, ..
+                } => {
+                    Self::add_touch_event(input, self.window_size, finger_id, TouchPhase::Moved, x, y);
+                }
+                Event::FingerUp {
+                    finger_id, x, y, ..
+                } => {
+                    Self::add_touch_event(input, self.window_size, finger_id, TouchPhase::Ended, x, y);
+                }
                 Event::MouseButtonDown { mouse_btn, .. } => {
-                    input
-                        .add_mouse_button_event(Self::translate_sdl2_mouse_button(mouse_btn), true);
+                    if let Some(button) = Self::translate_sdl2_mouse_button(mouse_btn) {
+                        input.add_mouse_button_event(button, true);
+                    }
                 }
                 Event::MouseButtonUp { mouse_btn, .. } => {
-                    input.add_mouse_button_event(
-                        Self::translate_sdl2_mouse_button(mouse_btn),
-                        false,
-                    );
+                    if let Some(button) = Self::translate_sdl2_mouse_button(mouse_btn) {
+                        input.add_mouse_button_event(button, false);
+                    }
                 }
                 Event::Window {
                     win_event: sdl2::event::WindowEvent::FocusGained,
@@ -176,6 +225,12 @@ impl Sdl2Window {
                 } => {
                     input.add_focus_event(false);
                 }
+                Event::Window {
+                    win_event: sdl2::event::WindowEvent::SizeChanged(width, height),
+                    ..
+                } => {
+                    self.resize(width as u32, height as u32);
+                }
                 // Handle other events as needed
                 _ => {}
             }
@@ -183,7 +238,39 @@ impl Sdl2Window {
 
         self.update_modifiers(input);
         self.update_mouse_data(input);
-        //self.update_pad(input);
+
+        if let Some(controller) = self.controller.as_ref() {
+            Self::update_pad(controller, input);
+        }
+    }
+
+    /// Recreates the texture the software renderer output is copied to
+    fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) == self.window_size || width == 0 || height == 0 {
+            return;
+        }
+
+        let texture_creator = self.canvas.texture_creator();
+
+        if let Ok(texture) =
+            texture_creator.create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, width, height)
+        {
+            self.texture = texture;
+            self.window_size = (width, height);
+        }
+    }
+
+    /// Touch positions from SDL are normalized to 0..1 over the window
+    fn add_touch_event(
+        input: &mut Input,
+        window_size: (u32, u32),
+        finger_id: i64,
+        phase: TouchPhase,
+        x: f32,
+        y: f32,
+    ) {
+        let (width, height) = window_size;
+        input.add_touch_event(finger_id as u64, phase, x * width as f32, y * height as f32);
     }
 
     fn update_modifiers(&mut self, input: &mut Input) {
@@ -212,19 +299,18 @@ impl Sdl2Window {
         input.add_mouse_pos_event(Some((x as f32, y as f32)));
     }
 
-    #[allow(dead_code)]
-    fn translate_sdl2_mouse_button(button: MouseButton) -> i32 {
+    fn translate_sdl2_mouse_button(button: MouseButton) -> Option<i32> {
         match button {
-            MouseButton::Left => 1,
-            MouseButton::Middle => 2,
-            MouseButton::Right => 3,
-            // Map other buttons as needed
-            _ => 0,
+            MouseButton::Left => Some(flowi_core::input::MouseButton::Left as i32),
+            MouseButton::Right => Some(flowi_core::input::MouseButton::Right as i32),
+            MouseButton::Middle => Some(flowi_core::input::MouseButton::Middle as i32),
+            MouseButton::X1 => Some(3),
+            MouseButton::X2 => Some(4),
+            _ => None,
         }
     }
 
-    /*
-    fn update_pad(&mut self, controller: &GameController, input: &mut Input) {
+    fn update_pad(controller: &GameController, input: &mut Input) {
         let digital_buttons = [
             (Key::GamepadBack, Button::Back, 6),
             (Key::GamepadStart, Button::Start, 7),
@@ -242,16 +328,17 @@ impl Sdl2Window {
             (Key::GamepadR3, Button::RightStick, 9),
         ];
 
+        // Value at rest and at full deflection. Triggers are 0..32767 in SDL
         let analog_buttons = [
-            (Key::GamepadL2, Axis::TriggerLeft, 4, -32768, 32767),
-            (Key::GamepadR2, Axis::TriggerRight, 5, -32768, 32767),
-            (Key::GamepadLStickLeft, Axis::LeftX, 0, -32768, 0),
+            (Key::GamepadL2, Axis::TriggerLeft, 4, 0, 32767),
+            (Key::GamepadR2, Axis::TriggerRight, 5, 0, 32767),
+            (Key::GamepadLStickLeft, Axis::LeftX, 0, 0, -32768),
             (Key::GamepadLStickRight, Axis::LeftX, 0, 0, 32767),
-            (Key::GamepadLStickUp, Axis::LeftY, 1, -32768, 0),
+            (Key::GamepadLStickUp, Axis::LeftY, 1, 0, -32768),
             (Key::GamepadLStickDown, Axis::LeftY, 1, 0, 32767),
-            (Key::GamepadRStickLeft, Axis::RightX, 2, -32768, 0),
+            (Key::GamepadRStickLeft, Axis::RightX, 2, 0, -32768),
             (Key::GamepadRStickRight, Axis::RightX, 2, 0, 32767),
-            (Key::GamepadRStickUp, Axis::RightY, 3, -32768, 0),
+            (Key::GamepadRStickUp, Axis::RightY, 3, 0, -32768),
             (Key::GamepadRStickDown, Axis::RightY, 3, 0, 32767),
         ];
 
@@ -261,19 +348,13 @@ impl Sdl2Window {
             input.add_key_event(*key, pressed);
         }
 
-        // Analog buttons and sticks
-        for (key, axis, _index, min, max) in analog_buttons.iter() {
+        // Analog buttons and sticks. Deadzones are handled by Input
+        for (key, axis, _index, rest, max) in analog_buttons.iter() {
             let value = controller.axis(*axis) as i32;
-            let normalized_value = (value - min) as f32 / (max - min) as f32;
-            // Assuming Input::add_key_analog_event exists and handles the logic for analog input events
-            input.add_key_analog_event(
-                *key,
-                normalized_value > 0.0,
-                normalized_value.clamp(0.0, 1.0),
-            );
+            let normalized_value = ((value - rest) as f32 / (max - rest) as f32).clamp(0.0, 1.0);
+            input.add_key_analog_event(*key, normalized_value > 0.0, normalized_value);
         }
     }
-    */
 
     fn write_png(filename: &str, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
         use std::fs::File;
@@ -331,6 +412,7 @@ impl Window for Sdl2Window {
         let mut window = video_subsystem
             .window("test-bed", width, height)
             .position_centered()
+            .resizable()
             .build()
             .expect("Failed to create SDL window.");
 
@@ -364,11 +446,19 @@ impl Window for Sdl2Window {
 
         let event_pump = sdl_context.event_pump().unwrap();
 
+        // Use the first connected game controller, others are picked up when they are added
+        let controller_subsystem = sdl_context.game_controller().unwrap();
+        let controller = (0..controller_subsystem.num_joysticks().unwrap_or(0))
+            .filter(|&index| controller_subsystem.is_game_controller(index))
+            .find_map(|index| controller_subsystem.open(index).ok());
+
         Self {
             sdl_context,
             canvas,
             texture,
             event_pump,
+            controller_subsystem,
+            controller,
             time: 0.0,
             should_close: false,
             window_size: (width, height),
@@ -425,6 +515,9 @@ impl Window for Sdl2Window {
 
 
     fn update_software_renderer<'a>(&'a mut self, data: Option<SoftwareRenderData<'a>>) {
+        // The renderer is resized at the start of the next frame
+        let data = data.filter(|data| (data.width, data.height) == self.window_size);
+
         if let Some(data) = data {
             let t = span!("update_software_renderer");
             t.emit_color(0xFF0000);
@@ -463,6 +556,10 @@ impl Window for Sdl2Window {
     fn should_close(&mut self) -> bool {
         self.should_close
     }
+
+    fn size(&self) -> (usize, usize) {
+        (self.window_size.0 as _, self.window_size.1 as _)
+    }
 }
 
 // Note: ApplicationSettings struct is assumed to be defined elsewhere in your code.
@@ -0,0 +1,412 @@
+use crate::{Id, ItemState, Ui};
+use bitflags::bitflags;
+use std::cell::Ref;
+use std::collections::HashMap;
+
+/// What an element is to a screen reader, maps to the roles of platform APIs such as AT-SPI
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    Button,
+    /// On/off toggle
+    Switch,
+    Slider,
+    /// Picks one of several options, such as a cycler or a dropdown
+    ComboBox,
+    List,
+    ListItem,
+    ProgressBar,
+    TextInput,
+    Image,
+    Label,
+    /// Container for other nodes, such as a row of items
+    Group,
+}
+
+bitflags! {
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+    pub struct AccessStates: u32 {
+        const FOCUSED = 1 << 0;
+        const CHECKED = 1 << 1;
+        const SELECTED = 1 << 2;
+        const EXPANDED = 1 << 3;
+        const DISABLED = 1 << 4;
+    }
+}
+
+/// Semantic information for an element, exported in the [AccessTree]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessNode {
+    /// Id of the Clay element the node describes
+    pub id: u32,
+    /// Index of the parent node in [AccessTree::nodes]
+    pub parent: Option<usize>,
+    pub role: Role,
+    pub label: String,
+    /// Current value such as the text of a text field or the selected option
+    pub value: Option<String>,
+    pub states: AccessStates,
+    /// Screen rectangle of the element as [x0, y0, x1, y1], zero if it was outside the screen
+    pub bounds: [f32; 4],
+}
+
+impl AccessNode {
+    pub fn new(role: Role, label: &str) -> Self {
+        Self {
+            id: 0,
+            parent: None,
+            role,
+            label: label.to_string(),
+            value: None,
+            states: AccessStates::empty(),
+            bounds: [0.0; 4],
+        }
+    }
+
+    pub fn value(mut self, value: impl Into<String>) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.states.set(AccessStates::CHECKED, checked);
+        self
+    }
+
+    pub fn selected(mut self, selected: bool) -> Self {
+        self.states.set(AccessStates::SELECTED, selected);
+        self
+    }
+
+    pub fn expanded(mut self, expanded: bool) -> Self {
+        self.states.set(AccessStates::EXPANDED, expanded);
+        self
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.states.set(AccessStates::DISABLED, disabled);
+        self
+    }
+}
+
+/// All nodes declared during a frame in declaration order, parents come before their children
+#[derive(Debug, Default, Clone)]
+pub struct AccessTree {
+    nodes: Vec<AccessNode>,
+}
+
+impl AccessTree {
+    pub fn nodes(&self) -> &[AccessNode] {
+        &self.nodes
+    }
+
+    /// Children of the node at `parent`, `None` gives the top level nodes
+    pub fn children(&self, parent: Option<usize>) -> impl Iterator<Item = (usize, &AccessNode)> {
+        self.nodes.iter().enumerate().filter(move |(_, node)| node.parent == parent)
+    }
+
+    pub fn focused(&self) -> Option<&AccessNode> {
+        self.nodes.iter().find(|node| node.states.contains(AccessStates::FOCUSED))
+    }
+
+    pub fn find(&self, id: Id) -> Option<&AccessNode> {
+        self.nodes.iter().find(|node| node.id == id.id.id)
+    }
+}
+
+/// How urgent an announcement is
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Politeness {
+    /// Read when the screen reader is done with what it's currently reading
+    Polite,
+    /// Interrupts the screen reader
+    Assertive,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub text: String,
+    pub politeness: Politeness,
+}
+
+/// Display settings applied on top of the theme
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct DisplaySettings {
+    pub(crate) high_contrast: bool,
+    pub(crate) text_scale: f32,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            text_scale: 1.0,
+        }
+    }
+}
+
+/// Announcements kept when nobody takes them, older ones are dropped first
+const MAX_ANNOUNCEMENTS: usize = 32;
+
+#[derive(Default)]
+pub(crate) struct AccessibilityState {
+    /// Building the tree is skipped unless a screen reader bridge is connected
+    enabled: bool,
+    /// Tree that is being declared this frame
+    building: Vec<AccessNode>,
+    /// Tree of the last finished frame
+    tree: AccessTree,
+    /// Indices of the groups that are open while declaring
+    parents: Vec<usize>,
+    announcements: Vec<Announcement>,
+    pub(crate) display: DisplaySettings,
+}
+
+impl AccessibilityState {
+    pub(crate) fn begin(&mut self) {
+        self.building.clear();
+        self.parents.clear();
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.tree = AccessTree::default();
+            self.announcements.clear();
+        }
+    }
+
+    fn push(&mut self, id: Id, mut node: AccessNode) -> usize {
+        node.id = id.id.id;
+        node.parent = self.parents.last().copied();
+        self.building.push(node);
+        self.building.len() - 1
+    }
+
+    fn push_group(&mut self, id: Id, node: AccessNode) {
+        let index = self.push(id, node);
+        self.parents.push(index);
+    }
+
+    fn pop_group(&mut self) {
+        self.parents.pop();
+    }
+
+    fn announce(&mut self, text: &str, politeness: Politeness) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.announcements.len() >= MAX_ANNOUNCEMENTS {
+            self.announcements.remove(0);
+        }
+
+        self.announcements.push(Announcement {
+            text: text.to_string(),
+            politeness,
+        });
+    }
+
+    fn take_announcements(&mut self) -> Vec<Announcement> {
+        std::mem::take(&mut self.announcements)
+    }
+
+    /// Fills in the bounds from the layout and publishes the tree of the frame
+    pub(crate) fn end_frame(&mut self, item_states: &HashMap<u32, ItemState>) {
+        if !self.enabled {
+            return;
+        }
+
+        for node in &mut self.building {
+            if let Some(item) = item_states.get(&node.id) {
+                node.bounds = item.aabb.to_array();
+            }
+        }
+
+        std::mem::swap(&mut self.tree.nodes, &mut self.building);
+    }
+}
+
+impl Ui<'_> {
+    /// Enables building the [AccessTree] and collecting announcements. Off by default as
+    /// the labels are generated every frame.
+    pub fn set_accessibility_enabled(&self, enabled: bool) {
+        self.state.accessibility.borrow_mut().set_enabled(enabled);
+    }
+
+    pub fn accessibility_enabled(&self) -> bool {
+        self.state.accessibility.borrow().enabled
+    }
+
+    /// Describes the element with `id` to screen readers. `node` is only called while
+    /// accessibility is enabled. The focused state is set from the focus.
+    pub fn access_node<F: FnOnce() -> AccessNode>(&self, id: Id, node: F) {
+        if !self.accessibility_enabled() {
+            return;
+        }
+
+        let mut node = node();
+        node.states.set(AccessStates::FOCUSED, self.is_focused(id));
+        self.state.accessibility.borrow_mut().push(id, node);
+    }
+
+    /// Same as [Ui::access_node] with the nodes declared until the matching
+    /// [Ui::pop_access_group] as children
+    pub fn push_access_group<F: FnOnce() -> AccessNode>(&self, id: Id, node: F) {
+        if !self.accessibility_enabled() {
+            return;
+        }
+
+        let mut node = node();
+        node.states.set(AccessStates::FOCUSED, self.is_focused(id));
+
+        self.state.accessibility.borrow_mut().push_group(id, node);
+    }
+
+    pub fn pop_access_group(&self) {
+        self.state.accessibility.borrow_mut().pop_group();
+    }
+
+    /// Runs `f` with the nodes it declares as children of `node`
+    pub fn with_access_group<N, F>(&self, id: Id, node: N, f: F)
+    where
+        N: FnOnce() -> AccessNode,
+        F: FnOnce(&Ui),
+    {
+        self.push_access_group(id, node);
+        f(self);
+        self.pop_access_group();
+    }
+
+    /// Tree of the last finished frame, to be bridged to the screen reader of the platform
+    pub fn accessibility_tree(&self) -> Ref<'_, AccessTree> {
+        Ref::map(self.state.accessibility.borrow(), |accessibility| &accessibility.tree)
+    }
+
+    /// Queues text for the screen reader to read, such as what got selected. Only the latest
+    /// announcements are kept if they aren't taken with [Ui::take_announcements].
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        self.state.accessibility.borrow_mut().announce(text, politeness);
+    }
+
+    /// Returns the announcements queued since the last call
+    pub fn take_announcements(&self) -> Vec<Announcement> {
+        self.state.accessibility.borrow_mut().take_announcements()
+    }
+
+    /// Replaces the theme colors with high contrast colors, see [Theme::with_high_contrast]
+    ///
+    /// [Theme::with_high_contrast]: crate::Theme::with_high_contrast
+    pub fn set_high_contrast(&self, enabled: bool) {
+        self.state.accessibility.borrow_mut().display.high_contrast = enabled;
+    }
+
+    /// Multiplies the font sizes of the theme, such as 1.5 for large text
+    pub fn set_text_scale(&self, scale: f32) {
+        self.state.accessibility.borrow_mut().display.text_scale = scale.max(0.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clay_layout::bindings::{Clay_ElementId, Clay_String};
+    use glam::Vec4;
+
+    fn id(value: u32) -> Id {
+        Id {
+            id: Clay_ElementId {
+                id: value,
+                offset: 0,
+                baseId: 0,
+                stringId: Clay_String {
+                    length: 0,
+                    chars: std::ptr::null(),
+                },
+            },
+        }
+    }
+
+    fn enabled_state() -> AccessibilityState {
+        let mut state = AccessibilityState::default();
+        state.set_enabled(true);
+        state.begin();
+        state
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut state = enabled_state();
+
+        state.push_group(id(1), AccessNode::new(Role::List, "Demos"));
+        state.push(id(2), AccessNode::new(Role::ListItem, "First"));
+        state.push(id(3), AccessNode::new(Role::ListItem, "Second").selected(true));
+        state.pop_group();
+        state.push(id(4), AccessNode::new(Role::Button, "Settings"));
+
+        let mut item_states = HashMap::new();
+        item_states.insert(2, ItemState {
+            aabb: Vec4::new(1.0, 2.0, 3.0, 4.0),
+            ..Default::default()
+        });
+        state.end_frame(&item_states);
+
+        let tree = &state.tree;
+        let top: Vec<_> = tree.children(None).map(|(_, node)| node.label.as_str()).collect();
+        let items: Vec<_> = tree.children(Some(0)).map(|(_, node)| node.label.as_str()).collect();
+
+        assert_eq!(top, ["Demos", "Settings"]);
+        assert_eq!(items, ["First", "Second"]);
+        assert_eq!(tree.find(id(2)).unwrap().bounds, [1.0, 2.0, 3.0, 4.0]);
+        assert!(tree.find(id(3)).unwrap().states.contains(AccessStates::SELECTED));
+        assert_eq!(tree.focused(), None);
+    }
+
+    #[test]
+    fn test_focused() {
+        let mut state = enabled_state();
+        let mut node = AccessNode::new(Role::Switch, "Sound").checked(true);
+        node.states.insert(AccessStates::FOCUSED);
+
+        state.push(id(1), AccessNode::new(Role::Label, "Settings"));
+        state.push(id(2), node);
+        state.end_frame(&HashMap::new());
+
+        assert_eq!(state.tree.focused().map(|node| node.id), Some(2));
+    }
+
+    #[test]
+    fn test_announcements() {
+        let mut state = enabled_state();
+
+        for i in 0..MAX_ANNOUNCEMENTS + 2 {
+            state.announce(&i.to_string(), Politeness::Polite);
+        }
+
+        let announcements = state.take_announcements();
+        assert_eq!(announcements.len(), MAX_ANNOUNCEMENTS);
+        assert_eq!(announcements[0].text, "2");
+        assert!(state.take_announcements().is_empty());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut state = enabled_state();
+        state.push(id(1), AccessNode::new(Role::Button, "Play"));
+        state.end_frame(&HashMap::new());
+        state.announce("Play", Politeness::Polite);
+
+        // Disabling drops what was collected and nothing new is collected
+        state.set_enabled(false);
+        assert!(state.tree.nodes().is_empty());
+        assert!(state.announcements.is_empty());
+
+        state.begin();
+        state.push(id(1), AccessNode::new(Role::Button, "Play"));
+        state.end_frame(&HashMap::new());
+        state.announce("Play", Politeness::Polite);
+
+        assert!(state.tree.nodes().is_empty());
+        assert!(state.announcements.is_empty());
+    }
+}
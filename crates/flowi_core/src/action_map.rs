@@ -0,0 +1,293 @@
+use crate::input::{Input, Key};
+use crate::{ActionResponse, InputAction};
+use std::path::Path;
+use thiserror::Error;
+
+/// Number of actions in [InputAction]
+const ACTION_COUNT: usize = InputAction::Quit as usize + 1;
+
+const ALL_ACTIONS: [InputAction; ACTION_COUNT] = [
+    InputAction::MoveUp,
+    InputAction::MoveDown,
+    InputAction::MoveLeft,
+    InputAction::MoveRight,
+    InputAction::Select,
+    InputAction::Cancel,
+    InputAction::PageUp,
+    InputAction::PageDown,
+    InputAction::Back,
+    InputAction::Menu,
+    InputAction::Quit,
+];
+
+/// An input that can trigger an action. Gamepad buttons and analog stick directions are
+/// regular keys (such as [Key::GamepadDpadUp] and [Key::GamepadLStickUp])
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputBinding {
+    Key(Key),
+    MouseWheelUp,
+    MouseWheelDown,
+    MouseWheelLeft,
+    MouseWheelRight,
+}
+
+impl InputBinding {
+    /// Parses a binding by name. Keys use the [Key] variant names.
+    pub fn from_name(name: &str) -> Option<InputBinding> {
+        match name {
+            "MouseWheelUp" => Some(InputBinding::MouseWheelUp),
+            "MouseWheelDown" => Some(InputBinding::MouseWheelDown),
+            "MouseWheelLeft" => Some(InputBinding::MouseWheelLeft),
+            "MouseWheelRight" => Some(InputBinding::MouseWheelRight),
+            _ => Key::from_name(name).map(InputBinding::Key),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ActionMapError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {text}")]
+    Parse { line: usize, text: String },
+}
+
+/// Maps [InputAction]s to the inputs that trigger them. The map can be loaded from a config file
+/// so users can rebind the inputs. Each line in the file has the format
+///
+/// ```text
+/// # Comment
+/// MoveDown = DownArrow, GamepadDpadDown, GamepadLStickDown
+/// ```
+///
+/// Actions listed in the file replace the default bindings, other actions keep their defaults.
+#[derive(Debug, Clone)]
+pub struct ActionMap {
+    bindings: Vec<Vec<InputBinding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        use InputBinding as B;
+
+        let mut map = Self::empty();
+
+        map.bindings[InputAction::MoveUp as usize] = vec![
+            B::Key(Key::UpArrow),
+            B::Key(Key::GamepadDpadUp),
+            B::Key(Key::GamepadLStickUp),
+        ];
+        map.bindings[InputAction::MoveDown as usize] = vec![
+            B::Key(Key::DownArrow),
+            B::Key(Key::GamepadDpadDown),
+            B::Key(Key::GamepadLStickDown),
+        ];
+        map.bindings[InputAction::MoveLeft as usize] = vec![
+            B::Key(Key::LeftArrow),
+            B::Key(Key::GamepadDpadLeft),
+            B::Key(Key::GamepadLStickLeft),
+        ];
+        map.bindings[InputAction::MoveRight as usize] = vec![
+            B::Key(Key::RightArrow),
+            B::Key(Key::GamepadDpadRight),
+            B::Key(Key::GamepadLStickRight),
+        ];
+        map.bindings[InputAction::Select as usize] = vec![
+            B::Key(Key::Enter),
+            B::Key(Key::KeypadEnter),
+            B::Key(Key::Space),
+            B::Key(Key::GamepadFaceDown),
+        ];
+        // Escape closes the application so it isn't used for Cancel
+        map.bindings[InputAction::Cancel as usize] = vec![B::Key(Key::GamepadFaceRight)];
+        map.bindings[InputAction::PageUp as usize] =
+            vec![B::Key(Key::PageUp), B::Key(Key::GamepadL1)];
+        map.bindings[InputAction::PageDown as usize] =
+            vec![B::Key(Key::PageDown), B::Key(Key::GamepadR1)];
+        map.bindings[InputAction::Back as usize] =
+            vec![B::Key(Key::Backspace), B::Key(Key::GamepadBack)];
+        map.bindings[InputAction::Menu as usize] =
+            vec![B::Key(Key::Menu), B::Key(Key::GamepadStart)];
+        map.bindings[InputAction::Quit as usize] = vec![B::Key(Key::Escape)];
+
+        map
+    }
+}
+
+impl ActionMap {
+    /// Creates a map with the default bindings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a map without any bindings
+    pub fn empty() -> Self {
+        Self {
+            bindings: vec![Vec::new(); ACTION_COUNT],
+        }
+    }
+
+    /// Loads bindings from a config file on top of the defaults
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ActionMapError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    /// Parses bindings from a string on top of the defaults
+    pub fn parse(text: &str) -> Result<Self, ActionMapError> {
+        let mut map = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |text: String| ActionMapError::Parse {
+                line: index + 1,
+                text,
+            };
+
+            let Some((action_name, bindings)) = line.split_once('=') else {
+                return Err(parse_error(format!("Expected 'Action = Bindings', got '{}'", line)));
+            };
+
+            let action_name = action_name.trim();
+            let action = ALL_ACTIONS
+                .iter()
+                .find(|action| format!("{:?}", action) == action_name)
+                .copied()
+                .ok_or_else(|| parse_error(format!("Unknown action '{}'", action_name)))?;
+
+            let mut action_bindings = Vec::new();
+
+            for name in bindings.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                let binding = InputBinding::from_name(name)
+                    .ok_or_else(|| parse_error(format!("Unknown binding '{}'", name)))?;
+                action_bindings.push(binding);
+            }
+
+            map.bindings[action as usize] = action_bindings;
+        }
+
+        Ok(map)
+    }
+
+    /// Adds a binding to the action
+    pub fn bind(&mut self, action: InputAction, binding: InputBinding) {
+        let bindings = &mut self.bindings[action as usize];
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes all bindings for the action
+    pub fn clear(&mut self, action: InputAction) {
+        self.bindings[action as usize].clear();
+    }
+
+    pub fn bindings(&self, action: InputAction) -> &[InputBinding] {
+        &self.bindings[action as usize]
+    }
+
+    /// Returns [ActionResponse::Pressed] on the update any binding is pressed and again at the
+    /// key repeat rate while it's held. Between repeats the longest held time is returned.
+    pub(crate) fn response(&self, action: InputAction, input: &Input) -> ActionResponse {
+        let wheel = input.mouse_wheel();
+        let mut holding: Option<f32> = None;
+
+        for binding in self.bindings(action) {
+            let pressed = match *binding {
+                InputBinding::Key(key) => {
+                    if input.is_key_down(key) {
+                        let duration = input.key_down_duration(key);
+                        holding = Some(holding.map_or(duration, |h| h.max(duration)));
+                    }
+
+                    input.is_key_pressed_repeat(key)
+                }
+                InputBinding::MouseWheelUp => wheel.y > 0.0,
+                InputBinding::MouseWheelDown => wheel.y < 0.0,
+                InputBinding::MouseWheelLeft => wheel.x > 0.0,
+                InputBinding::MouseWheelRight => wheel.x < 0.0,
+            };
+
+            if pressed {
+                return ActionResponse::Pressed;
+            }
+        }
+
+        holding.map_or(ActionResponse::None, ActionResponse::Holding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let map = ActionMap::parse(
+            "# Comment\n\
+             MoveDown = S, GamepadDpadDown\n\
+             \n\
+             Select = MouseWheelLeft",
+        )
+        .unwrap();
+
+        assert_eq!(
+            map.bindings(InputAction::MoveDown),
+            &[InputBinding::Key(Key::S), InputBinding::Key(Key::GamepadDpadDown)]
+        );
+        assert_eq!(map.bindings(InputAction::Select), &[InputBinding::MouseWheelLeft]);
+        // Actions not in the config keep the defaults
+        assert_eq!(map.bindings(InputAction::MoveUp), ActionMap::new().bindings(InputAction::MoveUp));
+
+        // Quitting doesn't share a key with Cancel
+        let cancel = map.bindings(InputAction::Cancel);
+        assert!(map.bindings(InputAction::Quit).iter().all(|binding| !cancel.contains(binding)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            ActionMap::parse("MoveDown = NotAKey"),
+            Err(ActionMapError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            ActionMap::parse("\nJump = Space"),
+            Err(ActionMapError::Parse { line: 2, .. })
+        ));
+        assert!(ActionMap::parse("MoveDown").is_err());
+    }
+
+    #[test]
+    fn test_response() {
+        let map = ActionMap::new();
+        let mut input = Input::new();
+
+        input.add_key_event(Key::GamepadDpadDown, true);
+        input.update(0.0, 0.1);
+        assert_eq!(map.response(InputAction::MoveDown, &input), ActionResponse::Pressed);
+
+        input.update(0.1, 0.1);
+        assert_eq!(map.response(InputAction::MoveDown, &input), ActionResponse::Holding(0.1));
+        assert_eq!(map.response(InputAction::MoveUp, &input), ActionResponse::None);
+
+        // Passing the repeat delay repeats the press
+        input.update(0.2, 0.2);
+        assert_eq!(map.response(InputAction::MoveDown, &input), ActionResponse::Pressed);
+
+        input.add_key_event(Key::GamepadDpadDown, false);
+        input.add_mouse_wheel_event(0.0, 1.0);
+        input.update(0.4, 0.1);
+        assert_eq!(map.response(InputAction::MoveDown, &input), ActionResponse::None);
+        // The wheel scrolls instead of moving the focus by default
+        assert_eq!(map.response(InputAction::MoveUp, &input), ActionResponse::None);
+
+        let map = ActionMap::parse("MoveUp = MouseWheelUp").unwrap();
+        assert_eq!(map.response(InputAction::MoveUp, &input), ActionResponse::Pressed);
+    }
+}
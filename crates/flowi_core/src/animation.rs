@@ -0,0 +1,440 @@
+use crate::{ClayColor, Id, Ui};
+use fxhash::FxHasher;
+use glam::{Vec2, Vec4};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Distance to the target (per component) where an animation counts as done
+const SETTLE_EPSILON: f32 = 1e-3;
+
+/// Longest time step used when simulating springs, larger steps are split up to keep the
+/// simulation stable
+const MAX_SPRING_STEP: f32 = 1.0 / 120.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    /// Moves a fraction of the remaining distance each frame, halving the distance every
+    /// `1 / rate` seconds. Ignores the duration.
+    Exponential { rate: f32 },
+    Linear,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    /// Damped spring that can overshoot the target. Ignores the duration.
+    Spring { stiffness: f32, damping: f32 },
+}
+
+impl Easing {
+    /// Maps the time `t` (0.0 - 1.0) of an animation with a duration to its progress
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match *self {
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => {
+                let u = 1.0 - t;
+                1.0 - u * u * u
+            }
+            Easing::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::CubicInOut => {
+                let u = -2.0 * t + 2.0;
+                1.0 - u * u * u * 0.5
+            }
+            Easing::Linear | Easing::Exponential { .. } | Easing::Spring { .. } => t,
+        }
+    }
+}
+
+/// Moves `current` toward `target` using exponential easing
+#[inline]
+pub(crate) fn exponential_step(current: f32, target: f32, rate: f32, delta_time: f32) -> f32 {
+    current + (target - current) * (1.0 - 2f32.powf(-rate * delta_time))
+}
+
+/// How a property moves toward a new target. The completion callback is called once the
+/// target has been reached.
+pub struct Animation {
+    pub easing: Easing,
+    /// Length of animations with a timed easing curve in seconds
+    pub duration: f32,
+    /// Time in seconds before the animation starts moving toward a new target
+    pub delay: f32,
+    on_complete: Option<Box<dyn FnOnce()>>,
+}
+
+impl Animation {
+    pub fn new(easing: Easing) -> Self {
+        Self {
+            easing,
+            duration: 0.25,
+            delay: 0.0,
+            on_complete: None,
+        }
+    }
+
+    pub fn exponential(rate: f32) -> Self {
+        Self::new(Easing::Exponential { rate })
+    }
+
+    pub fn spring(stiffness: f32, damping: f32) -> Self {
+        Self::new(Easing::Spring { stiffness, damping })
+    }
+
+    #[inline]
+    pub fn duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    #[inline]
+    pub fn delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    #[inline]
+    pub fn on_complete<F: FnOnce() + 'static>(mut self, f: F) -> Self {
+        self.on_complete = Some(Box::new(f));
+        self
+    }
+}
+
+type Lanes = [f32; 4];
+
+/// Values that can be animated. They are animated as up to four independent components.
+pub trait Animatable: Copy {
+    fn to_lanes(self) -> Lanes;
+    fn from_lanes(lanes: Lanes) -> Self;
+}
+
+impl Animatable for f32 {
+    fn to_lanes(self) -> Lanes {
+        [self, 0.0, 0.0, 0.0]
+    }
+
+    fn from_lanes(lanes: Lanes) -> Self {
+        lanes[0]
+    }
+}
+
+impl Animatable for Vec2 {
+    fn to_lanes(self) -> Lanes {
+        [self.x, self.y, 0.0, 0.0]
+    }
+
+    fn from_lanes(lanes: Lanes) -> Self {
+        Vec2::new(lanes[0], lanes[1])
+    }
+}
+
+impl Animatable for Vec4 {
+    fn to_lanes(self) -> Lanes {
+        self.to_array()
+    }
+
+    fn from_lanes(lanes: Lanes) -> Self {
+        Vec4::from_array(lanes)
+    }
+}
+
+impl Animatable for ClayColor {
+    fn to_lanes(self) -> Lanes {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    fn from_lanes(lanes: Lanes) -> Self {
+        ClayColor::rgba(lanes[0], lanes[1], lanes[2], lanes[3])
+    }
+}
+
+struct Tween {
+    start: Lanes,
+    current: Lanes,
+    target: Lanes,
+    velocity: Lanes,
+    elapsed: f32,
+    easing: Easing,
+    duration: f32,
+    delay: f32,
+    finished: bool,
+    on_complete: Option<Box<dyn FnOnce()>>,
+    frame: u64,
+}
+
+impl Tween {
+    fn new(value: Lanes) -> Self {
+        Self {
+            start: value,
+            current: value,
+            target: value,
+            velocity: [0.0; 4],
+            elapsed: 0.0,
+            easing: Easing::Linear,
+            duration: 0.0,
+            delay: 0.0,
+            finished: true,
+            on_complete: None,
+            frame: 0,
+        }
+    }
+
+    /// Starts moving from the current value toward `target`. Springs keep their velocity.
+    fn retarget(&mut self, target: Lanes, animation: Animation) {
+        self.start = self.current;
+        self.target = target;
+        self.elapsed = 0.0;
+        self.easing = animation.easing;
+        self.duration = animation.duration;
+        self.delay = animation.delay;
+        self.finished = false;
+        self.on_complete = animation.on_complete;
+    }
+
+    fn is_settled(&self) -> bool {
+        (0..4).all(|i| {
+            (self.current[i] - self.target[i]).abs() < SETTLE_EPSILON
+                && self.velocity[i].abs() < SETTLE_EPSILON
+        })
+    }
+
+    /// Advances the animation and returns true when it reached the target during this step
+    fn step(&mut self, delta_time: f32) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        let prev_elapsed = self.elapsed;
+        self.elapsed += delta_time;
+
+        if self.elapsed < self.delay {
+            return false;
+        }
+
+        // Only the part of the step after the delay moves the value
+        let dt = (self.elapsed - self.delay).min(self.elapsed - prev_elapsed);
+
+        match self.easing {
+            Easing::Exponential { rate } => {
+                for i in 0..4 {
+                    self.current[i] = exponential_step(self.current[i], self.target[i], rate, dt);
+                }
+            }
+            Easing::Spring { stiffness, damping } => {
+                let steps = (dt / MAX_SPRING_STEP).ceil().max(1.0);
+                let step = dt / steps;
+
+                for _ in 0..steps as usize {
+                    for i in 0..4 {
+                        let force = stiffness * (self.target[i] - self.current[i]) - damping * self.velocity[i];
+                        self.velocity[i] += force * step;
+                        self.current[i] += self.velocity[i] * step;
+                    }
+                }
+            }
+            easing => {
+                let t = if self.duration > 0.0 {
+                    (self.elapsed - self.delay) / self.duration
+                } else {
+                    1.0
+                };
+                let progress = easing.apply(t);
+
+                for i in 0..4 {
+                    self.current[i] = self.start[i] + (self.target[i] - self.start[i]) * progress;
+                }
+
+                if t >= 1.0 {
+                    self.current = self.target;
+                }
+            }
+        }
+
+        if self.is_settled() {
+            self.current = self.target;
+            self.velocity = [0.0; 4];
+            self.finished = true;
+        }
+
+        self.finished
+    }
+}
+
+/// Animated properties keyed by item id and property name. Properties that aren't animated
+/// during a frame are removed at the end of it.
+#[derive(Default)]
+pub(crate) struct Animations {
+    tweens: HashMap<u64, Tween>,
+}
+
+impl Animations {
+    fn key(id: Id, property: &str) -> u64 {
+        let mut hasher = FxHasher::default();
+        id.id.id.hash(&mut hasher);
+        property.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Updates the property and returns its value. New properties start at `from`.
+    fn update(
+        &mut self,
+        key: u64,
+        from: Lanes,
+        target: Lanes,
+        animation: Animation,
+        delta_time: f32,
+        frame: u64,
+    ) -> (Lanes, Option<Box<dyn FnOnce()>>) {
+        let tween = self.tweens.entry(key).or_insert_with(|| Tween::new(from));
+
+        if tween.target != target {
+            tween.retarget(target, animation);
+        }
+
+        // Properties are only stepped once per frame even if they are queried more often
+        let mut on_complete = None;
+
+        if tween.frame != frame {
+            tween.frame = frame;
+
+            if tween.step(delta_time) {
+                on_complete = tween.on_complete.take();
+            }
+        }
+
+        (tween.current, on_complete)
+    }
+
+    fn is_animating(&self, key: u64) -> bool {
+        self.tweens.get(&key).is_some_and(|tween| !tween.finished)
+    }
+
+    pub(crate) fn end_frame(&mut self, frame: u64) {
+        self.tweens.retain(|_, tween| tween.frame == frame);
+    }
+}
+
+impl Ui<'_> {
+    /// Animates a property of the item toward `target` and returns the current value. The
+    /// first time a property is used it starts at the target. Changing the target restarts
+    /// the animation from the current value.
+    pub fn animate<T: Animatable>(&self, id: Id, property: &str, target: T, animation: Animation) -> T {
+        self.animate_from(id, property, target, target, animation)
+    }
+
+    /// Same as [Ui::animate] but a new property starts at `from`
+    pub fn animate_from<T: Animatable>(
+        &self,
+        id: Id,
+        property: &str,
+        from: T,
+        target: T,
+        animation: Animation,
+    ) -> T {
+        let state = &self.state;
+        let key = Animations::key(id, property);
+
+        // The borrow ends before the callback runs so it can start new animations
+        let (value, on_complete) = state.animations.borrow_mut().update(
+            key,
+            from.to_lanes(),
+            target.to_lanes(),
+            animation,
+            state.delta_time.get(),
+            state.current_frame.get(),
+        );
+
+        if let Some(on_complete) = on_complete {
+            on_complete();
+        }
+
+        T::from_lanes(value)
+    }
+
+    /// True while the property is moving toward its target
+    pub fn is_animating(&self, id: Id, property: &str) -> bool {
+        self.state.animations.borrow().is_animating(Animations::key(id, property))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Animates property 0 from 0.0 for a number of frames, continuing from `frame`
+    fn run(animations: &mut Animations, frame: &mut u64, target: f32, animation: &Animation, frames: u64) -> f32 {
+        let mut value = 0.0;
+
+        for _ in 0..frames {
+            *frame += 1;
+            let animation = Animation::new(animation.easing)
+                .duration(animation.duration)
+                .delay(animation.delay);
+            let (lanes, _) = animations.update(0, [0.0; 4], target.to_lanes(), animation, 0.1, *frame);
+            value = f32::from_lanes(lanes);
+        }
+
+        value
+    }
+
+    #[test]
+    fn test_easing_curves() {
+        for easing in [Easing::Linear, Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6);
+        }
+
+        assert!(Easing::CubicIn.apply(0.5) < 0.5);
+        assert!(Easing::CubicOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::CubicInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_timed_with_delay() {
+        let mut animations = Animations::default();
+        let mut frame = 0;
+        let animation = Animation::new(Easing::Linear).duration(1.0).delay(0.2);
+
+        // Waiting for the delay
+        assert_eq!(run(&mut animations, &mut frame, 10.0, &animation, 2), 0.0);
+        // Halfway through the duration
+        let value = run(&mut animations, &mut frame, 10.0, &animation, 5);
+        assert!((value - 5.0).abs() < 1e-4, "{}", value);
+
+        assert_eq!(run(&mut animations, &mut frame, 10.0, &animation, 20), 10.0);
+        assert!(!animations.is_animating(0));
+    }
+
+    #[test]
+    fn test_exponential_and_spring_settle() {
+        for animation in [Animation::exponential(8.0), Animation::spring(120.0, 15.0)] {
+            let mut animations = Animations::default();
+            assert_eq!(run(&mut animations, &mut 0, 1.0, &animation, 100), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_completion_and_cleanup() {
+        let mut animations = Animations::default();
+        let completed = Rc::new(Cell::new(0));
+        let counter = completed.clone();
+
+        let (_, on_complete) = animations.update(
+            0,
+            [0.0; 4],
+            [1.0; 4],
+            Animation::new(Easing::Linear).duration(0.1).on_complete(move || counter.set(counter.get() + 1)),
+            0.1,
+            1,
+        );
+
+        on_complete.unwrap()();
+        assert_eq!(completed.get(), 1);
+
+        animations.end_frame(1);
+        assert_eq!(animations.tweens.len(), 1);
+        animations.end_frame(2);
+        assert!(animations.tweens.is_empty());
+    }
+}
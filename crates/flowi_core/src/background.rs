@@ -0,0 +1,354 @@
+use crate::image::image::ImageInfo;
+use crate::io::io::{IoHandle, IoHandler};
+use crate::render_api::{DrawImage, GradientData, GradientKind, RenderCommand, RenderType};
+use crate::{ClayColor, Color, Ui};
+use std::f32::consts::TAU;
+
+const DEFAULT_FADE_TIME: f32 = 0.4;
+/// Time for one zoom in and out of [BackgroundMode::KenBurns]
+const KEN_BURNS_PERIOD: f32 = 30.0;
+const KEN_BURNS_ZOOM: f32 = 0.1;
+
+const DEFAULT_FALLBACK_TOP: ClayColor = ClayColor::rgba(32.0, 36.0, 48.0, 255.0);
+const DEFAULT_FALLBACK_BOTTOM: ClayColor = ClayColor::rgba(8.0, 9.0, 12.0, 255.0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackgroundMode {
+    /// Drawn at its size in the top right corner
+    AlignTopRight,
+    /// Drawn at its size in the center of the screen
+    Centered,
+    /// Scaled to cover the screen, the parts that don't fit are cropped
+    Cover,
+    /// Scaled to fit inside the screen
+    Contain,
+    /// Repeated at its size from the top left corner
+    Tiled,
+    /// Covers the screen with a slow pan and zoom
+    KenBurns,
+}
+
+impl BackgroundMode {
+    /// Modes that draw the image at its size can use the faster unscaled copy
+    fn is_unscaled(self) -> bool {
+        matches!(self, Self::AlignTopRight | Self::Centered | Self::Tiled)
+    }
+
+    /// Where to draw an image of `image_size` on the screen. `time` is the number of seconds
+    /// the image has been shown.
+    fn rects(self, image_size: (f32, f32), screen_size: (f32, f32), time: f32) -> Vec<[f32; 4]> {
+        let (iw, ih) = image_size;
+        let (sw, sh) = screen_size;
+
+        if iw <= 0.0 || ih <= 0.0 {
+            return Vec::new();
+        }
+
+        let centered = |w: f32, h: f32| {
+            let x0 = ((sw - w) * 0.5).floor();
+            let y0 = ((sh - h) * 0.5).floor();
+            [x0, y0, x0 + w, y0 + h]
+        };
+
+        match self {
+            Self::AlignTopRight => vec![[sw - iw, 0.0, sw, ih]],
+            Self::Centered => vec![centered(iw, ih)],
+            Self::Cover => {
+                let scale = (sw / iw).max(sh / ih);
+                vec![centered(iw * scale, ih * scale)]
+            }
+            Self::Contain => {
+                let scale = (sw / iw).min(sh / ih);
+                vec![centered(iw * scale, ih * scale)]
+            }
+            Self::Tiled => {
+                let mut rects = Vec::new();
+                let mut y = 0.0;
+
+                while y < sh {
+                    let mut x = 0.0;
+
+                    while x < sw {
+                        rects.push([x, y, x + iw, y + ih]);
+                        x += iw;
+                    }
+
+                    y += ih;
+                }
+
+                rects
+            }
+            Self::KenBurns => {
+                // Zooms in and back out over a period while panning from the top left
+                // towards the bottom right of the part that doesn't fit
+                let t = 0.5 - 0.5 * (time * TAU / KEN_BURNS_PERIOD).cos();
+                let scale = (sw / iw).max(sh / ih) * (1.0 + KEN_BURNS_ZOOM * t);
+                let (w, h) = (iw * scale, ih * scale);
+                let x0 = -(w - sw) * t;
+                let y0 = -(h - sh) * t;
+                vec![[x0, y0, x0 + w, y0 + h]]
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct BackgroundImage {
+    handle: IoHandle,
+    mode: BackgroundMode,
+}
+
+/// The parts of a loaded [ImageInfo] needed to draw it
+#[derive(Copy, Clone)]
+struct LoadedImage {
+    width: u32,
+    height: u32,
+    stride: u32,
+    data: *const i16,
+}
+
+impl LoadedImage {
+    fn get(io_handler: &mut IoHandler, image: Option<BackgroundImage>) -> Option<Self> {
+        let info = io_handler.get_loaded_as::<ImageInfo>(image?.handle)?;
+
+        Some(Self {
+            width: info.width as _,
+            height: info.height as _,
+            stride: info.stride as _,
+            data: info.data.as_ptr() as _,
+        })
+    }
+}
+
+/// The background image and the one it's fading from. The previous image is kept on screen
+/// until the new one has been loaded.
+pub(crate) struct BackgroundState {
+    current: Option<BackgroundImage>,
+    previous: Option<BackgroundImage>,
+    current_loaded: bool,
+    /// Progress of the fade from the previous image to the current one
+    fade: f32,
+    fade_time: f32,
+    /// Seconds since the current image was loaded
+    time: f32,
+    /// Time of the previous image when it was replaced so a moving image doesn't jump
+    previous_time: f32,
+    fallback: Option<(ClayColor, ClayColor)>,
+}
+
+impl Default for BackgroundState {
+    fn default() -> Self {
+        Self {
+            current: None,
+            previous: None,
+            current_loaded: false,
+            fade: 1.0,
+            fade_time: DEFAULT_FADE_TIME,
+            time: 0.0,
+            previous_time: 0.0,
+            fallback: Some((DEFAULT_FALLBACK_TOP, DEFAULT_FALLBACK_BOTTOM)),
+        }
+    }
+}
+
+impl BackgroundState {
+    fn set_image(&mut self, handle: IoHandle, mode: BackgroundMode) {
+        if let Some(current) = self.current.as_mut() {
+            if current.handle.0 == handle.0 {
+                current.mode = mode;
+                return;
+            }
+        }
+
+        // Keep what is on screen while the new image loads. If the current image never
+        // made it to the screen the previous one is still showing.
+        if self.current_loaded || self.previous.is_none() {
+            self.previous = self.current;
+            self.previous_time = self.time;
+        }
+
+        self.current = Some(BackgroundImage { handle, mode });
+        self.current_loaded = false;
+        self.fade = 0.0;
+        self.time = 0.0;
+    }
+
+    fn update(&mut self, delta_time: f32, current_loaded: bool) {
+        self.current_loaded = current_loaded;
+
+        if !current_loaded {
+            return;
+        }
+
+        self.time += delta_time;
+
+        self.fade = if self.fade_time > 0.0 {
+            (self.fade + delta_time / self.fade_time).min(1.0)
+        } else {
+            1.0
+        };
+
+        if self.fade >= 1.0 {
+            self.previous = None;
+        }
+    }
+
+    /// Adds the render commands for the background, called first in the frame so
+    /// everything else is drawn on top
+    pub(crate) fn push_commands(
+        &mut self,
+        io_handler: &mut IoHandler,
+        screen_size: (usize, usize),
+        delta_time: f32,
+        primitives: &mut Vec<RenderCommand>,
+    ) {
+        let current = LoadedImage::get(io_handler, self.current);
+        let previous = LoadedImage::get(io_handler, self.previous);
+        self.update(delta_time, current.is_some());
+
+        let screen_size = (screen_size.0 as f32, screen_size.1 as f32);
+
+        // The previous image or the fallback stays below until the fade is done
+        if self.fade < 1.0 || current.is_none() {
+            match (previous, self.previous) {
+                (Some(loaded), Some(image)) => {
+                    Self::push_image(loaded, image.mode, screen_size, self.previous_time, 1.0, primitives);
+                }
+                _ => {
+                    if let Some((top, bottom)) = self.fallback {
+                        primitives.push(RenderCommand {
+                            bounding_box: [0.0, 0.0, screen_size.0, screen_size.1],
+                            render_type: RenderType::DrawGradient(GradientData {
+                                end_color: Ui::color(bottom),
+                                kind: GradientKind::Linear { direction: [0.0, 1.0] },
+                                corners: [0.0; 4],
+                            }),
+                            color: Ui::color(top),
+                        });
+                    }
+                }
+            }
+        }
+
+        if let (Some(loaded), Some(image)) = (current, self.current) {
+            Self::push_image(loaded, image.mode, screen_size, self.time, self.fade, primitives);
+        }
+    }
+
+    fn push_image(
+        image: LoadedImage,
+        mode: BackgroundMode,
+        screen_size: (f32, f32),
+        time: f32,
+        opacity: f32,
+        primitives: &mut Vec<RenderCommand>,
+    ) {
+        let draw_image = DrawImage {
+            rounded_corners: [0.0; 4],
+            width: image.width,
+            height: image.height,
+            stride: image.stride,
+            handle: image.data,
+        };
+
+        for rect in mode.rects((image.width as f32, image.height as f32), screen_size, time) {
+            // The unscaled copy can't blend so fading images are drawn as regular images
+            let render_type = if mode.is_unscaled() && opacity >= 1.0 {
+                RenderType::DrawBackground(draw_image)
+            } else {
+                RenderType::DrawImage(draw_image)
+            };
+
+            primitives.push(RenderCommand {
+                bounding_box: rect,
+                render_type,
+                color: Color::new(255.0, 255.0, 255.0, (opacity * 255.0).round()),
+            });
+        }
+    }
+}
+
+impl Ui<'_> {
+    /// Sets the background image. The current background stays until the new image has
+    /// been loaded and then fades over to it.
+    pub fn set_background_image(&self, handle: IoHandle, mode: BackgroundMode) {
+        self.state.background.borrow_mut().set_image(handle, mode);
+    }
+
+    /// Removes the background image, the fallback gradient is shown instead
+    pub fn clear_background_image(&self) {
+        let mut background = self.state.background.borrow_mut();
+        background.current = None;
+        background.previous = None;
+        background.current_loaded = false;
+    }
+
+    /// Time in seconds to fade between background images, 0 switches instantly
+    pub fn set_background_fade_time(&self, seconds: f32) {
+        self.state.background.borrow_mut().fade_time = seconds.max(0.0);
+    }
+
+    /// Vertical gradient drawn when no background image is loaded, `None` draws nothing
+    pub fn set_background_fallback(&self, colors: Option<(ClayColor, ClayColor)>) {
+        self.state.background.borrow_mut().fallback = colors;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: (f32, f32) = (1280.0, 720.0);
+
+    #[test]
+    fn test_mode_rects() {
+        let image = (640.0, 480.0);
+
+        assert_eq!(BackgroundMode::AlignTopRight.rects(image, SCREEN, 0.0), vec![[640.0, 0.0, 1280.0, 480.0]]);
+        assert_eq!(BackgroundMode::Centered.rects(image, SCREEN, 0.0), vec![[320.0, 120.0, 960.0, 600.0]]);
+        assert_eq!(BackgroundMode::Cover.rects(image, SCREEN, 0.0), vec![[0.0, -120.0, 1280.0, 840.0]]);
+        assert_eq!(BackgroundMode::Contain.rects(image, SCREEN, 0.0), vec![[160.0, 0.0, 1120.0, 720.0]]);
+
+        let tiles = BackgroundMode::Tiled.rects(image, SCREEN, 0.0);
+        assert_eq!(tiles.len(), 2 * 2);
+        assert_eq!(tiles[3], [640.0, 480.0, 1280.0, 960.0]);
+    }
+
+    #[test]
+    fn test_ken_burns_covers_screen() {
+        let image = (640.0, 480.0);
+
+        for step in 0..=10 {
+            let [x0, y0, x1, y1] = BackgroundMode::KenBurns.rects(image, SCREEN, step as f32 * 3.0)[0];
+            assert!(x0 <= 0.0 && y0 <= 0.0 && x1 >= SCREEN.0 && y1 >= SCREEN.1 - 0.01);
+        }
+
+        // Starts a period at the cover size in the top left corner
+        assert_eq!(BackgroundMode::KenBurns.rects(image, SCREEN, 0.0), vec![[0.0, 0.0, 1280.0, 960.0]]);
+    }
+
+    #[test]
+    fn test_cross_fade() {
+        let mut background = BackgroundState::default();
+        background.set_image(IoHandle(1), BackgroundMode::Cover);
+        background.update(0.1, true);
+        background.update(1.0, true);
+        assert_eq!(background.fade, 1.0);
+        assert!(background.previous.is_none());
+
+        // The first image stays while the second loads
+        background.set_image(IoHandle(2), BackgroundMode::Cover);
+        background.update(0.1, false);
+        assert_eq!(background.previous.map(|image| image.handle.0), Some(1));
+        assert_eq!(background.fade, 0.0);
+
+        // An image that never loaded doesn't replace the one on screen
+        background.set_image(IoHandle(3), BackgroundMode::Cover);
+        assert_eq!(background.previous.map(|image| image.handle.0), Some(1));
+
+        background.update(DEFAULT_FADE_TIME * 0.5, true);
+        assert!((background.fade - 0.5).abs() < 1e-5);
+        background.update(DEFAULT_FADE_TIME, true);
+        assert!(background.previous.is_none());
+    }
+}
@@ -34,6 +34,20 @@ pub trait ContentProvider {
     fn get_item_id(&mut self, row: u64, col: u64) -> u64;
     fn get_item(&mut self, ui: &Ui, visible: ItemVisibility, row: u64, col: u64) -> Item;
     fn get_column_count(&mut self, ui: &Ui, row: u64) -> u64;
+    /// Number of rows, the selector doesn't move past the last one
+    fn get_row_count(&mut self, ui: &Ui) -> u64;
     /// Get the name of the row
     fn get_row_name(&mut self, ui: &Ui, row: u64) -> &str;
+    /// Text read by screen readers for the item, such as the title and the author. `None`
+    /// if the item hasn't been loaded yet.
+    fn get_item_label(&mut self, _ui: &Ui, _row: u64, _col: u64) -> Option<String> {
+        None
+    }
+    /// Images to cycle through in the background once the item has stayed selected for a
+    /// while, starting with its background image. This is only called for the selected item
+    /// so the provider can wait with loading the extra images until it's called. Images that
+    /// haven't loaded yet are skipped.
+    fn get_preview_images(&mut self, _ui: &Ui, _row: u64, _col: u64) -> &[IoHandle] {
+        &[]
+    }
 }
@@ -1,5 +1,8 @@
+use crate::accessibility::{AccessNode, Politeness, Role};
 use crate::content_provider::{ContentProvider, Item, ItemVisibility};
-use crate::{fixed, grow, Alignment, BackgroundMode, ClayColor, Declaration, LayoutAlignmentX, LayoutAlignmentY, LayoutDirection, LoadPriority, Padding, Ui};
+use crate::animation::exponential_step;
+use crate::theme::{FontSize, Size};
+use crate::{fixed, grow, ActionResponse, Alignment, BackgroundMode, ClayColor, Declaration, ImageLoadState, InputAction, LayoutAlignmentX, LayoutAlignmentY, LayoutDirection, LoadPriority, Padding, ThemeColor, Ui};
 /// This module is responsible for displaying a list of items that can be selected. It acts very
 /// similar to how movie based selectors for many streaming services works. The user can scroll
 /// through a list of items and select one of them. The selected item will be displayed in a larger
@@ -28,25 +31,76 @@ struct ItemState {
     hot: f32,
 }
 
-#[allow(dead_code)]
-const UNSELECTED_IMAGE_SIZE: (f32, f32) = (250.0, 187.5);
 const ENTRY_ID: &str = "selection_entry";
+/// Seconds an item has to stay selected before its preview images are shown
+const PREVIEW_DELAY: f32 = 1.5;
+/// Seconds each preview image is shown before cross-fading to the next one
+const PREVIEW_INTERVAL: f32 = 5.0;
+
+/// Cycles the background through the preview images of the selected item
+#[derive(Debug, Default)]
+struct Preview {
+    /// Item the preview is for, changing it restarts the preview
+    item_id: u64,
+    /// Time the item has been selected
+    selected_time: f32,
+    /// Time the current image has been shown
+    shown_time: f32,
+    /// Index of the preview image in the background
+    index: usize,
+}
+
+impl Preview {
+    /// Updates the time the item has been selected and returns true once the preview should
+    /// be shown
+    fn update(&mut self, item_id: u64, dt: f32) -> bool {
+        if item_id != self.item_id {
+            *self = Self { item_id, ..Self::default() };
+        }
+
+        self.selected_time += dt;
+        self.selected_time >= PREVIEW_DELAY
+    }
+
+    /// Returns the index of the image to switch to when the current one has been shown long
+    /// enough. Images that aren't loaded are skipped.
+    fn next_image(&mut self, dt: f32, count: usize, is_loaded: impl Fn(usize) -> bool) -> Option<usize> {
+        self.shown_time += dt;
+
+        if count < 2 || self.shown_time < PREVIEW_INTERVAL {
+            return None;
+        }
+
+        let next = (1..count).map(|step| (self.index + step) % count).find(|&index| is_loaded(index))?;
+        self.index = next;
+        self.shown_time = 0.0;
+        Some(next)
+    }
+}
 
 pub struct ContentSelector {
     /// Selected item in row, col format
     selected_item: RowColumn,
     /// If we are about to transition to a new row this is the row we are transitioning to.
     transition_row: u64,
-    /// Temporary time when we decide to transition to a new row
+    /// The transition scrolls down to the previous row instead of up to the next one
+    transition_up: bool,
+    /// Time since the selector was created
     temp_time: f32,
     /// The scroll value of the content selector
     scroll_value: f32,
+    /// Horizontal scroll value of the selected row, keeps the selected item in view
+    row_scroll: f32,
     /// The scroll value of the content selector
     curve_transition: f32,
     /// Fade out value during transition
     row_transition_fade_out: f32,
     /// Current state of the content selector
     state: State,
+    /// Preview images of the selected item
+    preview: Preview,
+    /// Item that was last announced to screen readers in (row, col) format
+    announced_item: Option<(u64, u64)>,
     //item_states: HashMap<u64, ItemState>,
 }
 
@@ -55,11 +109,15 @@ impl ContentSelector {
         ContentSelector {
             selected_item: RowColumn::default(),
             transition_row: 0,
+            transition_up: false,
             temp_time: 0.0,
             scroll_value: 0.0,
+            row_scroll: 0.0,
             row_transition_fade_out: 1.0,
             curve_transition: 0.0,
             state: State::Init,
+            preview: Preview::default(),
+            announced_item: None,
             //item_states: HashMap::new(),
         }
     }
@@ -73,9 +131,16 @@ impl ContentSelector {
         }
 
         let id = ui.id_index(name, row as _);
+        let theme = ui.theme();
+        let color = theme.color(ThemeColor::Text);
+
+        let row_scroll = if row == self.selected_item.row { self.row_scroll } else { 0.0 };
+        ui.update_scroll(id, (row_scroll, 0.0));
 
-        ui.text_with_layout(name, 36,
-            ClayColor::rgba(255.0, 255.0, 255.0, 255.0 * opacity),
+        ui.push_access_group(id, || AccessNode::new(Role::List, name));
+
+        ui.text_with_layout(name, theme.font_size(FontSize::Heading),
+            ClayColor::rgba(color.r, color.g, color.b, color.a * opacity),
             &Declaration::new()
                 .layout()
                     .width(grow!())
@@ -85,11 +150,11 @@ impl ContentSelector {
             .id(id)
             .layout()
                 .width(grow!())
-                .height(fixed!(360.0))
+                .height(fixed!(theme.size(Size::SelectorRowHeight)))
                 .direction(LayoutDirection::LeftToRight)
                 .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Center))
-                .child_gap(64)
-                .padding(Padding::horizontal(20))
+                .child_gap(ui.scaled(64.0) as u16)
+                .padding(Padding::horizontal(ui.scaled(20.0) as u16))
             .end()
             .scroll(true, false), |ui|
        {
@@ -135,8 +200,84 @@ impl ContentSelector {
                 }
 
                 draw_selection_entry(self.temp_time, ui, &item, is_selected, opacity);
+
+                ui.access_node(id, || {
+                    let label = provider.get_item_label(ui, row, col).unwrap_or_default();
+                    AccessNode::new(Role::ListItem, &label).selected(is_selected)
+                });
             }
        });
+
+        ui.pop_access_group();
+    }
+
+    /// Distance between the tops of two rows, which is what a row transition scrolls. Taken
+    /// from the last layout so it follows the heading size and the ui scale.
+    fn row_step(&self, ui: &Ui, provider: &mut dyn ContentProvider) -> f32 {
+        let mut bounds = |row: u64| {
+            let name = provider.get_row_name(ui, row);
+            ui.state.layout.bounding_box(ui.id_index(name, row as _))
+        };
+
+        let row = self.selected_item.row;
+
+        match (bounds(row), bounds(row + 1)) {
+            (Some(first), Some(second)) if second.y > first.y => second.y - first.y,
+            _ => {
+                let theme = ui.theme();
+                theme.size(Size::SelectorRowHeight) + theme.font_size(FontSize::Heading) as f32
+            }
+        }
+    }
+
+    /// Horizontal scroll of the selected row that brings the selected item into view, or the
+    /// current scroll if the item is already visible. Taken from the last layout like `row_step`.
+    fn column_scroll(&self, ui: &Ui, provider: &mut dyn ContentProvider) -> f32 {
+        let RowColumn { row, col, .. } = self.selected_item;
+        let item_id = provider.get_item_id(row, col);
+        let name = provider.get_row_name(ui, row);
+        let layout = &ui.state.layout;
+
+        let (Some(row_bounds), Some(item_bounds)) = (
+            layout.bounding_box(ui.id_index(name, row as _)),
+            layout.bounding_box(ui.id_index(ENTRY_ID, (item_id + 10000) as _)),
+        ) else {
+            return self.row_scroll;
+        };
+
+        // Position of the item in the row when it isn't scrolled
+        let x = item_bounds.x - row_bounds.x - self.row_scroll;
+        let margin = ui.scaled(20.0);
+        let min_scroll = row_bounds.width - margin - (x + item_bounds.width);
+        let max_scroll = margin - x;
+
+        self.row_scroll.max(min_scroll).min(max_scroll).min(0.0)
+    }
+
+    /// Announces the selected item to screen readers once its label is available, such as
+    /// "Row Amiga Demo, item 3 of 12: 3D Demo II by Anarchy"
+    fn announce_selection(&mut self, ui: &Ui, provider: &mut dyn ContentProvider) {
+        let RowColumn { row, col, .. } = self.selected_item;
+
+        if !ui.accessibility_enabled() || self.announced_item == Some((row, col)) {
+            return;
+        }
+
+        let Some(label) = provider.get_item_label(ui, row, col) else {
+            return;
+        };
+
+        let count = provider.get_column_count(ui, row);
+        let row_name = provider.get_row_name(ui, row);
+        let text = ui.localize_args("selector.announce", &[
+            ("row", &row_name),
+            ("index", &(col + 1)),
+            ("count", &count),
+            ("label", &label),
+        ]);
+
+        ui.announce(text, Politeness::Assertive);
+        self.announced_item = Some((row, col));
     }
 
     #[rustfmt::skip]
@@ -150,12 +291,14 @@ impl ContentSelector {
                 .width(grow!())
                 .height(grow!())
                 .direction(LayoutDirection::TopToBottom)
-                .padding(Padding::horizontal(20))
+                .padding(Padding::horizontal(ui.scaled(20.0) as u16))
                 .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Top))
             .end()
             .scroll(false, true), |ui|
         {
-            self.draw_row(ui, provider, self.selected_item.row, self.row_transition_fade_out);
+            // Moving up the new row is already the first one and fades in instead of out
+            let opacity = if self.transition_up { 1.0 - self.row_transition_fade_out } else { self.row_transition_fade_out };
+            self.draw_row(ui, provider, self.selected_item.row, opacity);
             self.draw_row(ui, provider, self.selected_item.row + 1, 1.0);
             self.draw_row(ui, provider, self.selected_item.row + 2, 1.0);
         });
@@ -163,14 +306,18 @@ impl ContentSelector {
         let dt = ui.delta_time();
         self.temp_time += dt;
 
-        //let down = false; //ui.input().is_action_active(ActionResponse::Down);
-        // Simulate a transition to a new row ever 5 seconds
-        let down = if self.temp_time > 5.0 {
-            self.temp_time = 0.0;
-            true
-        } else {
-            false
-        };
+        // Swiping up or down flicks between the rows and swiping sideways moves between the items
+        let swipe = ui.gestures().iter().find_map(|gesture| gesture.swipe_direction()).unwrap_or_default();
+
+        // The selector moves between its items itself, keep the focus navigation out of it
+        let selected_id = provider.get_item_id(self.selected_item.row, self.selected_item.col);
+        ui.capture_navigation(ui.id_index(ENTRY_ID, selected_id as _), true, true);
+
+        let pressed = |action| ui.get_input_action(action) == ActionResponse::Pressed;
+        let up = pressed(InputAction::MoveUp) || swipe.y > 0.0;
+        let down = pressed(InputAction::MoveDown) || swipe.y < 0.0;
+        let left = pressed(InputAction::MoveLeft) || swipe.x > 0.0;
+        let right = pressed(InputAction::MoveRight) || swipe.x < 0.0;
 
         if self.state == State::Init {
             let item_id = provider.get_item_id(0, 0);
@@ -180,29 +327,85 @@ impl ContentSelector {
         }
 
         if self.state == State::RowTransition {
-            let anime_rate = 1.0 - 2f32.powf(-8.0 * dt);
-            self.curve_transition += anime_rate * (1.0 - self.curve_transition);
+            self.curve_transition = exponential_step(self.curve_transition, 1.0, 8.0, dt);
 
             self.row_transition_fade_out -= dt;
-            self.scroll_value = -(self.curve_transition * 400.0);
+
+            let scroll = if self.transition_up { 1.0 - self.curve_transition } else { self.curve_transition };
+            self.scroll_value = -(scroll * self.row_step(ui, provider));
 
             if self.row_transition_fade_out <= 0.0 {
                 self.row_transition_fade_out = 1.0;
                 self.state = State::Idle;
                 self.scroll_value = 0.0;
                 self.curve_transition = 0.0;
+                self.transition_up = false;
                 self.selected_item.row = self.transition_row;
+                self.selected_item.col = 0;
+                self.row_scroll = 0.0;
+            }
+        }
+
+        if self.state == State::Idle && (left || right) {
+            let column_count = provider.get_column_count(ui, self.selected_item.row);
+            let col = self.selected_item.col;
+            let new_col = if left { col.saturating_sub(1) } else { (col + 1).min(column_count.saturating_sub(1)) };
+
+            if new_col != col {
+                let row = self.selected_item.row;
+                let item_id = provider.get_item_id(row, new_col);
+                let item = provider.get_item(ui, ItemVisibility::Selected, row, new_col);
+                ui.set_background_image(item.background_image, BackgroundMode::AlignTopRight);
+                ui.set_focus_id(ui.id_index(ENTRY_ID, item_id as _));
+                self.selected_item.col = new_col;
             }
         }
 
         // TODO: Handle the case if we already are in a transition state
-        if self.state == State::Idle && down {
-            self.transition_row = self.selected_item.row + 1;
+        let row = self.selected_item.row;
+        let row_count = provider.get_row_count(ui);
+        let can_move_down = down && row + 1 < row_count;
+        let can_move_up = up && row > 0;
+
+        if self.state == State::Idle && (can_move_down || can_move_up) {
+            self.transition_row = if can_move_down { row + 1 } else { row - 1 };
             let item_id = provider.get_item_id(self.transition_row, 0);
             let item = provider.get_item(ui, ItemVisibility::Hidden, self.transition_row, 0);
             ui.set_background_image(item.background_image, BackgroundMode::AlignTopRight);
             ui.set_focus_id(ui.id_index(ENTRY_ID, item_id as _));
             self.state = State::RowTransition;
+
+            // The previous row is drawn first right away and is scrolled into view
+            if can_move_up {
+                self.transition_up = true;
+                self.scroll_value = -self.row_step(ui, provider);
+                self.selected_item.row = self.transition_row;
+                self.selected_item.col = 0;
+                self.row_scroll = 0.0;
+            }
+        }
+
+        // Scroll the selected row the same way as the rows so the selected item stays in view
+        if self.state == State::Idle {
+            let target = self.column_scroll(ui, provider);
+            self.row_scroll = exponential_step(self.row_scroll, target, 8.0, dt);
+        }
+
+        // Cycle the background through the previews once the item has been selected for a while
+        if self.state == State::Idle {
+            self.announce_selection(ui, provider);
+
+            let RowColumn { row, col, .. } = self.selected_item;
+            let item_id = provider.get_item_id(row, col);
+
+            if self.preview.update(item_id, dt) {
+                let images = provider.get_preview_images(ui, row, col);
+                let is_loaded = |index: usize| ui.image_load_state(images[index]) == ImageLoadState::Loaded;
+
+                if let Some(index) = self.preview.next_image(dt, images.len(), is_loaded) {
+                    ui.set_background_image(images[index], BackgroundMode::AlignTopRight);
+                }
+            }
         }
     }
 }
@@ -211,8 +414,10 @@ impl ContentSelector {
 #[allow(dead_code)]
 #[rustfmt::skip]
 fn draw_selection_entry(_time: f32, ui: &Ui, item: &Item, _is_selected: bool, opacity: f32) {
-    // TODO: Get the data from settings structs as this is affected by the screen size
-    let mut size = (250.0, 187.5);
+    let theme = ui.theme();
+    let grow = theme.size(Size::ItemFocusGrow);
+    let mut size = (theme.size(Size::ItemWidth), theme.size(Size::ItemHeight));
+    let unselected_size = size;
     let id = ui.id_index(ENTRY_ID, item.id as _);
 
     ui.with_layout(&Declaration::new()
@@ -224,8 +429,31 @@ fn draw_selection_entry(_time: f32, ui: &Ui, item: &Item, _is_selected: bool, op
         .end(), |ui|
     {
         if let Some(item_state) = ui.item_state(id) {
-            size = (250.0 + (item_state.active * 40.0),  187.5 + (item_state.active * 40.0));
+            size = (unselected_size.0 + item_state.active * grow, unselected_size.1 + item_state.active * grow);
         }
         ui.image_with_opts(id, item.image, opacity, size);
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_cycle() {
+        let mut preview = Preview::default();
+
+        // Nothing happens until the item has been selected for a while
+        assert!(!preview.update(1, 1.0));
+        assert!(preview.update(1, PREVIEW_DELAY));
+
+        // Image 1 is still loading so it's skipped
+        assert_eq!(preview.next_image(PREVIEW_INTERVAL - 1.0, 3, |_| true), None);
+        assert_eq!(preview.next_image(1.0, 3, |index| index != 1), Some(2));
+        assert_eq!(preview.next_image(PREVIEW_INTERVAL, 3, |_| true), Some(0));
+
+        // Selecting another item restarts the preview
+        assert!(!preview.update(2, 0.1));
+        assert_eq!(preview.index, 0);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::theme::{FontSize, Radius, Spacing};
+use crate::{fixed, ClayColor, Declaration, LayoutDirection, Padding, ThemeColor, Ui};
+use clay_layout::elements::{FloatingAttachPointType, FloatingAttachToElement, PointerCaptureMode};
+use std::collections::VecDeque;
+
+const DEBUG_Z_INDEX: i16 = 500;
+/// Number of frames the frame timing is averaged over
+const FRAME_TIME_SAMPLES: usize = 60;
+
+const HIGHLIGHT_COLOR: ClayColor = ClayColor::rgba(255.0, 0.0, 255.0, 255.0);
+const HIGHLIGHT_FILL_COLOR: ClayColor = ClayColor::rgba(255.0, 0.0, 255.0, 40.0);
+const PANEL_COLOR: ClayColor = ClayColor::rgba(0.0, 0.0, 0.0, 200.0);
+
+/// Counters shown in the debug overlay. Updated at the end of each frame.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct DebugStats {
+    /// Render commands generated by the layout
+    pub layout_commands: usize,
+    /// Layout commands skipped as they were empty or outside the screen
+    pub culled_commands: usize,
+    /// Render commands sent to the renderer, a layout command can become several
+    pub render_commands: usize,
+    /// Generated text buffers in the text cache
+    pub cached_texts: usize,
+    /// Entries in the text measure cache
+    pub measured_texts: u32,
+    /// Loads waiting to be started by the io handler
+    pub io_queued: usize,
+    /// Loads that have been started but not finished
+    pub io_inflight: usize,
+    /// Average frame time in seconds
+    pub frame_time: f32,
+    /// Longest frame time in seconds
+    pub max_frame_time: f32,
+}
+
+#[derive(Default)]
+pub(crate) struct DebugState {
+    pub(crate) enabled: bool,
+    pub(crate) stats: DebugStats,
+    frame_times: VecDeque<f32>,
+}
+
+impl DebugState {
+    pub(crate) fn add_frame_time(&mut self, delta_time: f32) {
+        if self.frame_times.len() == FRAME_TIME_SAMPLES {
+            self.frame_times.pop_front();
+        }
+
+        self.frame_times.push_back(delta_time);
+
+        let total: f32 = self.frame_times.iter().sum();
+        self.stats.frame_time = total / self.frame_times.len() as f32;
+        self.stats.max_frame_time = self.frame_times.iter().copied().fold(0.0, f32::max);
+    }
+}
+
+impl Ui<'_> {
+    /// Shows a panel with frame stats, highlights the item under the pointer and opens
+    /// Clay's layout inspector with the element tree on the right side of the screen.
+    pub fn set_debug_overlay(&self, enabled: bool) {
+        self.state.debug.borrow_mut().enabled = enabled;
+        self.state.layout.enable_debug_mode(enabled);
+    }
+
+    pub fn toggle_debug_overlay(&self) {
+        self.set_debug_overlay(!self.is_debug_overlay_enabled());
+    }
+
+    pub fn is_debug_overlay_enabled(&self) -> bool {
+        self.state.debug.borrow().enabled
+    }
+
+    /// Stats of the last frame. These are collected even if the overlay isn't shown.
+    pub fn debug_stats(&self) -> DebugStats {
+        self.state.debug.borrow().stats
+    }
+
+    /// Declares the stats panel and the hover highlight, called at the end of the frame so
+    /// they are on top
+    #[rustfmt::skip]
+    pub(crate) fn draw_debug_overlay(&self) {
+        let state = &self.state;
+
+        if !state.debug.borrow().enabled {
+            return;
+        }
+
+        let stats = self.debug_stats();
+        let theme = self.theme();
+
+        // The innermost item under the pointer, using the bounding boxes of the last frame
+        let mouse_pos = state.input.borrow().mouse_pos();
+        let hovered = state.item_states.borrow().iter()
+            .filter(|(_, item)| {
+                mouse_pos.x >= item.aabb.x && mouse_pos.y >= item.aabb.y &&
+                mouse_pos.x < item.aabb.z && mouse_pos.y < item.aabb.w
+            })
+            .map(|(id, item)| (*id, item.aabb))
+            .min_by(|(_, a), (_, b)| {
+                let area = |r: &glam::Vec4| (r.z - r.x) * (r.w - r.y);
+                area(a).total_cmp(&area(b))
+            });
+
+        let focus = state.focus_id.get()
+            .map_or_else(|| "none".to_owned(), |id| format!("{:08x}", id.id.id));
+
+        let mut lines = vec![
+            format!("Frame {:.2} ms (max {:.2} ms)", stats.frame_time * 1000.0, stats.max_frame_time * 1000.0),
+            format!("Layout commands {} ({} culled)", stats.layout_commands, stats.culled_commands),
+            format!("Render commands {}", stats.render_commands),
+            format!("Text cache {} (measured {})", stats.cached_texts, stats.measured_texts),
+            format!("Io queued {} inflight {}", stats.io_queued, stats.io_inflight),
+            format!("Focus {}", focus),
+        ];
+
+        if let Some((id, aabb)) = hovered {
+            lines.push(format!(
+                "Hovered {:08x} at {:.0},{:.0} size {:.0}x{:.0}",
+                id, aabb.x, aabb.y, aabb.z - aabb.x, aabb.w - aabb.y
+            ));
+
+            // Anonymous floating elements at the root would get the same id
+            state.layout.with(Declaration::new()
+                .id(state.layout.id("Flowi__DebugHighlight"))
+                .layout()
+                    .width(fixed!(aabb.z - aabb.x))
+                    .height(fixed!(aabb.w - aabb.y))
+                .end()
+                .floating()
+                    .attach_to(FloatingAttachToElement::Root)
+                    .offset((aabb.x, aabb.y).into())
+                    .z_index(DEBUG_Z_INDEX)
+                    .pointer_capture_mode(PointerCaptureMode::Passthrough)
+                .end()
+                .border().all_directions(1).color(HIGHLIGHT_COLOR).end()
+                .background_color(HIGHLIGHT_FILL_COLOR), |_| {});
+        }
+
+        let margin = theme.spacing(Spacing::Small) as f32;
+
+        state.layout.with(Declaration::new()
+            .id(state.layout.id("Flowi__DebugPanel"))
+            .layout()
+                .direction(LayoutDirection::TopToBottom)
+                .padding(Padding::all(theme.spacing(Spacing::Medium)))
+            .end()
+            .floating()
+                .attach_to(FloatingAttachToElement::Root)
+                .attach_points(FloatingAttachPointType::LeftTop, FloatingAttachPointType::LeftTop)
+                .offset((margin, margin).into())
+                .z_index(DEBUG_Z_INDEX)
+                .pointer_capture_mode(PointerCaptureMode::Passthrough)
+            .end()
+            .corner_radius().all(theme.radius(Radius::Small)).end()
+            .background_color(PANEL_COLOR), |_| {
+                for line in lines {
+                    let line = self.frame_str(&line);
+                    self.themed_text(line, FontSize::Small, theme.color(ThemeColor::Text));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_time() {
+        let mut debug = DebugState::default();
+        debug.add_frame_time(0.01);
+        debug.add_frame_time(0.03);
+        assert!((debug.stats.frame_time - 0.02).abs() < 1e-6);
+        assert_eq!(debug.stats.max_frame_time, 0.03);
+
+        // Only the last samples are used
+        for _ in 0..FRAME_TIME_SAMPLES {
+            debug.add_frame_time(0.005);
+        }
+
+        assert!((debug.stats.frame_time - 0.005).abs() < 1e-6);
+        assert_eq!(debug.stats.max_frame_time, 0.005);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::render_api::{BlurData, DrawRectRoundedData, GradientData, GradientKind, ShadowData};
+use crate::{ClayColor, Color, CustomDraw, Declaration, RenderCommand, RenderType, Ui};
+
+/// Fill of an element that replaces its background color
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Gradient {
+    /// `angle` is in degrees where 0 goes from the bottom to the top and 90 from the left
+    /// to the right
+    Linear {
+        angle: f32,
+        start: ClayColor,
+        end: ClayColor,
+    },
+    /// `center` is relative to the element where (0.5, 0.5) is the middle. The gradient
+    /// ends at the corner furthest from the center.
+    Radial {
+        center: (f32, f32),
+        start: ClayColor,
+        end: ClayColor,
+    },
+}
+
+/// Soft shadow drawn below an element
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shadow {
+    pub color: ClayColor,
+    pub offset: (f32, f32),
+    /// Distance over which the shadow fades out
+    pub blur: f32,
+    /// Grows the shadow on all sides before it's blurred
+    pub spread: f32,
+}
+
+impl Shadow {
+    pub fn new(color: ClayColor) -> Self {
+        Self {
+            color,
+            offset: (0.0, 0.0),
+            blur: 0.0,
+            spread: 0.0,
+        }
+    }
+
+    pub fn offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    pub fn blur(mut self, blur: f32) -> Self {
+        self.blur = blur.max(0.0);
+        self
+    }
+
+    pub fn spread(mut self, spread: f32) -> Self {
+        self.spread = spread;
+        self
+    }
+}
+
+/// Effects drawn for an element, added to a [Declaration] with [DeclarationEffects::effects]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct BoxEffects {
+    pub gradient: Option<Gradient>,
+    pub shadow: Option<Shadow>,
+    /// Radius of the blur applied to what is below the element, 0 disables it
+    pub backdrop_blur: f32,
+}
+
+impl BoxEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn linear_gradient(mut self, angle: f32, start: ClayColor, end: ClayColor) -> Self {
+        self.gradient = Some(Gradient::Linear { angle, start, end });
+        self
+    }
+
+    pub fn radial_gradient(mut self, center: (f32, f32), start: ClayColor, end: ClayColor) -> Self {
+        self.gradient = Some(Gradient::Radial { center, start, end });
+        self
+    }
+
+    pub fn drop_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    pub fn backdrop_blur(mut self, radius: f32) -> Self {
+        self.backdrop_blur = radius.max(0.0);
+        self
+    }
+
+    /// Adds the render commands for an element at `aabb`. `background` and `corners` come
+    /// from the declaration of the element.
+    pub(crate) fn push_commands(
+        &self,
+        aabb: [f32; 4],
+        background: Color,
+        corners: [f32; 4],
+        primitives: &mut Vec<RenderCommand>,
+    ) {
+        if let Some(shadow) = self.shadow {
+            let grow = shadow.spread + shadow.blur;
+
+            primitives.push(RenderCommand {
+                bounding_box: [
+                    aabb[0] + shadow.offset.0 - grow,
+                    aabb[1] + shadow.offset.1 - grow,
+                    aabb[2] + shadow.offset.0 + grow,
+                    aabb[3] + shadow.offset.1 + grow,
+                ],
+                render_type: RenderType::DrawShadow(ShadowData {
+                    corners: corners.map(|radius| (radius + shadow.spread).max(0.0)),
+                    blur: shadow.blur,
+                }),
+                color: Ui::color(shadow.color),
+            });
+        }
+
+        if self.backdrop_blur > 0.0 {
+            primitives.push(RenderCommand {
+                bounding_box: aabb,
+                render_type: RenderType::BackdropBlur(BlurData {
+                    radius: self.backdrop_blur,
+                    corners,
+                }),
+                color: Color::new(255.0, 255.0, 255.0, 255.0),
+            });
+        }
+
+        if let Some(gradient) = self.gradient {
+            let (kind, start, end) = gradient_kind(gradient, aabb);
+
+            primitives.push(RenderCommand {
+                bounding_box: aabb,
+                render_type: RenderType::DrawGradient(GradientData {
+                    end_color: Ui::color(end),
+                    kind,
+                    corners,
+                }),
+                color: Ui::color(start),
+            });
+        } else if background.a > 0.0 {
+            let render_type = if corners.iter().all(|&radius| radius == 0.0) {
+                RenderType::DrawRect
+            } else {
+                RenderType::DrawRectRounded(DrawRectRoundedData { corners })
+            };
+
+            primitives.push(RenderCommand {
+                bounding_box: aabb,
+                render_type,
+                color: background,
+            });
+        }
+    }
+}
+
+/// Converts the gradient to screen coordinates for the element at `aabb`
+fn gradient_kind(gradient: Gradient, aabb: [f32; 4]) -> (GradientKind, ClayColor, ClayColor) {
+    match gradient {
+        Gradient::Linear { angle, start, end } => {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            (GradientKind::Linear { direction: [sin, -cos] }, start, end)
+        }
+
+        Gradient::Radial { center, start, end } => {
+            let x = aabb[0] + (aabb[2] - aabb[0]) * center.0;
+            let y = aabb[1] + (aabb[3] - aabb[1]) * center.1;
+            let dx = (x - aabb[0]).max(aabb[2] - x);
+            let dy = (y - aabb[1]).max(aabb[3] - y);
+            let radius = (dx * dx + dy * dy).sqrt();
+
+            (GradientKind::Radial { center: [x, y], radius }, start, end)
+        }
+    }
+}
+
+/// Adds [BoxEffects] to a [Declaration]
+pub trait DeclarationEffects {
+    /// Draws the element with the effects. The gradient replaces the background color, the
+    /// corner radius of the element is used for all of them.
+    fn effects(&mut self, ui: &Ui, effects: &BoxEffects) -> &mut Self;
+}
+
+impl DeclarationEffects for Declaration {
+    fn effects(&mut self, ui: &Ui, effects: &BoxEffects) -> &mut Self {
+        let mut custom_draws = ui.state.custom_draws.borrow_mut();
+        custom_draws.push(CustomDraw::Effects(*effects));
+
+        // Custom data is the index + 1 into the custom draws as null means no custom data
+        let custom_index = custom_draws.len();
+
+        unsafe {
+            self.custom().data_ptr(custom_index as _);
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: ClayColor = ClayColor::rgba(255.0, 255.0, 255.0, 255.0);
+
+    #[test]
+    fn test_gradient_kind() {
+        let aabb = [100.0, 100.0, 200.0, 300.0];
+
+        let (kind, _, _) = gradient_kind(Gradient::Linear { angle: 180.0, start: WHITE, end: WHITE }, aabb);
+        let GradientKind::Linear { direction } = kind else { panic!() };
+        assert!(direction[0].abs() < 1e-6 && (direction[1] - 1.0).abs() < 1e-6);
+
+        let (kind, _, _) = gradient_kind(Gradient::Radial { center: (0.0, 0.5), start: WHITE, end: WHITE }, aabb);
+        assert_eq!(kind, GradientKind::Radial { center: [100.0, 200.0], radius: (100.0f32 * 100.0 + 100.0 * 100.0).sqrt() });
+    }
+
+    #[test]
+    fn test_effect_commands() {
+        let mut primitives = Vec::new();
+        let effects = BoxEffects::new()
+            .drop_shadow(Shadow::new(WHITE).offset(2.0, 4.0).blur(8.0).spread(1.0))
+            .backdrop_blur(6.0);
+
+        effects.push_commands([10.0, 10.0, 50.0, 50.0], Ui::color(WHITE), [4.0; 4], &mut primitives);
+
+        // Shadow, blur and the background color in draw order
+        assert_eq!(primitives.len(), 3);
+        assert_eq!(primitives[0].bounding_box, [3.0, 5.0, 61.0, 63.0]);
+        assert!(matches!(primitives[0].render_type, RenderType::DrawShadow(ShadowData { corners: [5.0, 5.0, 5.0, 5.0], blur: 8.0 })));
+        assert!(matches!(primitives[1].render_type, RenderType::BackdropBlur(_)));
+        assert!(matches!(primitives[2].render_type, RenderType::DrawRectRounded(_)));
+
+        // Transparent element without a gradient only gets the shadow
+        primitives.clear();
+        let effects = BoxEffects::new().drop_shadow(Shadow::new(WHITE));
+        effects.push_commands([0.0; 4], Color::new(0.0, 0.0, 0.0, 0.0), [0.0; 4], &mut primitives);
+        assert_eq!(primitives.len(), 1);
+    }
+}
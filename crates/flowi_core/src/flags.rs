@@ -1,4 +1,7 @@
+use bitflags::bitflags;
+
 bitflags! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub struct BoxFlags: u64 {
         // Interaction
         const MOUSE_CLICKABLE            = 1 << 0;
@@ -31,9 +34,7 @@ bitflags! {
         const SKIP_VIEW_OFF_X            = 1 << 25;
         const SKIP_VIEW_OFF_Y            = 1 << 26;
 
-        // Appearance / Animation
-        const DRAW_DROP_SHADOW           = 1 << 27;
-        const DRAW_BACKGROUND_BLUR       = 1 << 28;
+        // Appearance / Animation. Drop shadows and background blur are set with `BoxEffects`
         const DRAW_BACKGROUND            = 1 << 29;
         const DRAW_BORDER                = 1 << 30;
         const DRAW_SIDE_TOP              = 1 << 31;
@@ -57,6 +58,7 @@ bitflags! {
         const HAS_DISPLAY_STRING         = 1 << 49;
         const HAS_FUZZY_MATCH_RANGES     = 1 << 50;
         const ROUND_CHILDREN_BY_PARENT   = 1 << 51;
+        const DEFAULT_FOCUS              = 1 << 52;
 
         // Bundles
         const CLICKABLE           = Self::MOUSE_CLICKABLE.bits() | Self::KEYBOARD_CLICKABLE.bits();
@@ -72,6 +74,7 @@ bitflags! {
 }
 
 bitflags! {
+    #[allow(dead_code)]
     pub(crate) struct StackFlags : u32 {
         const OWNER = 1 << 1;
         const PREF_WIDTH = 1 << 2;
@@ -0,0 +1,436 @@
+use crate::flags::BoxFlags;
+use crate::{Id, Ui};
+use glam::Vec4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FocusDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl FocusDirection {
+    #[inline]
+    fn is_horizontal(self) -> bool {
+        matches!(self, FocusDirection::Left | FocusDirection::Right)
+    }
+}
+
+/// Navigation rules for a container. Navigation first looks for a candidate inside the scope
+/// of the focused item. If there is none the focus wraps around if enabled for the axis,
+/// otherwise the search continues in the parent scope unless `contain` is set.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FocusScope {
+    pub wrap_x: bool,
+    pub wrap_y: bool,
+    pub contain: bool,
+}
+
+impl FocusScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn wrap_x(mut self, wrap: bool) -> Self {
+        self.wrap_x = wrap;
+        self
+    }
+
+    #[inline]
+    pub fn wrap_y(mut self, wrap: bool) -> Self {
+        self.wrap_y = wrap;
+        self
+    }
+
+    #[inline]
+    pub fn contain(mut self, contain: bool) -> Self {
+        self.contain = contain;
+        self
+    }
+
+    fn wraps(&self, direction: FocusDirection) -> bool {
+        if direction.is_horizontal() {
+            self.wrap_x
+        } else {
+            self.wrap_y
+        }
+    }
+}
+
+struct FocusItem {
+    id: Id,
+    flags: BoxFlags,
+    scope: usize,
+}
+
+struct ScopeEntry {
+    parent: Option<usize>,
+    config: FocusScope,
+}
+
+/// Focusable items and scopes registered during the current frame. Scope 0 is the root scope
+/// that contains everything.
+pub(crate) struct FocusState {
+    items: Vec<FocusItem>,
+    scopes: Vec<ScopeEntry>,
+    scope_stack: Vec<usize>,
+    /// Axes (x, y) the focused widget uses for its own input this frame
+    captured: (bool, bool),
+}
+
+impl FocusState {
+    pub(crate) fn new() -> Self {
+        let mut state = Self {
+            items: Vec::new(),
+            scopes: Vec::new(),
+            scope_stack: Vec::new(),
+            captured: (false, false),
+        };
+        state.begin();
+        state
+    }
+
+    pub(crate) fn begin(&mut self) {
+        self.items.clear();
+        self.scopes.clear();
+        self.scopes.push(ScopeEntry {
+            parent: None,
+            config: FocusScope::default(),
+        });
+        self.scope_stack.clear();
+        self.scope_stack.push(0);
+        self.captured = (false, false);
+    }
+
+    pub(crate) fn capture(&mut self, x: bool, y: bool) {
+        self.captured.0 |= x;
+        self.captured.1 |= y;
+    }
+
+    pub(crate) fn is_captured(&self, direction: FocusDirection) -> bool {
+        if direction.is_horizontal() {
+            self.captured.0
+        } else {
+            self.captured.1
+        }
+    }
+
+    fn current_scope(&self) -> usize {
+        *self.scope_stack.last().unwrap_or(&0)
+    }
+
+    fn is_in_scope(&self, mut scope: usize, parent: usize) -> bool {
+        loop {
+            if scope == parent {
+                return true;
+            }
+
+            match self.scopes[scope].parent {
+                Some(p) => scope = p,
+                None => return false,
+            }
+        }
+    }
+
+    /// Item that gets focus when nothing is focused
+    fn default_item(&self) -> Option<Id> {
+        self.default_item_in(0)
+    }
+
+    /// Item flagged with [BoxFlags::DEFAULT_FOCUS] in the scope, or the first navigable one
+    pub(crate) fn default_item_in(&self, scope: usize) -> Option<Id> {
+        let navigable = || {
+            self.items.iter().filter(move |item| {
+                !item.flags.contains(BoxFlags::FOCUS_NAV_SKIP) && self.is_in_scope(item.scope, scope)
+            })
+        };
+
+        navigable()
+            .find(|item| item.flags.contains(BoxFlags::DEFAULT_FOCUS))
+            .or_else(|| navigable().next())
+            .map(|item| item.id)
+    }
+
+    /// True if the item was registered in the scope or one of its child scopes this frame
+    pub(crate) fn scope_contains(&self, scope: usize, id: Id) -> bool {
+        self.items
+            .iter()
+            .any(|item| item.id.id.id == id.id.id && self.is_in_scope(item.scope, scope))
+    }
+
+    pub(crate) fn push_scope(&mut self, config: FocusScope) -> usize {
+        let parent = self.current_scope();
+
+        self.scopes.push(ScopeEntry {
+            parent: Some(parent),
+            config,
+        });
+
+        let index = self.scopes.len() - 1;
+        self.scope_stack.push(index);
+        index
+    }
+
+    pub(crate) fn pop_scope(&mut self) {
+        self.scope_stack.pop();
+    }
+
+    /// Finds the item to move the focus to. `aabb` returns the bounds of an item from the
+    /// last layout.
+    pub(crate) fn navigate<F>(
+        &self,
+        focused: Option<Id>,
+        direction: FocusDirection,
+        aabb: F,
+    ) -> Option<Id>
+    where
+        F: Fn(Id) -> Option<Vec4>,
+    {
+        let axis_flag = if direction.is_horizontal() {
+            BoxFlags::DEFAULT_FOCUS_NAV_X
+        } else {
+            BoxFlags::DEFAULT_FOCUS_NAV_Y
+        };
+
+        let Some(focused) = focused else {
+            return self.default_item();
+        };
+
+        // Items that aren't registered, such as the entries of a content selector, handle
+        // the move actions themselves
+        let current = self.items.iter().find(|item| item.id.id.id == focused.id.id)?;
+
+        let current_aabb = aabb(current.id)?;
+
+        let candidates_in = |scope: usize| {
+            self.items
+                .iter()
+                .filter(move |item| {
+                    item.id.id.id != current.id.id.id
+                        && item.flags.contains(axis_flag)
+                        && !item.flags.contains(BoxFlags::FOCUS_NAV_SKIP)
+                        && self.is_in_scope(item.scope, scope)
+                })
+                .filter_map(|item| aabb(item.id).map(|bounds| (item.id, bounds)))
+        };
+
+        let mut scope = current.scope;
+
+        loop {
+            if let Some(id) = find_best_candidate(current_aabb, direction, candidates_in(scope)) {
+                return Some(id);
+            }
+
+            let entry = &self.scopes[scope];
+
+            if entry.config.wraps(direction) {
+                let bounds = candidates_in(scope).map(|(_, b)| b).reduce(|a, b| {
+                    Vec4::new(a.x.min(b.x), a.y.min(b.y), a.z.max(b.z), a.w.max(b.w))
+                })?;
+
+                let wrapped = wrap_aabb(current_aabb, bounds, direction);
+                return find_best_candidate(wrapped, direction, candidates_in(scope));
+            }
+
+            if entry.config.contain {
+                return None;
+            }
+
+            scope = entry.parent?;
+        }
+    }
+}
+
+/// Moves `aabb` to just outside the opposite side of `bounds` so a regular search in
+/// `direction` finds the item on the far side.
+fn wrap_aabb(aabb: Vec4, bounds: Vec4, direction: FocusDirection) -> Vec4 {
+    let width = aabb.z - aabb.x;
+    let height = aabb.w - aabb.y;
+
+    match direction {
+        FocusDirection::Right => Vec4::new(bounds.x - width - 1.0, aabb.y, bounds.x - 1.0, aabb.w),
+        FocusDirection::Left => Vec4::new(bounds.z + 1.0, aabb.y, bounds.z + width + 1.0, aabb.w),
+        FocusDirection::Down => Vec4::new(aabb.x, bounds.y - height - 1.0, aabb.z, bounds.y - 1.0),
+        FocusDirection::Up => Vec4::new(aabb.x, bounds.w + 1.0, aabb.z, bounds.w + height + 1.0),
+    }
+}
+
+/// Picks the closest candidate in `direction`. Candidates must start past the center of the
+/// current item. The score is the gap along the direction plus twice the offset on the other
+/// axis, where items that overlap on the other axis have no offset.
+fn find_best_candidate<I>(current: Vec4, direction: FocusDirection, candidates: I) -> Option<Id>
+where
+    I: Iterator<Item = (Id, Vec4)>,
+{
+    // Rotate everything so the direction is always along positive x
+    let to_axis = |r: Vec4| -> (f32, f32, f32, f32) {
+        match direction {
+            FocusDirection::Right => (r.x, r.z, r.y, r.w),
+            FocusDirection::Left => (-r.z, -r.x, r.y, r.w),
+            FocusDirection::Down => (r.y, r.w, r.x, r.z),
+            FocusDirection::Up => (-r.w, -r.y, r.x, r.z),
+        }
+    };
+
+    let (cur_min, cur_max, cur_perp_min, cur_perp_max) = to_axis(current);
+    let cur_center = (cur_min + cur_max) * 0.5;
+    let cur_perp_center = (cur_perp_min + cur_perp_max) * 0.5;
+
+    let mut best: Option<(Id, f32)> = None;
+
+    for (id, aabb) in candidates {
+        let (min, max, perp_min, perp_max) = to_axis(aabb);
+        let center = (min + max) * 0.5;
+
+        if center <= cur_center || min < cur_center {
+            continue;
+        }
+
+        let gap = (min - cur_max).max(0.0);
+        let perp_offset = if perp_max > cur_perp_min && perp_min < cur_perp_max {
+            0.0
+        } else {
+            ((perp_min + perp_max) * 0.5 - cur_perp_center).abs()
+        };
+
+        let score = gap + perp_offset * 2.0;
+
+        if best.is_none_or(|(_, best_score)| score < best_score) {
+            best = Some((id, score));
+        }
+    }
+
+    best.map(|(id, _)| id)
+}
+
+impl Ui<'_> {
+    /// Registers an item for directional focus navigation in the current focus scope. Call it
+    /// every frame while the item is part of the layout.
+    pub fn focusable(&self, id: Id, flags: BoxFlags) {
+        let mut focus = self.state.focus.borrow_mut();
+        let scope = focus.current_scope();
+        focus.items.push(FocusItem { id, flags, scope });
+    }
+
+    /// Runs `f` with a new focus scope. Focusable items registered inside `f` belong to the scope.
+    pub fn with_focus_scope<F: FnOnce(&Ui)>(&self, scope: FocusScope, f: F) {
+        self.state.focus.borrow_mut().push_scope(scope);
+
+        f(self);
+
+        self.state.focus.borrow_mut().pop_scope();
+    }
+
+    pub fn focus_id(&self) -> Option<Id> {
+        self.state.focus_id.get()
+    }
+
+    pub fn is_focused(&self, id: Id) -> bool {
+        self.focus_id().is_some_and(|focus| focus.id.id == id.id.id)
+    }
+
+    /// Moves the focus from the focused item in `direction` and returns the new focus. If nothing
+    /// is focused the item flagged with [BoxFlags::DEFAULT_FOCUS] (or the first item) gets focus.
+    pub fn navigate_focus(&self, direction: FocusDirection) -> Option<Id> {
+        let state = &self.state;
+        let item_states = state.item_states.borrow();
+        let layout = &state.layout;
+
+        // Items without render commands of their own get the bounds from the layout
+        let target = state.focus.borrow().navigate(state.focus_id.get(), direction, |id| {
+            item_states
+                .get(&id.id.id)
+                .map(|item| item.aabb)
+                .filter(|aabb| *aabb != Vec4::ZERO)
+                .or_else(|| {
+                    layout
+                        .bounding_box(id)
+                        .map(|b| Vec4::new(b.x, b.y, b.x + b.width, b.y + b.height))
+                })
+        })?;
+
+        state.focus_id.set(Some(target));
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clay_layout::bindings::{Clay_ElementId, Clay_String};
+
+    fn id(value: u32) -> Id {
+        Id {
+            id: Clay_ElementId {
+                id: value,
+                offset: 0,
+                baseId: 0,
+                stringId: Clay_String {
+                    length: 0,
+                    chars: std::ptr::null(),
+                },
+            },
+        }
+    }
+
+    fn rect(x: f32, y: f32) -> Vec4 {
+        Vec4::new(x, y, x + 10.0, y + 10.0)
+    }
+
+    /// 3x2 grid with ids 1..=6 in row order
+    fn grid() -> Vec<(Id, Vec4)> {
+        (0..6)
+            .map(|i| (id(i + 1), rect((i % 3) as f32 * 20.0, (i / 3) as f32 * 20.0)))
+            .collect()
+    }
+
+    fn best(current: u32, direction: FocusDirection) -> Option<u32> {
+        let items = grid();
+        let aabb = items[current as usize - 1].1;
+        let candidates = items.into_iter().filter(|(i, _)| i.id.id != current);
+        find_best_candidate(aabb, direction, candidates).map(|i| i.id.id)
+    }
+
+    #[test]
+    fn test_find_best_candidate() {
+        assert_eq!(best(1, FocusDirection::Right), Some(2));
+        assert_eq!(best(1, FocusDirection::Down), Some(4));
+        assert_eq!(best(5, FocusDirection::Up), Some(2));
+        assert_eq!(best(6, FocusDirection::Left), Some(5));
+        assert_eq!(best(3, FocusDirection::Right), None);
+        assert_eq!(best(1, FocusDirection::Up), None);
+    }
+
+    #[test]
+    fn test_navigate_scopes() {
+        let mut state = FocusState::new();
+        let items = grid();
+
+        // First row in a wrapping scope, second row in the root scope
+        state.scopes.push(ScopeEntry {
+            parent: Some(0),
+            config: FocusScope::new().wrap_x(true),
+        });
+
+        for (index, (item_id, _)) in items.iter().enumerate() {
+            let scope = if index < 3 { 1 } else { 0 };
+            let flags = if index == 4 { BoxFlags::DEFAULT_FOCUS_NAV | BoxFlags::DEFAULT_FOCUS } else { BoxFlags::DEFAULT_FOCUS_NAV };
+            state.items.push(FocusItem { id: *item_id, flags, scope });
+        }
+
+        let aabb = |i: Id| items.iter().find(|(x, _)| x.id.id == i.id.id).map(|(_, b)| *b);
+        let nav = |from: Option<u32>, dir| state.navigate(from.map(id), dir, aabb).map(|i| i.id.id);
+
+        assert_eq!(nav(None, FocusDirection::Right), Some(5));
+        assert_eq!(nav(Some(3), FocusDirection::Right), Some(1));
+        assert_eq!(nav(Some(1), FocusDirection::Left), Some(3));
+        // Leaves the scope when there is nothing below inside it
+        assert_eq!(nav(Some(2), FocusDirection::Down), Some(5));
+        // The root scope doesn't wrap
+        assert_eq!(nav(Some(6), FocusDirection::Right), None);
+        // Focus on an item that isn't registered is kept
+        assert_eq!(nav(Some(7), FocusDirection::Right), None);
+    }
+}
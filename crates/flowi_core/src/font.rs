@@ -1,10 +1,17 @@
 use crate::internal_error::{InternalError, InternalResult};
 use crate::render_api::RawVoidPtr;
-use background_worker::{AnySend, BoxAnySend, Receiver, WorkSystem, WorkerResult};
+use crate::text_effects::{TextEffectsConfig, EFFECT_LAYER_COUNT};
+use background_worker::{
+    AnySend, BoxAnySend, CallbackError, Receiver, WorkSystem, WorkerResult,
+};
 use cosmic_text::{
-    Attrs, AttrsOwned, Buffer, Color, FontSystem, Metrics, Shaping, SwashCache, Weight,
+    Attrs, AttrsOwned, Buffer, Color, FontSystem, LayoutGlyph, Metrics, Shaping, SwashCache,
+    Weight,
 };
-use std::borrow::Cow;
+use crate::measure_cache::{MeasureCache, MeasureCacheStats, MeasureKey};
+use crate::io::io::{is_remote_url, IoHandle, IoHandler, LoadPriority, LoadState};
+use job_system::JobSystem;
+use log::error;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
@@ -15,6 +22,42 @@ pub(crate) struct GeneratorConfig {
     size: u32,
     sub_pixel_steps_x: u32,
     sub_pixel_steps_y: u32,
+    effects: TextEffectsConfig,
+}
+
+impl GeneratorConfig {
+    pub(crate) fn new(
+        text: &str,
+        size: u32,
+        font_handle: FontHandle,
+        effects: TextEffectsConfig,
+    ) -> Self {
+        Self {
+            font_handle,
+            text: text.to_string(),
+            size,
+            sub_pixel_steps_x: SUB_PIXEL_STEPS_X,
+            sub_pixel_steps_y: 1,
+            effects,
+        }
+    }
+}
+
+/// A single span of a rich text string. Spans sharing the same color are given the same
+/// `layer` so they end up in the same coverage buffer and can be tinted in one draw.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub(crate) struct RichSpanConfig {
+    pub(crate) text: String,
+    pub(crate) font_handle: FontHandle,
+    pub(crate) size: u32,
+    pub(crate) layer: u32,
+    pub(crate) underline: bool,
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Default)]
+pub(crate) struct RichGeneratorConfig {
+    pub(crate) spans: Vec<RichSpanConfig>,
+    pub(crate) wrap_width: Option<u32>,
 }
 
 fn srgb_to_linear(srgb: f32) -> f32 {
@@ -39,6 +82,10 @@ fn build_srgb_to_linear_table() -> [i16; 256] {
 
 pub type FontHandle = u64;
 
+/// Number of horizontal sub pixel positions each string is generated at. The renderer
+/// picks the variant closest to the fractional x position of the text.
+pub(crate) const SUB_PIXEL_STEPS_X: u32 = 4;
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 struct FontFaceInfo {
@@ -59,10 +106,60 @@ pub struct CachedString {
     pub height: u32,
     pub sub_pixel_step_x: u32,
     pub sub_pixel_step_y: u32,
+    /// Pixels added on each side of the text to fit the effects. The text should be drawn
+    /// this much up and to the left of its layout position.
+    pub padding: u32,
+    /// Shadow, glow and outline coverage in draw order. Null if the effect isn't used.
+    pub effect_layers: [RawVoidPtr; EFFECT_LAYER_COUNT],
+}
+
+impl CachedString {
+    /// Frees the text and effect buffers.
+    ///
+    /// # Safety
+    ///
+    /// The buffers must have been allocated by the text generation and neither this string nor
+    /// any clone of it can be used afterwards.
+    unsafe fn free_buffers(&self) {
+        let len = (self.stride * self.height * self.sub_pixel_step_x) as usize;
+
+        for ptr in std::iter::once(self.data).chain(self.effect_layers) {
+            if !ptr.0.is_null() {
+                let slice = std::ptr::slice_from_raw_parts_mut(ptr.0 as *mut i16, len);
+                drop(Box::from_raw(slice));
+            }
+        }
+    }
+}
+
+/// Underline rectangle (x0, y0, x1, y1) relative to the top left of the rich text
+#[derive(Debug, Clone, Copy)]
+pub struct Underline {
+    pub rect: [f32; 4],
+    pub layer: u32,
+}
+
+/// Rich text that has been shaped as a single buffer and rendered into one coverage
+/// buffer per color layer.
+#[derive(Debug, Clone)]
+pub struct CachedRichText {
+    pub layers: Vec<CachedString>,
+    pub underlines: Vec<Underline>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Fonts are loaded through the IoHandler so this tracks if a font can be used yet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontLoadState {
+    Loading,
+    Loaded,
+    Failed(String),
 }
 
 type LoadedFonts = HashMap<FontHandle, FontInfo>;
 type CachedStrings = HashMap<GeneratorConfig, CachedString>;
+type CachedRichTexts = HashMap<RichGeneratorConfig, CachedRichText>;
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -75,7 +172,7 @@ struct AsyncState {
 
 impl AsyncState {
     fn new() -> Self {
-        let font_system = FontSystem::new();
+        let font_system = new_font_system();
         let swash_cache = SwashCache::new();
         let srgb_to_linear = build_srgb_to_linear_table();
 
@@ -88,6 +185,20 @@ impl AsyncState {
     }
 }
 
+/// Font system with the system fonts loaded. Miri can't map the font files so only the
+/// fonts loaded by the user are available when running under it.
+fn new_font_system() -> FontSystem {
+    #[cfg(not(miri))]
+    {
+        FontSystem::new()
+    }
+
+    #[cfg(miri)]
+    {
+        FontSystem::new_with_locale_and_db("en-US".to_string(), cosmic_text::fontdb::Database::new())
+    }
+}
+
 #[derive(Clone, Debug)]
 struct FontInfo {
     attrs: AttrsOwned,
@@ -95,6 +206,27 @@ struct FontInfo {
 
 struct InflightGeneration {
     config: GeneratorConfig,
+    /// Cache generation the text was queued in, see [TextGenerator::clear_text_cache]
+    generation: u64,
+    receiver: Receiver<WorkerResult>,
+}
+
+/// Font where the data is being read by the IoHandler
+struct PendingFont {
+    font_id: FontHandle,
+    name: String,
+    io_handle: IoHandle,
+}
+
+/// Font that has been loaded on the main thread and is being loaded for the workers
+struct InflightFontLoad {
+    font_id: FontHandle,
+    receiver: Receiver<WorkerResult>,
+}
+
+struct InflightRichGeneration {
+    config: RichGeneratorConfig,
+    generation: u64,
     receiver: Receiver<WorkerResult>,
 }
 
@@ -106,28 +238,39 @@ pub(crate) struct TextGenerator {
     sync_font_system: FontSystem,
     sync_loaded_fonts: LoadedFonts,
     inflight_text_generations: Vec<InflightGeneration>,
+    cached_rich_texts: CachedRichTexts,
+    inflight_rich_generations: Vec<InflightRichGeneration>,
+    font_states: HashMap<FontHandle, FontLoadState>,
+    measure_cache: MeasureCache,
+    pending_fonts: Vec<PendingFont>,
+    inflight_font_loads: Vec<InflightFontLoad>,
+    /// Bumped when the text cache is cleared so texts generated before that are dropped
+    cache_generation: u64,
     font_id_counter: u64,
     text_buffers_id: u64,
     load_font_async_id: usize,
     gen_text_async_id: usize,
+    gen_rich_text_async_id: usize,
 }
 
 pub(crate) struct LoadConfig {
     pub(crate) font_id: FontHandle,
-    pub(crate) font_path: Cow<'static, str>,
+    pub(crate) name: String,
+    pub(crate) data: Arc<Vec<u8>>,
 }
 
-/// Loads a font into the font system and stores its information.
+/// Loads a font into the font system and stores its information. `font_path` is only
+/// used for naming and error reporting as the data has already been read.
 fn load_font(
     id: FontHandle,
     font_path: &str,
+    data: Arc<Vec<u8>>,
     loaded_fonts: &mut LoadedFonts,
     font_system: &mut FontSystem,
 ) -> InternalResult<()> {
     let font_db = font_system.db_mut();
 
-    // Load the font from the given path. This assumes that the path points to a valid font file.
-    let ids = font_db.load_font_source(cosmic_text::fontdb::Source::File(font_path.into()));
+    let ids = font_db.load_font_source(cosmic_text::fontdb::Source::Binary(data));
 
     // Check if a font ID was obtained from loading the font.
     // If not, an error is returned since we can't proceed without an ID.
@@ -167,6 +310,7 @@ fn measure_string_size(
     font_info: &FontInfo,
     font_size: u32,
     line_height: f32,
+    wrap_width: Option<u32>,
     font_system: &mut FontSystem,
 ) -> Option<(f32, f32)> {
     // Define metrics for the text
@@ -174,6 +318,7 @@ fn measure_string_size(
 
     // Create a buffer for the text
     let mut buffer = Buffer::new(font_system, metrics);
+    buffer.set_size(font_system, wrap_width.map(|width| width as f32), None);
 
     // Set the text in the buffer with default attributes
     buffer.set_text(
@@ -200,12 +345,67 @@ fn measure_string_size(
     Some((width, height))
 }
 
+/// Rasterizes the buffer once per horizontal sub pixel phase into `layer_count` coverage
+/// buffers. Phase `n` of a layer starts at `n * width * height`. `origin` offsets the text
+/// in both directions to leave room for effects.
+fn rasterize_buffer<F: Fn(&LayoutGlyph) -> usize>(
+    buffer: &Buffer,
+    state: &mut AsyncState,
+    size: (usize, usize),
+    origin: i32,
+    sub_pixel_steps_x: u32,
+    layer_count: usize,
+    layer_for_glyph: F,
+) -> Vec<Vec<i16>> {
+    let (width, height) = size;
+    let phase_size = width * height;
+    let mut layers = vec![vec![0i16; phase_size * sub_pixel_steps_x as usize]; layer_count];
+    let text_color = Color::rgb(0xFF, 0xFF, 0xFF);
+
+    // zeno, which swash rasterizes the glyphs with, reads uninitialized memory that Miri
+    // rejects, so the glyphs are left empty under it
+    if cfg!(miri) {
+        return layers;
+    }
+
+    for phase in 0..sub_pixel_steps_x as usize {
+        let offset_x = phase as f32 / sub_pixel_steps_x as f32;
+
+        for run in buffer.layout_runs() {
+            for glyph in run.glyphs.iter() {
+                let output = &mut layers[layer_for_glyph(glyph)][phase * phase_size..];
+                let physical_glyph = glyph.physical((offset_x, 0.), 1.0);
+
+                state.swash_cache.with_pixels(
+                    &mut state.font_system,
+                    physical_glyph.cache_key,
+                    text_color,
+                    |x, y, color| {
+                        let x = origin + physical_glyph.x + x;
+                        let y = origin + run.line_y as i32 + physical_glyph.y + y;
+                        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                            return;
+                        }
+
+                        let c = (color.0 >> 24) as u8;
+                        output[y as usize * width + x as usize] = state.srgb_to_linear[c as usize];
+                    },
+                );
+            }
+        }
+    }
+
+    layers
+}
+
 #[allow(dead_code)]
 fn generate_text(
     text: &str,
     font_info: &FontInfo,
     font_size: u32,
     line_height: f32,
+    sub_pixel_steps_x: u32,
+    effects: &TextEffectsConfig,
     state: &mut AsyncState,
 ) -> WorkerResult {
     // Define metrics for the text
@@ -236,67 +436,199 @@ fn generate_text(
         height += run.line_height;
     }
 
+    let padding = effects.padding();
+
     // + 8 as we always do 8 pixels wide in the rendering
-    let width = (width + 8.0) as usize;
-    let height = height as usize;
+    let width = (width + 8.0) as usize + padding as usize * 2;
+    let height = height as usize + padding as usize * 2;
+
+    let output = rasterize_buffer(
+        &buffer,
+        state,
+        (width, height),
+        padding as i32,
+        sub_pixel_steps_x,
+        1,
+        |_| 0,
+    )
+    .remove(0);
 
-    let mut output = vec![0; width * height];
+    let mut effect_layers = [RawVoidPtr::default(); EFFECT_LAYER_COUNT];
 
-    // Create a default text color
-    let text_color = Color::rgb(0xFF, 0xFF, 0xFF);
-    let mut max_y_with_pixels = 0;
+    if *effects != TextEffectsConfig::default() {
+        let phase_size = width * height;
+        let mut layers: [Vec<i16>; EFFECT_LAYER_COUNT] =
+            std::array::from_fn(|_| Vec::with_capacity(output.len()));
 
-    // Draw the buffer (for performance, instead use SwashCache directly)
-    buffer.draw(
-        &mut state.font_system,
-        &mut state.swash_cache,
-        text_color,
-        |x, y, _w, _h, color| {
-            let c = (color.0 >> 24) as u8;
-            if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
-                return;
+        for phase in output.chunks(phase_size) {
+            for (layer, effect) in layers.iter_mut().zip(effects.generate(phase, width, height)) {
+                if let Some(effect) = effect {
+                    layer.extend_from_slice(&effect);
+                }
             }
+        }
 
-            let pixel_value = state.srgb_to_linear[c as usize];
-            max_y_with_pixels = if pixel_value != 0 {
-                y
-            } else {
-                max_y_with_pixels
-            };
-
-            output[(y as usize * width + x as usize) as usize] = pixel_value;
-        },
-    );
+        for (ptr, layer) in effect_layers.iter_mut().zip(layers) {
+            if !layer.is_empty() {
+                *ptr = RawVoidPtr(Box::into_raw(layer.into_boxed_slice()) as _);
+            }
+        }
+    }
 
     Ok(Box::new(CachedString {
         data: RawVoidPtr(Box::into_raw(output.into_boxed_slice()) as _),
         stride: width as u32,
         width: width as u32,
-        //height: max_y_with_pixels as u32,
         height: height as u32,
-        sub_pixel_step_x: 1,
+        sub_pixel_step_x: sub_pixel_steps_x,
         sub_pixel_step_y: 1,
+        padding,
+        effect_layers,
         id: 0,
     }))
 }
 
+/// Shapes all spans of a rich text into one buffer so kerning, baselines and wrapping
+/// are shared across spans. Returns None if any of the fonts hasn't been loaded.
+fn shape_rich_text(
+    config: &RichGeneratorConfig,
+    loaded_fonts: &LoadedFonts,
+    font_system: &mut FontSystem,
+) -> Option<Buffer> {
+    let first = config.spans.first()?;
+    let max_size = config.spans.iter().map(|span| span.size).max()?;
+
+    let default_attrs = loaded_fonts.get(&first.font_handle)?.attrs.as_attrs();
+    let mut spans = Vec::with_capacity(config.spans.len());
+
+    // metadata is used to find the span (and thus the layer) of each glyph after layout
+    for (index, span) in config.spans.iter().enumerate() {
+        let font_info = loaded_fonts.get(&span.font_handle)?;
+        let attrs = font_info
+            .attrs
+            .as_attrs()
+            .metrics(Metrics::new(span.size as _, span.size as f32 * 1.1))
+            .metadata(index);
+        spans.push((span.text.as_str(), attrs));
+    }
+
+    let metrics = Metrics::new(max_size as _, max_size as f32 * 1.1);
+    let mut buffer = Buffer::new(font_system, metrics);
+
+    buffer.set_size(
+        font_system,
+        config.wrap_width.map(|width| width as f32),
+        None,
+    );
+    buffer.set_rich_text(font_system, spans, default_attrs, Shaping::Advanced);
+    buffer.shape_until_scroll(font_system, true);
+
+    Some(buffer)
+}
+
+fn rich_text_size(buffer: &Buffer) -> (f32, f32) {
+    let mut width = 0.0f32;
+    let mut height = 0.0f32;
+    for run in buffer.layout_runs() {
+        width = width.max(run.line_w);
+        height += run.line_height;
+    }
+
+    (width, height)
+}
+
+fn generate_rich_text(config: &RichGeneratorConfig, state: &mut AsyncState) -> WorkerResult {
+    let buffer = shape_rich_text(config, &state.loaded_fonts, &mut state.font_system).ok_or(
+        CallbackError::Other("Font not loaded for rich text".to_string()),
+    )?;
+
+    let (width, height) = rich_text_size(&buffer);
+
+    // + 8 as we always do 8 pixels wide in the rendering
+    let width = (width + 8.0) as usize;
+    let height = height as usize;
+
+    let layer_count = config.spans.iter().map(|span| span.layer).max().unwrap_or(0) as usize + 1;
+    let layers = rasterize_buffer(
+        &buffer,
+        state,
+        (width, height),
+        0,
+        SUB_PIXEL_STEPS_X,
+        layer_count,
+        |glyph| config.spans[glyph.metadata].layer as usize,
+    );
+
+    let mut underlines = Vec::new();
+
+    for run in buffer.layout_runs() {
+        for glyph in run.glyphs.iter() {
+            let span = &config.spans[glyph.metadata];
+
+            if span.underline {
+                let thickness = (glyph.font_size / 14.0).max(1.0);
+                let y0 = run.line_y + thickness;
+                underlines.push(Underline {
+                    rect: [glyph.x, y0, glyph.x + glyph.w, y0 + thickness],
+                    layer: span.layer,
+                });
+            }
+        }
+    }
+
+    let layers = layers
+        .into_iter()
+        .map(|output| CachedString {
+            data: RawVoidPtr(Box::into_raw(output.into_boxed_slice()) as _),
+            stride: width as u32,
+            width: width as u32,
+            height: height as u32,
+            sub_pixel_step_x: SUB_PIXEL_STEPS_X,
+            sub_pixel_step_y: 1,
+            padding: 0,
+            effect_layers: Default::default(),
+            id: 0,
+        })
+        .collect();
+
+    Ok(Box::new(CachedRichText {
+        layers,
+        underlines,
+        width: width as u32,
+        height: height as u32,
+    }))
+}
+
+fn job_generate_rich_text(data: BoxAnySend, state: Arc<Mutex<AnySend>>) -> WorkerResult {
+    let data = data.downcast::<Box<RichGeneratorConfig>>().unwrap();
+    let mut locked_state = state.lock().unwrap();
+    let state = locked_state.downcast_mut::<AsyncState>().unwrap();
+
+    generate_rich_text(&data, state)
+}
+
 fn job_generate_text(data: BoxAnySend, state: Arc<Mutex<AnySend>>) -> WorkerResult {
     let data = data.downcast::<Box<GeneratorConfig>>().unwrap();
     let mut locked_state = state.lock().unwrap();
-    let mut state = locked_state.downcast_mut::<AsyncState>().unwrap();
-
-    if let Some(font) = state.loaded_fonts.get(&data.font_handle) {
-        let font_clone = font.clone();
-        generate_text(
-            &data.text,
-            &font_clone,
-            data.size,
-            data.size as f32 * 1.1,
-            &mut state,
-        )
-    } else {
-        panic!("Font not found");
-    }
+    let state = locked_state.downcast_mut::<AsyncState>().unwrap();
+
+    // The generator only queues text for loaded fonts so this should only happen if the
+    // font failed to load.
+    let font = state
+        .loaded_fonts
+        .get(&data.font_handle)
+        .cloned()
+        .ok_or_else(|| CallbackError::Other(format!("Font {} not loaded", data.font_handle)))?;
+
+    generate_text(
+        &data.text,
+        &font,
+        data.size,
+        data.size as f32 * 1.1,
+        data.sub_pixel_steps_x,
+        &data.effects,
+        state,
+    )
 }
 
 fn job_load_font(data: BoxAnySend, state: Arc<Mutex<AnySend>>) -> WorkerResult {
@@ -307,13 +639,13 @@ fn job_load_font(data: BoxAnySend, state: Arc<Mutex<AnySend>>) -> WorkerResult {
 
     load_font(
         config.font_id,
-        &config.font_path,
+        &config.name,
+        config.data.clone(),
         &mut state.loaded_fonts,
         &mut state.font_system,
     )
-    .unwrap();
+    .map_err(|e| CallbackError::Other(e.to_string()))?;
 
-    // TODO: Error handling
     Ok(Box::new(()))
 }
 
@@ -325,44 +657,149 @@ impl TextGenerator {
             bg_worker.register_callback_with_state(job_load_font, async_state.clone());
         let gen_text_async_id =
             bg_worker.register_callback_with_state(job_generate_text, async_state.clone());
+        let gen_rich_text_async_id =
+            bg_worker.register_callback_with_state(job_generate_rich_text, async_state.clone());
 
         Self {
             async_state,
-            sync_font_system: FontSystem::new(),
+            sync_font_system: new_font_system(),
             sync_loaded_fonts: HashMap::new(),
             font_id_counter: 0,
             cached_strings: HashMap::new(),
             load_font_async_id,
             gen_text_async_id,
+            gen_rich_text_async_id,
             inflight_text_generations: Vec::new(),
+            cached_rich_texts: HashMap::new(),
+            inflight_rich_generations: Vec::new(),
+            font_states: HashMap::new(),
+            measure_cache: MeasureCache::new(),
+            pending_fonts: Vec::new(),
+            inflight_font_loads: Vec::new(),
+            cache_generation: 0,
             text_buffers_id: 1,
         }
     }
 
-    pub fn load_font(&mut self, path: &str, bg_worker: &WorkSystem) -> InternalResult<FontHandle> {
+    /// Starts loading a font from a file or url. The handle can be used right away but
+    /// text using it will not be measured or generated until the font has been loaded.
+    pub(crate) fn load_font(
+        &mut self,
+        path: &str,
+        io_handler: &mut IoHandler,
+        job_system: &JobSystem,
+    ) -> FontHandle {
         let font_id = self.font_id_counter;
-        // First we load the font sync so we know it loaded fine, if it's ok we
-        // will also schedle it to be loaded async to be used for rendering later.
-        // We load it on the main thread also for text measurement.
-        load_font(
+        self.font_id_counter += 1;
+
+        let callback = Box::new(|data: &[u8]| Box::new(data.to_vec()) as BoxAnySend);
+        // Urls go through the cache and remote queue like images, local files are read directly
+        let io_handle = if is_remote_url(path) {
+            io_handler.load_with_callback(path, callback, LoadPriority::Highest, job_system)
+        } else {
+            io_handler.load_local_with_callback(path, callback, job_system)
+        };
+
+        self.pending_fonts.push(PendingFont {
             font_id,
-            path,
-            &mut self.sync_loaded_fonts,
-            &mut self.sync_font_system,
-        )?;
-
-        // Start loading the font async.
-        bg_worker.add_work(
-            self.load_font_async_id,
-            Box::new(LoadConfig {
-                font_id,
-                font_path: Cow::Owned(path.to_string()),
-            }),
-        );
+            name: path.to_string(),
+            io_handle,
+        });
 
-        self.font_id_counter += 1;
+        self.font_states.insert(font_id, FontLoadState::Loading);
+
+        font_id
+    }
+
+    pub(crate) fn font_load_state(&self, font_id: FontHandle) -> FontLoadState {
+        self.font_states
+            .get(&font_id)
+            .cloned()
+            .unwrap_or_else(|| FontLoadState::Failed(format!("Unknown font {}", font_id)))
+    }
+
+    #[inline]
+    fn is_font_loaded(&self, font_id: FontHandle) -> bool {
+        matches!(self.font_states.get(&font_id), Some(FontLoadState::Loaded))
+    }
+
+    /// Moves fonts along from being read by the IoHandler, to being loaded on the main thread
+    /// (for measuring) and then on the workers (for generating text). Returns true if any
+    /// font was loaded on the main thread as text measurements will change because of it.
+    fn update_font_loads(&mut self, io_handler: &mut IoHandler, bg_worker: &WorkSystem) -> bool {
+        let mut measurements_changed = false;
+
+        let mut i = 0;
+        while i < self.pending_fonts.len() {
+            let pending = &self.pending_fonts[i];
+            let result = match io_handler.return_loaded(pending.io_handle, LoadPriority::Highest) {
+                LoadState::Loaded(data) => match data.downcast::<Vec<u8>>() {
+                    Ok(data) => Ok(Arc::new(*data)),
+                    Err(_) => Err("Invalid font data".to_string()),
+                },
+                LoadState::Failed(e) => Err(e),
+                LoadState::NotStarted | LoadState::Loading(_) => {
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let pending = self.pending_fonts.remove(i);
+
+            // Load the font on the main thread first so we know it's valid before
+            // handing it over to the workers.
+            let result = result.and_then(|data| {
+                load_font(
+                    pending.font_id,
+                    &pending.name,
+                    data.clone(),
+                    &mut self.sync_loaded_fonts,
+                    &mut self.sync_font_system,
+                )
+                .map(|_| data)
+                .map_err(|e| e.to_string())
+            });
+
+            match result {
+                Ok(data) => {
+                    measurements_changed = true;
+
+                    let config = LoadConfig {
+                        font_id: pending.font_id,
+                        name: pending.name,
+                        data,
+                    };
+
+                    self.inflight_font_loads.push(InflightFontLoad {
+                        font_id: pending.font_id,
+                        receiver: bg_worker.add_work(self.load_font_async_id, Box::new(config)),
+                    });
+                }
+
+                Err(e) => {
+                    error!("Failed to load font {}: {}", pending.name, e);
+                    self.font_states
+                        .insert(pending.font_id, FontLoadState::Failed(e));
+                }
+            }
+        }
 
-        Ok(font_id)
+        let mut i = 0;
+        while i < self.inflight_font_loads.len() {
+            let state = match self.inflight_font_loads[i].receiver.try_recv() {
+                Ok(Ok(_)) => FontLoadState::Loaded,
+                Ok(Err(e)) => FontLoadState::Failed(e.to_string()),
+                Err(_) => {
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let inflight = self.inflight_font_loads.remove(i);
+            self.font_states.insert(inflight.font_id, state);
+        }
+
+        measurements_changed
     }
 
     pub(crate) fn measure_text_size(
@@ -371,17 +808,153 @@ impl TextGenerator {
         font_id: FontHandle,
         font_size: u32,
     ) -> Option<(f32, f32)> {
-        if let Some(font_info) = self.sync_loaded_fonts.get(&font_id) {
+        self.measure_text_size_with_config(text, font_id, font_size, 0, None)
+    }
+
+    /// Measures the text, using the cached size if the same text has been measured before.
+    /// Letter spacing is added between each character the same way Clay does.
+    pub(crate) fn measure_text_size_with_config(
+        &mut self,
+        text: &str,
+        font_id: FontHandle,
+        font_size: u32,
+        letter_spacing: u16,
+        wrap_width: Option<u32>,
+    ) -> Option<(f32, f32)> {
+        let key = MeasureKey {
+            text,
+            font: font_id,
+            size: font_size,
+            letter_spacing,
+            wrap_width,
+        };
+
+        let font_info = self.sync_loaded_fonts.get(&font_id)?;
+        let font_system = &mut self.sync_font_system;
+
+        self.measure_cache.get_or_measure(key, || {
             let line_height = font_size as f32 * 1.1; // TODO: Proper size calculation here
-            measure_string_size(
+            let (width, height) = measure_string_size(
                 text,
                 font_info,
                 font_size,
                 line_height,
-                &mut self.sync_font_system,
-            )
-        } else {
-            None
+                wrap_width,
+                font_system,
+            )?;
+
+            let spacing = text.chars().count().saturating_sub(1) as f32 * letter_spacing as f32;
+            Some((width + spacing, height))
+        })
+    }
+
+    /// Drops all generated text and measurements, such as when the language is changed.
+    /// Text is generated again the next time it's drawn. Texts that are still being generated
+    /// are dropped when they arrive.
+    pub(crate) fn clear_text_cache(&mut self) {
+        for (_, text) in self.cached_strings.drain() {
+            unsafe { text.free_buffers() };
+        }
+
+        for (_, text) in self.cached_rich_texts.drain() {
+            for layer in &text.layers {
+                unsafe { layer.free_buffers() };
+            }
+        }
+
+        self.cache_generation += 1;
+        self.measure_cache.clear();
+    }
+
+    /// Number of generated text buffers
+    pub(crate) fn cached_text_count(&self) -> usize {
+        self.cached_strings.len()
+    }
+
+    /// Evicts old measurements and returns the stats for the frame
+    pub(crate) fn end_frame(&mut self) -> MeasureCacheStats {
+        self.measure_cache.end_frame()
+    }
+
+    pub(crate) fn measure_rich_text_size(
+        &mut self,
+        config: &RichGeneratorConfig,
+    ) -> Option<(f32, f32)> {
+        let fonts = &self.sync_loaded_fonts;
+        let font_system = &mut self.sync_font_system;
+
+        self.measure_cache.get_or_measure_rich(config, || {
+            let buffer = shape_rich_text(config, fonts, font_system)?;
+            Some(rich_text_size(&buffer))
+        })
+    }
+
+    pub(crate) fn queue_generate_rich_text(
+        &mut self,
+        config: &RichGeneratorConfig,
+        bg_worker: &WorkSystem,
+    ) {
+        if config
+            .spans
+            .iter()
+            .any(|span| !self.is_font_loaded(span.font_handle))
+            || self.cached_rich_texts.contains_key(config)
+            || self
+                .inflight_rich_generations
+                .iter()
+                .any(|inflight| {
+                    inflight.generation == self.cache_generation && inflight.config == *config
+                })
+        {
+            return;
+        }
+
+        let inflight = InflightRichGeneration {
+            config: config.clone(),
+            generation: self.cache_generation,
+            receiver: bg_worker.add_work(self.gen_rich_text_async_id, Box::new(config.clone())),
+        };
+
+        self.inflight_rich_generations.push(inflight);
+    }
+
+    pub(crate) fn get_rich_text(&self, config: &RichGeneratorConfig) -> Option<&CachedRichText> {
+        self.cached_rich_texts.get(config)
+    }
+
+    fn update_rich_texts(&mut self) {
+        let mut i = 0;
+        while i < self.inflight_rich_generations.len() {
+            let inflight = &self.inflight_rich_generations[i];
+            match inflight.receiver.try_recv() {
+                Ok(Ok(mut data)) => {
+                    let data = data.downcast_mut::<CachedRichText>().unwrap();
+
+                    if inflight.generation != self.cache_generation {
+                        for layer in &data.layers {
+                            unsafe { layer.free_buffers() };
+                        }
+                        self.inflight_rich_generations.remove(i);
+                        continue;
+                    }
+
+                    for layer in data.layers.iter_mut() {
+                        layer.id = self.text_buffers_id;
+                        self.text_buffers_id += 1;
+                    }
+                    self.cached_rich_texts
+                        .insert(inflight.config.clone(), data.clone());
+                    self.inflight_rich_generations.remove(i);
+                }
+
+                Ok(Err(e)) => {
+                    error!("Error generating rich text: {:?}", e);
+                    self.inflight_rich_generations.remove(i);
+                }
+
+                // Still being generated
+                Err(_) => i += 1,
+            }
         }
     }
 
@@ -392,22 +965,33 @@ impl TextGenerator {
         font_id: FontHandle,
         bg_worker: &WorkSystem,
     ) -> Option<CachedString> {
-        let gen_config = GeneratorConfig {
-            font_handle: font_id,
-            text: text.to_string(),
-            sub_pixel_steps_x: 1,
-            sub_pixel_steps_y: 1,
-            size,
-        };
+        let gen_config = GeneratorConfig::new(text, size, font_id, TextEffectsConfig::default());
+        self.queue_generate(gen_config, bg_worker)
+    }
+
+    pub(crate) fn queue_generate(
+        &mut self,
+        gen_config: GeneratorConfig,
+        bg_worker: &WorkSystem,
+    ) -> Option<CachedString> {
+        // Wait with the generation until the font has been loaded
+        if !self.is_font_loaded(gen_config.font_handle) {
+            return None;
+        }
 
         // First check if we have the text cached.
         // TODO: Fix this. We should not clone because it will clone the whole text buffer.
         if let Some(cached_string) = self.cached_strings.get(&gen_config) {
             return Some(cached_string.clone());
+        } else if self.inflight_text_generations.iter().any(|inflight| {
+            inflight.generation == self.cache_generation && inflight.config == gen_config
+        }) {
+            None
         } else {
             // Queue the text generation if it's not cached.
             let inflight = InflightGeneration {
                 config: gen_config.clone(),
+                generation: self.cache_generation,
                 receiver: bg_worker.add_work(self.gen_text_async_id, Box::new(gen_config)),
             };
 
@@ -417,42 +1001,52 @@ impl TextGenerator {
         }
     }
 
-    pub fn update(&mut self) {
+    /// Returns true if text measurements may have changed since the last update
+    pub(crate) fn update(&mut self, io_handler: &mut IoHandler, bg_worker: &WorkSystem) -> bool {
+        let measurements_changed = self.update_font_loads(io_handler, bg_worker);
+        self.update_rich_texts();
+
         let mut i = 0;
         while i < self.inflight_text_generations.len() {
             let inflight = &self.inflight_text_generations[i];
-            if let Ok(data) = inflight.receiver.try_recv() {
-                match data {
-                    Ok(mut data) => {
-                        let data = data.downcast_mut::<CachedString>().unwrap();
-                        data.id = self.text_buffers_id;
-                        self.cached_strings
-                            .insert(inflight.config.clone(), data.clone());
+            match inflight.receiver.try_recv() {
+                Ok(Ok(mut data)) => {
+                    let data = data.downcast_mut::<CachedString>().unwrap();
+
+                    // Generated before the cache was cleared
+                    if inflight.generation != self.cache_generation {
+                        unsafe { data.free_buffers() };
                         self.inflight_text_generations.remove(i);
-                        self.text_buffers_id += 1;
+                        continue;
                     }
 
-                    Err(e) => {
-                        println!("Error generating text: {:?}", e);
-                        i += 1;
-                    }
+                    data.id = self.text_buffers_id;
+                    self.cached_strings
+                        .insert(inflight.config.clone(), data.clone());
+                    self.inflight_text_generations.remove(i);
+                    self.text_buffers_id += 1;
                 }
+
+                Ok(Err(e)) => {
+                    error!("Error generating text: {:?}", e);
+                    self.inflight_text_generations.remove(i);
+                }
+
+                // Still being generated
+                Err(_) => i += 1,
             }
         }
+
+        measurements_changed
     }
 
     pub fn get_text(&self, text: &str, size: u32, font_id: FontHandle) -> Option<&CachedString> {
-        let gen_config = GeneratorConfig {
-            font_handle: font_id,
-            text: text.to_string(),
-            sub_pixel_steps_x: 1,
-            sub_pixel_steps_y: 1,
-            size,
-        };
-
-        //dbg!("{}", &gen_config);
+        let gen_config = GeneratorConfig::new(text, size, font_id, TextEffectsConfig::default());
+        self.get_generated(&gen_config)
+    }
 
-        self.cached_strings.get(&gen_config).map(|s| &*s)
+    pub(crate) fn get_generated(&self, gen_config: &GeneratorConfig) -> Option<&CachedString> {
+        self.cached_strings.get(gen_config)
     }
 }
 
@@ -460,7 +1054,9 @@ impl TextGenerator {
 mod tests {
     use super::*;
 
+    // Miri adds random errors to powf so the exact values don't match
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_srgb_to_linear() {
         assert_eq!(srgb_to_linear(0.0), 0.0);
         assert_eq!(srgb_to_linear(1.0), 1.0);
@@ -475,6 +1071,47 @@ mod tests {
         assert_eq!(table[128], 7073);
     }
 
+    #[test]
+    fn test_clear_text_cache_drops_inflight_texts() {
+        use std::time::{Duration, Instant};
+
+        let bg_worker = WorkSystem::new(2);
+        let job_system = JobSystem::new(2).unwrap();
+        let mut io_handler = IoHandler::new(Duration::from_millis(500));
+        let mut generator = TextGenerator::new(&bg_worker);
+
+        let font = generator.load_font(
+            "../flowi/data/montserrat-regular.ttf",
+            &mut io_handler,
+            &job_system,
+        );
+
+        // Updates the generator until `done` returns true, Miri runs the jobs much slower
+        let timeout = Duration::from_secs(if cfg!(miri) { 600 } else { 10 });
+        let mut wait_for = |generator: &mut TextGenerator, done: &dyn Fn(&TextGenerator) -> bool| {
+            let start = Instant::now();
+            while !done(generator) {
+                assert!(start.elapsed() < timeout, "timed out");
+                generator.update(&mut io_handler, &bg_worker);
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        wait_for(&mut generator, &|g| g.is_font_loaded(font));
+
+        // Text queued before the clear is dropped when it arrives
+        generator.queue_generate_text("Hello", 20, font, &bg_worker);
+        generator.clear_text_cache();
+        wait_for(&mut generator, &|g| g.inflight_text_generations.is_empty());
+        assert_eq!(generator.cached_text_count(), 0);
+
+        generator.queue_generate_text("Hello", 20, font, &bg_worker);
+        wait_for(&mut generator, &|g| g.cached_text_count() == 1);
+
+        generator.clear_text_cache();
+        assert_eq!(generator.cached_text_count(), 0);
+    }
+
     /*
     #[test]
     fn test_load_sync() {
@@ -58,17 +58,11 @@ impl ImageInfo {
         }
     }
 
-    pub fn vec_to_u8<T>(v: Vec<T>) -> Vec<u8> {
-        let element_size = std::mem::size_of::<T>();
-        let len = v.len();
-        let capacity = v.capacity();
-        let ptr = v.as_ptr() as *mut u8;
-
-        // Prevent the original vector from being dropped
-        std::mem::forget(v);
-
-        // Create a new Vec<u8> from the raw parts
-        unsafe { Vec::from_raw_parts(ptr, len * element_size, capacity * element_size) }
+    pub fn vec_to_u8<T: Copy>(v: Vec<T>) -> Vec<u8> {
+        // The allocation of v can't be reused as a Vec<u8> frees it with the alignment of u8
+        // instead of T, so the data is copied
+        let len = v.len() * std::mem::size_of::<T>();
+        unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, len) }.to_vec()
     }
 }
 
@@ -132,7 +132,7 @@ pub(crate) fn decode_zune_internal(
         Ok(image_info)
     } else {
         let image_info = ImageInfo {
-            data: vec_to_u8(color16_output),
+            data: ImageInfo::vec_to_u8(color16_output),
             width: dimensions.0 as i32,
             height: dimensions.1 as i32,
             //stride: dimensions.0 + 1,
@@ -246,7 +246,7 @@ pub fn upscale_image_integer(
     }
 
     ImageInfo {
-        data: vec_to_u8(output_data),
+        data: ImageInfo::vec_to_u8(output_data),
         width: out_width as i32,
         height: out_height as i32,
         stride: out_width,
@@ -256,19 +256,6 @@ pub fn upscale_image_integer(
     }
 }
 
-fn vec_to_u8<T>(v: Vec<T>) -> Vec<u8> {
-    let element_size = std::mem::size_of::<T>();
-    let len = v.len();
-    let capacity = v.capacity();
-    let ptr = v.as_ptr() as *mut u8;
-
-    // Prevent the original vector from being dropped
-    std::mem::forget(v);
-
-    // Create a new Vec<u8> from the raw parts
-    unsafe { Vec::from_raw_parts(ptr, len * element_size, capacity * element_size) }
-}
-
 static SRGB_TO_LINEAR_TABLE: [i16; 256] = [
     0, 10, 20, 30, 40, 50, 60, 70, 80, 90, 99, 110, 120, 132, 144, 157, 170, 184, 198, 213, 229,
     246, 263, 281, 299, 319, 338, 359, 380, 403, 425, 449, 473, 498, 524, 551, 578, 606, 635, 665,
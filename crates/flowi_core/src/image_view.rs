@@ -0,0 +1,409 @@
+use crate::image::image::ImageInfo;
+use crate::io::io::IoHandle;
+use crate::render_api::{
+    DrawImage, DrawRectRoundedData, GradientData, GradientKind, RenderCommand, RenderType, ScissorData,
+};
+use crate::signal::Signal;
+use crate::theme::ThemeColor;
+use crate::{fixed, Color, CustomDraw, Declaration, Id, Ui};
+
+const DEFAULT_FADE_TIME: f32 = 0.25;
+/// Seconds for the shimmer to sweep over the placeholder
+const SHIMMER_PERIOD: f32 = 1.2;
+
+/// Load progress of an image, see [Ui::image_load_state]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageLoadState {
+    Loading,
+    Loaded,
+    Failed,
+}
+
+/// How an image is fitted to the size of the widget
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFit {
+    /// Stretched to the size of the widget
+    Fill,
+    /// Scaled to fit inside the widget with the aspect ratio kept
+    Contain,
+    /// Scaled to cover the widget with the aspect ratio kept, the parts outside are cropped
+    Cover,
+}
+
+impl ImageFit {
+    /// Where to draw an image of `image_size` for a widget at `aabb`
+    fn rect(self, image_size: (f32, f32), aabb: [f32; 4]) -> [f32; 4] {
+        let (iw, ih) = image_size;
+        let (w, h) = (aabb[2] - aabb[0], aabb[3] - aabb[1]);
+
+        if iw <= 0.0 || ih <= 0.0 {
+            return aabb;
+        }
+
+        let scale = match self {
+            Self::Fill => return aabb,
+            Self::Contain => (w / iw).min(h / ih),
+            Self::Cover => (w / iw).max(h / ih),
+        };
+
+        let (sw, sh) = (iw * scale, ih * scale);
+        let x0 = aabb[0] + (w - sw) * 0.5;
+        let y0 = aabb[1] + (h - sh) * 0.5;
+
+        [x0, y0, x0 + sw, y0 + sh]
+    }
+}
+
+/// Options for [Ui::image_view]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImageOptions {
+    pub fit: ImageFit,
+    pub opacity: f32,
+    pub corner_radius: f32,
+    /// Seconds to fade in the image once it has been loaded
+    pub fade_time: f32,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            fit: ImageFit::Cover,
+            opacity: 1.0,
+            corner_radius: 0.0,
+            fade_time: DEFAULT_FADE_TIME,
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fit(mut self, fit: ImageFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn corner_radius(mut self, radius: f32) -> Self {
+        self.corner_radius = radius.max(0.0);
+        self
+    }
+
+    pub fn fade_time(mut self, seconds: f32) -> Self {
+        self.fade_time = seconds.max(0.0);
+        self
+    }
+}
+
+/// Per widget state kept in the state store
+#[derive(Default)]
+struct ImageViewState {
+    /// Seconds the image has been loading, drives the shimmer
+    loading_time: f32,
+    /// Progress of the fade in after the image was loaded
+    fade: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum ImageContent {
+    Loading { shimmer: f32 },
+    Failed,
+    Loaded { image: DrawImage, fade: f32 },
+}
+
+/// Draw data for an image widget, resolved when the widget is declared
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ImageDraw {
+    content: ImageContent,
+    fit: ImageFit,
+    opacity: f32,
+    corner_radius: f32,
+    placeholder: Color,
+    highlight: Color,
+    glyph: Color,
+    glyph_background: Color,
+}
+
+impl ImageDraw {
+    pub(crate) fn push_commands(&self, aabb: [f32; 4], primitives: &mut Vec<RenderCommand>) {
+        let corners = [self.corner_radius; 4];
+
+        match self.content {
+            ImageContent::Loading { shimmer } => self.push_placeholder(aabb, shimmer, 1.0, primitives),
+            ImageContent::Failed => self.push_error(aabb, primitives),
+            ImageContent::Loaded { image, fade } => {
+                // The placeholder fades out below the image
+                if fade < 1.0 {
+                    self.push_placeholder(aabb, 0.0, 1.0 - fade, primitives);
+                }
+
+                let rect = self.fit.rect((image.width as f32, image.height as f32), aabb);
+                let color = Color::new(255.0, 255.0, 255.0, (255.0 * self.opacity * fade).round());
+
+                // Cropped images are clipped to the widget which also rounds the corners
+                if self.fit == ImageFit::Cover {
+                    push_scissor(aabb, corners, primitives, |primitives| {
+                        primitives.push(RenderCommand {
+                            bounding_box: rect,
+                            render_type: RenderType::DrawImage(image),
+                            color,
+                        });
+                    });
+                } else {
+                    primitives.push(RenderCommand {
+                        bounding_box: rect,
+                        render_type: RenderType::DrawImage(DrawImage {
+                            rounded_corners: corners,
+                            ..image
+                        }),
+                        color,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Fill with a highlight sweeping from left to right at `shimmer` (0.0 - 1.0)
+    fn push_placeholder(&self, aabb: [f32; 4], shimmer: f32, alpha: f32, primitives: &mut Vec<RenderCommand>) {
+        let alpha = alpha * self.opacity;
+        let with_alpha = |color: Color, a: f32| Color::new(color.r, color.g, color.b, (color.a * a).round());
+
+        push_scissor(aabb, [self.corner_radius; 4], primitives, |primitives| {
+            primitives.push(RenderCommand {
+                bounding_box: aabb,
+                render_type: RenderType::DrawRect,
+                color: with_alpha(self.placeholder, alpha),
+            });
+
+            if shimmer <= 0.0 {
+                return;
+            }
+
+            // The band starts left of the widget and ends right of it
+            let band = (aabb[2] - aabb[0]) * 0.5;
+            let x = aabb[0] - band + (aabb[2] - aabb[0] + band) * shimmer;
+            let transparent = with_alpha(self.highlight, 0.0);
+            let highlight = with_alpha(self.highlight, alpha);
+            let halves = [(x, transparent, highlight), (x + band * 0.5, highlight, transparent)];
+
+            for (x0, start, end) in halves {
+                primitives.push(RenderCommand {
+                    bounding_box: [x0, aabb[1], x0 + band * 0.5, aabb[3]],
+                    render_type: RenderType::DrawGradient(GradientData {
+                        end_color: end,
+                        kind: GradientKind::Linear { direction: [1.0, 0.0] },
+                        corners: [0.0; 4],
+                    }),
+                    color: start,
+                });
+            }
+        });
+    }
+
+    /// Placeholder with an exclamation mark in a circle
+    fn push_error(&self, aabb: [f32; 4], primitives: &mut Vec<RenderCommand>) {
+        self.push_placeholder(aabb, 0.0, 1.0, primitives);
+
+        let size = ((aabb[2] - aabb[0]).min(aabb[3] - aabb[1]) * 0.4).round();
+
+        if size < 4.0 {
+            return;
+        }
+
+        let cx = ((aabb[0] + aabb[2]) * 0.5).round();
+        let cy = ((aabb[1] + aabb[3]) * 0.5).round();
+        let half = size * 0.5;
+        let bar = (size * 0.12).max(1.0);
+
+        let shapes = [
+            ([cx - half, cy - half, cx + half, cy + half], half, self.glyph_background),
+            ([cx - bar * 0.5, cy - half * 0.6, cx + bar * 0.5, cy + half * 0.2], bar * 0.5, self.glyph),
+            ([cx - bar * 0.5, cy + half * 0.35, cx + bar * 0.5, cy + half * 0.35 + bar], bar * 0.5, self.glyph),
+        ];
+
+        for (rect, radius, color) in shapes {
+            primitives.push(RenderCommand {
+                bounding_box: rect,
+                render_type: RenderType::DrawRectRounded(DrawRectRoundedData { corners: [radius; 4] }),
+                color: Color::new(color.r, color.g, color.b, (color.a * self.opacity).round()),
+            });
+        }
+    }
+}
+
+fn push_scissor<F: FnOnce(&mut Vec<RenderCommand>)>(
+    aabb: [f32; 4],
+    corners: [f32; 4],
+    primitives: &mut Vec<RenderCommand>,
+    f: F,
+) {
+    let color = Color::new(0.0, 0.0, 0.0, 0.0);
+
+    primitives.push(RenderCommand {
+        bounding_box: aabb,
+        render_type: RenderType::ScissorStart(ScissorData { corners }),
+        color,
+    });
+
+    f(primitives);
+
+    primitives.push(RenderCommand {
+        bounding_box: aabb,
+        render_type: RenderType::ScissorEnd,
+        color,
+    });
+}
+
+impl Ui<'_> {
+    /// Tells if an image can be drawn yet, such as to only switch to images that have loaded
+    pub fn image_load_state(&self, handle: IoHandle) -> ImageLoadState {
+        let mut io_handler = self.state.io_handler.borrow_mut();
+
+        if io_handler.get_loaded_as::<ImageInfo>(handle).is_some() {
+            ImageLoadState::Loaded
+        } else if io_handler.has_failed(handle) {
+            ImageLoadState::Failed
+        } else {
+            ImageLoadState::Loading
+        }
+    }
+
+    /// Image of `size` that shows a placeholder while the image is loading and an error
+    /// glyph if it failed to load. The image fades in once it has been loaded.
+    pub fn image_view(&self, id: Id, handle: IoHandle, size: (f32, f32), options: &ImageOptions) -> Signal {
+        let state = &self.state;
+        let delta_time = state.delta_time.get();
+        let theme = self.theme();
+
+        let mut io_handler = state.io_handler.borrow_mut();
+
+        let loaded = io_handler.get_loaded_as::<ImageInfo>(handle).map(|info| DrawImage {
+            rounded_corners: [0.0; 4],
+            width: info.width as _,
+            height: info.height as _,
+            stride: info.stride as _,
+            handle: info.data.as_ptr() as _,
+        });
+
+        let failed = io_handler.has_failed(handle);
+        drop(io_handler);
+
+        let content = {
+            let mut view = self.state::<ImageViewState>(id);
+
+            match loaded {
+                Some(image) => {
+                    view.fade = if options.fade_time > 0.0 {
+                        (view.fade + delta_time / options.fade_time).min(1.0)
+                    } else {
+                        1.0
+                    };
+
+                    ImageContent::Loaded { image, fade: view.fade }
+                }
+                None if failed => ImageContent::Failed,
+                None => {
+                    view.loading_time += delta_time;
+                    ImageContent::Loading { shimmer: (view.loading_time / SHIMMER_PERIOD).fract() }
+                }
+            }
+        };
+
+        let mut custom_draws = state.custom_draws.borrow_mut();
+
+        custom_draws.push(CustomDraw::Image(ImageDraw {
+            content,
+            fit: options.fit,
+            opacity: options.opacity,
+            corner_radius: options.corner_radius,
+            placeholder: Self::color(theme.color(ThemeColor::Control)),
+            highlight: Self::color(theme.color(ThemeColor::SurfaceFocused)),
+            glyph: Self::color(theme.color(ThemeColor::Text)),
+            glyph_background: Self::color(theme.color(ThemeColor::Accent)),
+        }));
+
+        // Custom data is the index + 1 into the custom draws as null means no custom data
+        let custom_index = custom_draws.len();
+        drop(custom_draws);
+
+        unsafe {
+            state.layout.with(
+                Declaration::new()
+                    .id(id)
+                    .layout()
+                    .width(fixed!(size.0))
+                    .height(fixed!(size.1))
+                    .end()
+                    .custom()
+                    .data_ptr(custom_index as _)
+                    .end(),
+                |_ui| {},
+            );
+        }
+
+        self.signal_id(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_rects() {
+        let aabb = [0.0, 0.0, 200.0, 100.0];
+        let image = (100.0, 100.0);
+
+        assert_eq!(ImageFit::Fill.rect(image, aabb), aabb);
+        assert_eq!(ImageFit::Contain.rect(image, aabb), [50.0, 0.0, 150.0, 100.0]);
+        assert_eq!(ImageFit::Cover.rect(image, aabb), [0.0, -50.0, 200.0, 150.0]);
+    }
+
+    #[test]
+    fn test_content_commands() {
+        let color = Color::new(10.0, 10.0, 10.0, 255.0);
+        let mut draw = ImageDraw {
+            content: ImageContent::Loading { shimmer: 0.5 },
+            fit: ImageFit::Cover,
+            opacity: 1.0,
+            corner_radius: 8.0,
+            placeholder: color,
+            highlight: color,
+            glyph: color,
+            glyph_background: color,
+        };
+
+        let mut primitives = Vec::new();
+        draw.push_commands([0.0, 0.0, 100.0, 100.0], &mut primitives);
+
+        // Clipped fill and the two halves of the shimmer band
+        assert!(matches!(primitives[0].render_type, RenderType::ScissorStart(ScissorData { corners: [8.0, 8.0, 8.0, 8.0] })));
+        assert_eq!(primitives.iter().filter(|p| matches!(p.render_type, RenderType::DrawGradient(_))).count(), 2);
+        assert!(matches!(primitives.last().unwrap().render_type, RenderType::ScissorEnd));
+
+        // Contained images carry the corners themselves
+        let image = DrawImage {
+            rounded_corners: [0.0; 4],
+            width: 50,
+            height: 100,
+            stride: 50,
+            handle: std::ptr::null(),
+        };
+
+        draw.content = ImageContent::Loaded { image, fade: 1.0 };
+        draw.fit = ImageFit::Contain;
+        primitives.clear();
+        draw.push_commands([0.0, 0.0, 100.0, 100.0], &mut primitives);
+
+        assert_eq!(primitives.len(), 1);
+        assert_eq!(primitives[0].bounding_box, [25.0, 0.0, 75.0, 100.0]);
+        let RenderType::DrawImage(image) = primitives[0].render_type else { panic!() };
+        assert_eq!(image.rounded_corners, [8.0; 4]);
+    }
+}
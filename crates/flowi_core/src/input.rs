@@ -1,3 +1,4 @@
+use crate::touch::{Gesture, TouchPhase, TouchPoint, TouchSettings, TouchTracker};
 use glam::Vec2;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -14,7 +15,7 @@ pub enum MouseSource {
 /// A key identifier
 #[allow(missing_docs)] // Self-describing
 #[non_exhaustive]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Key {
     Tab,
     LeftArrow,
@@ -163,6 +164,168 @@ pub enum Key {
     ModShortcut,
 }
 
+/// Number of keys in [Key]
+pub(crate) const KEY_COUNT: usize = Key::ModShortcut as usize + 1;
+
+/// All keys in declaration order, used to look up keys by name
+const ALL_KEYS: [Key; KEY_COUNT] = [
+    Key::Tab,
+    Key::LeftArrow,
+    Key::RightArrow,
+    Key::UpArrow,
+    Key::DownArrow,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Home,
+    Key::End,
+    Key::Insert,
+    Key::Delete,
+    Key::Backspace,
+    Key::Space,
+    Key::Enter,
+    Key::Escape,
+    Key::LeftCtrl,
+    Key::LeftShift,
+    Key::LeftAlt,
+    Key::LeftSuper,
+    Key::RightCtrl,
+    Key::RightShift,
+    Key::RightAlt,
+    Key::RightSuper,
+    Key::Menu,
+    Key::Alpha0,
+    Key::Alpha1,
+    Key::Alpha2,
+    Key::Alpha3,
+    Key::Alpha4,
+    Key::Alpha5,
+    Key::Alpha6,
+    Key::Alpha7,
+    Key::Alpha8,
+    Key::Alpha9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Apostrophe,
+    Key::Comma,
+    Key::Minus,
+    Key::Period,
+    Key::Slash,
+    Key::Semicolon,
+    Key::Equal,
+    Key::LeftBracket,
+    Key::Backslash,
+    Key::RightBracket,
+    Key::GraveAccent,
+    Key::CapsLock,
+    Key::ScrollLock,
+    Key::NumLock,
+    Key::PrintScreen,
+    Key::Pause,
+    Key::Keypad0,
+    Key::Keypad1,
+    Key::Keypad2,
+    Key::Keypad3,
+    Key::Keypad4,
+    Key::Keypad5,
+    Key::Keypad6,
+    Key::Keypad7,
+    Key::Keypad8,
+    Key::Keypad9,
+    Key::KeypadDecimal,
+    Key::KeypadDivide,
+    Key::KeypadMultiply,
+    Key::KeypadSubtract,
+    Key::KeypadAdd,
+    Key::KeypadEnter,
+    Key::KeypadEqual,
+    Key::GamepadStart,
+    Key::GamepadBack,
+    Key::GamepadFaceLeft,
+    Key::GamepadFaceRight,
+    Key::GamepadFaceUp,
+    Key::GamepadFaceDown,
+    Key::GamepadDpadLeft,
+    Key::GamepadDpadRight,
+    Key::GamepadDpadUp,
+    Key::GamepadDpadDown,
+    Key::GamepadL1,
+    Key::GamepadR1,
+    Key::GamepadL2,
+    Key::GamepadR2,
+    Key::GamepadL3,
+    Key::GamepadR3,
+    Key::GamepadLStickLeft,
+    Key::GamepadLStickRight,
+    Key::GamepadLStickUp,
+    Key::GamepadLStickDown,
+    Key::GamepadRStickLeft,
+    Key::GamepadRStickRight,
+    Key::GamepadRStickUp,
+    Key::GamepadRStickDown,
+    Key::MouseLeft,
+    Key::MouseRight,
+    Key::MouseMiddle,
+    Key::MouseX1,
+    Key::MouseX2,
+    Key::MouseWheelX,
+    Key::MouseWheelY,
+    Key::ReservedForModCtrl,
+    Key::ReservedForModShift,
+    Key::ReservedForModAlt,
+    Key::ReservedForModSuper,
+    Key::ModCtrl,
+    Key::ModShift,
+    Key::ModAlt,
+    Key::ModSuper,
+    Key::ModShortcut,
+];
+
+impl Key {
+    /// Finds a key by its variant name, such as "UpArrow" or "GamepadFaceDown"
+    pub fn from_name(name: &str) -> Option<Key> {
+        ALL_KEYS
+            .iter()
+            .find(|key| format!("{:?}", key) == name)
+            .copied()
+    }
+}
+
 #[derive(Debug)]
 pub struct InputSettings {
     pub mouse_threshold: f32,
@@ -170,9 +333,56 @@ pub struct InputSettings {
     pub double_click_max_dist_x2: f32,
     pub key_repeat_delay: f32,
     pub key_repeat_rate: f32,
+    /// Analog values below this are treated as not pressed. Values above are rescaled to 0..1
+    pub analog_deadzone: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct KeyState {
+    pub(crate) down: bool,
+    /// Went down during the last update
+    pub(crate) pressed: bool,
+    /// Went up during the last update
+    pub(crate) released: bool,
+    /// Time the key has been held down, -1.0 if not down
+    pub(crate) down_duration: f32,
+    pub(crate) down_duration_prev: f32,
+    /// Value in 0..1 for analog inputs, 1.0 for digital keys that are down
+    pub(crate) analog_value: f32,
+}
+
+impl Default for KeyState {
+    fn default() -> Self {
+        Self {
+            down: false,
+            pressed: false,
+            released: false,
+            down_duration: -1.0,
+            down_duration_prev: -1.0,
+            analog_value: 0.0,
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Copy)]
+enum InputEvent {
+    Key { key: Key, down: bool, value: f32 },
+    Char(char),
+    MouseWheel(Vec2),
+    MouseSource(MouseSource),
+    Focus(bool),
+    Touch { id: u64, phase: TouchPhase, pos: Vec2 },
+}
+
+/// Index of the mouse buttons passed to [Input::add_mouse_button_event]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseButton {
+    Left = 0,
+    Right = 1,
+    Middle = 2,
+}
+
+#[derive(Debug)]
 pub(crate) struct MouseState {
     pub(crate) clicked_pos: Vec2,
     pub(crate) down: bool,
@@ -183,15 +393,42 @@ pub(crate) struct MouseState {
     pub(crate) clicked_time: f32,
     pub(crate) released_time: f32,
     pub(crate) clicked_count: i32,
+    /// Count of the last click sequence, kept between updates to detect double clicks
+    pub(crate) clicked_last_count: i32,
     pub(crate) down_duration_prev: f32,
 }
 
+impl Default for MouseState {
+    fn default() -> Self {
+        Self {
+            clicked_pos: Vec2::ZERO,
+            down: false,
+            clicked: false,
+            double_clicked: false,
+            released: false,
+            down_duration: -1.0,
+            clicked_time: f32::MIN,
+            released_time: f32::MIN,
+            clicked_count: 0,
+            clicked_last_count: 0,
+            down_duration_prev: -1.0,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Input {
     pub(crate) settings: InputSettings,
     pub(crate) mouse_pos: Vec2,
     pub(crate) mouse_pos_prev: Vec2,
     pub(crate) mouse_buttons: [MouseState; 5],
+    pub(crate) mouse_wheel: Vec2,
+    pub(crate) mouse_source: MouseSource,
+    pub(crate) keys: Vec<KeyState>,
+    pub(crate) chars: Vec<char>,
+    pub(crate) focused: bool,
+    pub(crate) touch: TouchTracker,
+    events: Vec<InputEvent>,
     pub delta_time: f32,
 }
 
@@ -207,18 +444,42 @@ impl Input {
                 double_click_max_dist_x2: 6.0 * 6.0,
                 key_repeat_delay: 0.250,
                 key_repeat_rate: 0.050,
+                analog_deadzone: 0.25,
             },
+            mouse_wheel: Vec2::ZERO,
+            mouse_source: MouseSource::Mouse,
+            keys: vec![KeyState::default(); KEY_COUNT],
+            chars: Vec::new(),
+            focused: true,
+            touch: TouchTracker::default(),
+            events: Vec::with_capacity(64),
             delta_time: 0.0,
         }
     }
 
     /// Queue a new key down/up event.
     /// Key should be "translated" (as in, generally [Key::A] matches the key end-user would use to emit an 'A' character)
-    pub fn add_key_event(&mut self, _key: Key, _down: bool) {}
+    pub fn add_key_event(&mut self, key: Key, down: bool) {
+        let value = if down { 1.0 } else { 0.0 };
+        self.events.push(InputEvent::Key { key, down, value });
+    }
 
-    /// Queue a new key down/up event for analog values (
-    /// e.g. ImGuiKey_Gamepad_ values). Dead-zones should be handled by the backend.
-    pub fn add_key_analog_event(&mut self, _key: Key, _down: bool, _value: f32) {}
+    /// Queue a new key down/up event for analog values (e.g. Key::Gamepad* values) in 0..1.
+    /// Values inside the deadzone in [InputSettings] are treated as the key being up.
+    pub fn add_key_analog_event(&mut self, key: Key, down: bool, value: f32) {
+        let deadzone = self.settings.analog_deadzone;
+        let value = if down && value > deadzone {
+            ((value - deadzone) / (1.0 - deadzone)).min(1.0)
+        } else {
+            0.0
+        };
+
+        self.events.push(InputEvent::Key {
+            key,
+            down: value > 0.0,
+            value,
+        });
+    }
 
     /// Queue a mouse position update. Use None to signify no mouse (e.g. app not focused and not hovered)
     pub fn add_mouse_pos_event(&mut self, pos: Option<(f32, f32)>) {
@@ -231,27 +492,214 @@ impl Input {
         self.mouse_pos = Vec2::new(x, y);
     }
 
-    /// Queue a mouse button change
+    /// Queue a mouse button change. Buttons are indexed as in [MouseButton], 3 and 4 are the
+    /// extra buttons.
     pub fn add_mouse_button_event(&mut self, button: i32, down: bool) {
-        self.mouse_buttons[button as usize].down = down;
+        if let Some(state) = self.mouse_buttons.get_mut(button as usize) {
+            state.down = down;
+        }
+    }
+
+    /// True while the button is held down
+    #[inline]
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons[button as usize].down
+    }
+
+    #[inline]
+    pub fn mouse_pos(&self) -> Vec2 {
+        self.mouse_pos
     }
 
     /// Queue a mouse wheel update.
     /// wheel_y<0: scroll down, wheel_y>0: scroll up, wheel_x<0: scroll right, wheel_x>0: scroll left.
-    pub fn add_mouse_wheel_event(_x: f32, _y: f32) {}
+    pub fn add_mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.events.push(InputEvent::MouseWheel(Vec2::new(x, y)));
+    }
 
     /// Queue a mouse source change (Mouse/TouchScreen/Pen)
-    pub fn add_mouse_source_event(_source: MouseSource) {}
+    pub fn add_mouse_source_event(&mut self, source: MouseSource) {
+        self.events.push(InputEvent::MouseSource(source));
+    }
 
     /// Queue a gain/loss of focus for the application (generally based on OS/platform focus of your window)
-    pub fn add_focus_event(&mut self, _focused: bool) {}
+    pub fn add_focus_event(&mut self, focused: bool) {
+        self.events.push(InputEvent::Focus(focused));
+    }
+
+    /// Queue a touch or pen contact change. `id` identifies the finger for multi-touch
+    pub fn add_touch_event(&mut self, id: u64, phase: TouchPhase, x: f32, y: f32) {
+        self.events.push(InputEvent::Touch {
+            id,
+            phase,
+            pos: Vec2::new(x, y),
+        });
+    }
 
     /// Queue a new character input
-    pub fn add_char_event(&mut self, _c: i32) {}
+    pub fn add_char_event(&mut self, c: i32) {
+        if let Some(c) = char::from_u32(c as u32) {
+            self.events.push(InputEvent::Char(c));
+        }
+    }
 
     /// Update the state
     pub fn update(&mut self, time: f32, delta_time: f32) {
+        self.update_key_state(delta_time);
         self.update_mouse_state(time, delta_time);
+        self.touch.update(time, delta_time);
+    }
+
+    /// Active touch points
+    #[inline]
+    pub fn touches(&self) -> &[TouchPoint] {
+        &self.touch.touches
+    }
+
+    /// Gestures recognized during the last update
+    #[inline]
+    pub fn gestures(&self) -> &[Gesture] {
+        &self.touch.gestures
+    }
+
+    #[inline]
+    pub fn touch_settings_mut(&mut self) -> &mut TouchSettings {
+        &mut self.touch.settings
+    }
+
+    /// True while the key is held down
+    #[inline]
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys[key as usize].down
+    }
+
+    /// True on the update the key went down
+    #[inline]
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.keys[key as usize].pressed
+    }
+
+    /// True on the update the key went up
+    #[inline]
+    pub fn is_key_released(&self, key: Key) -> bool {
+        self.keys[key as usize].released
+    }
+
+    /// True when the key went down and then repeatedly while it's held, using the key repeat
+    /// delay and rate in [InputSettings]
+    pub fn is_key_pressed_repeat(&self, key: Key) -> bool {
+        let state = &self.keys[key as usize];
+        state.pressed || self.key_repeat_count(state) > 0
+    }
+
+    /// Time in seconds the key has been held down, or -1.0 if it's up
+    #[inline]
+    pub fn key_down_duration(&self, key: Key) -> f32 {
+        self.keys[key as usize].down_duration
+    }
+
+    /// Value in 0..1 with the deadzone removed for analog inputs, 1.0 for pressed digital keys
+    #[inline]
+    pub fn key_analog_value(&self, key: Key) -> f32 {
+        self.keys[key as usize].analog_value
+    }
+
+    /// Characters typed since the last update
+    #[inline]
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    /// Mouse wheel movement since the last update
+    #[inline]
+    pub fn mouse_wheel(&self) -> Vec2 {
+        self.mouse_wheel
+    }
+
+    #[inline]
+    pub fn mouse_source(&self) -> MouseSource {
+        self.mouse_source
+    }
+
+    /// False when the application window doesn't have focus
+    #[inline]
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Number of times the key repeated during the last update
+    fn key_repeat_count(&self, state: &KeyState) -> u32 {
+        let t0 = state.down_duration_prev;
+        let t1 = state.down_duration;
+        let delay = self.settings.key_repeat_delay;
+        let rate = self.settings.key_repeat_rate;
+
+        if t0 < 0.0 || t1 <= delay || rate <= 0.0 {
+            return 0;
+        }
+
+        let count0 = if t0 < delay { -1 } else { ((t0 - delay) / rate) as i32 };
+        let count1 = ((t1 - delay) / rate) as i32;
+
+        (count1 - count0).max(0) as u32
+    }
+
+    fn update_key_state(&mut self, delta_time: f32) {
+        for key in self.keys.iter_mut() {
+            key.pressed = false;
+            key.released = false;
+        }
+
+        self.chars.clear();
+        self.mouse_wheel = Vec2::ZERO;
+
+        // Events are applied in order so a key that goes down and up within one update is
+        // still reported as both pressed and released.
+        for event in self.events.drain(..) {
+            match event {
+                InputEvent::Key { key, down, value } => {
+                    let state = &mut self.keys[key as usize];
+                    if down && !state.down {
+                        state.pressed = true;
+                        state.down_duration = -1.0;
+                    } else if !down && state.down {
+                        state.released = true;
+                    }
+
+                    state.down = down;
+                    state.analog_value = value;
+                }
+                InputEvent::Char(c) => self.chars.push(c),
+                InputEvent::MouseWheel(delta) => self.mouse_wheel += delta,
+                InputEvent::MouseSource(source) => self.mouse_source = source,
+                InputEvent::Touch { id, phase, pos } => self.touch.apply_event(id, phase, pos),
+                InputEvent::Focus(focused) => {
+                    self.focused = focused;
+
+                    // Keys released while unfocused will never be reported so release everything
+                    if !focused {
+                        for state in self.keys.iter_mut().filter(|state| state.down) {
+                            state.down = false;
+                            state.released = true;
+                            state.analog_value = 0.0;
+                        }
+                    }
+                }
+            }
+        }
+
+        for key in self.keys.iter_mut() {
+            key.down_duration_prev = key.down_duration;
+            key.down_duration = if key.down {
+                if key.down_duration < 0.0 {
+                    0.0
+                } else {
+                    key.down_duration + delta_time
+                }
+            } else {
+                -1.0
+            };
+        }
     }
 
     // Inpseration taken from Dear imgui
@@ -283,8 +731,13 @@ impl Input {
             };
 
             if button.clicked {
+                let mut is_repeated_click = false;
+
                 if time - button.clicked_time < self.settings.double_click_time {
-                    let delta_from_click_pos = if self.mouse_pos != Vec2::new(f32::NAN, f32::NAN) {
+                    // Mouse position is NaN until the backend has reported it
+                    let has_mouse_pos =
+                        self.mouse_pos.is_finite() && button.clicked_pos.is_finite();
+                    let delta_from_click_pos = if has_mouse_pos {
                         self.mouse_pos - button.clicked_pos
                     } else {
                         Vec2::new(0.0, 0.0)
@@ -293,14 +746,19 @@ impl Input {
                     if delta_from_click_pos.length_squared()
                         < self.settings.double_click_max_dist_x2
                     {
-                        button.clicked_count += 1;
+                        is_repeated_click = true;
                     }
+                }
+
+                if is_repeated_click {
+                    button.clicked_last_count += 1;
                 } else {
-                    button.clicked_count = 1;
-                };
+                    button.clicked_last_count = 1;
+                }
 
                 button.clicked_time = time;
                 button.clicked_pos = self.mouse_pos;
+                button.clicked_count = button.clicked_last_count;
             }
 
             button.double_clicked = button.clicked_count == 2;
@@ -312,3 +770,117 @@ impl Input {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_edges() {
+        let mut input = Input::new();
+        input.add_key_event(Key::Enter, true);
+        input.update(0.0, 0.016);
+        assert!(input.is_key_pressed(Key::Enter));
+        assert!(input.is_key_down(Key::Enter));
+
+        input.update(0.016, 0.016);
+        assert!(!input.is_key_pressed(Key::Enter));
+        assert!(input.is_key_down(Key::Enter));
+
+        input.add_key_event(Key::Enter, false);
+        input.update(0.032, 0.016);
+        assert!(input.is_key_released(Key::Enter));
+        assert!(!input.is_key_down(Key::Enter));
+        assert_eq!(input.key_down_duration(Key::Enter), -1.0);
+    }
+
+    #[test]
+    fn test_key_press_and_release_same_update() {
+        let mut input = Input::new();
+        input.add_key_event(Key::Space, true);
+        input.add_key_event(Key::Space, false);
+        input.update(0.0, 0.016);
+        assert!(input.is_key_pressed(Key::Space));
+        assert!(input.is_key_released(Key::Space));
+        assert!(!input.is_key_down(Key::Space));
+    }
+
+    #[test]
+    fn test_key_repeat() {
+        let mut input = Input::new();
+        input.add_key_event(Key::DownArrow, true);
+
+        let dt = 0.01;
+        let mut repeats = 0;
+        for i in 0..50 {
+            input.update(i as f32 * dt, dt);
+            if input.is_key_pressed_repeat(Key::DownArrow) {
+                repeats += 1;
+            }
+        }
+
+        // Initial press, one repeat at the delay and one every rate after that (0.49s held)
+        assert_eq!(repeats, 1 + 1 + 4);
+    }
+
+    #[test]
+    fn test_analog_deadzone() {
+        let mut input = Input::new();
+        input.add_key_analog_event(Key::GamepadLStickDown, true, 0.1);
+        input.update(0.0, 0.016);
+        assert!(!input.is_key_down(Key::GamepadLStickDown));
+
+        input.add_key_analog_event(Key::GamepadLStickDown, true, 1.0);
+        input.update(0.016, 0.016);
+        assert!(input.is_key_pressed(Key::GamepadLStickDown));
+        assert_eq!(input.key_analog_value(Key::GamepadLStickDown), 1.0);
+    }
+
+    #[test]
+    fn test_mouse_double_click() {
+        let mut input = Input::new();
+        let left = MouseButton::Left as i32;
+
+        input.add_mouse_button_event(left, true);
+        input.update(1.0, 0.016);
+        assert!(input.mouse_buttons[0].clicked);
+        assert!(!input.mouse_buttons[0].double_clicked);
+
+        input.add_mouse_button_event(left, false);
+        input.update(1.05, 0.016);
+        assert!(input.mouse_buttons[0].released);
+
+        input.add_mouse_button_event(left, true);
+        input.update(1.1, 0.016);
+        assert!(input.mouse_buttons[0].double_clicked);
+    }
+
+    #[test]
+    fn test_key_from_name() {
+        assert_eq!(Key::from_name("UpArrow"), Some(Key::UpArrow));
+        assert_eq!(Key::from_name("ModShortcut"), Some(Key::ModShortcut));
+        assert_eq!(Key::from_name("NotAKey"), None);
+
+        for (index, key) in ALL_KEYS.iter().enumerate() {
+            assert_eq!(*key as usize, index);
+        }
+    }
+
+    #[test]
+    fn test_focus_loss_releases_keys() {
+        let mut input = Input::new();
+        input.add_key_event(Key::A, true);
+        input.add_char_event('a' as i32);
+        input.add_mouse_wheel_event(0.0, 1.0);
+        input.update(0.0, 0.016);
+        assert_eq!(input.chars(), &['a']);
+        assert_eq!(input.mouse_wheel(), Vec2::new(0.0, 1.0));
+
+        input.add_focus_event(false);
+        input.update(0.016, 0.016);
+        assert!(input.is_key_released(Key::A));
+        assert!(!input.is_focused());
+        assert!(input.chars().is_empty());
+        assert_eq!(input.mouse_wheel(), Vec2::ZERO);
+    }
+}
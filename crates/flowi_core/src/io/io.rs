@@ -3,7 +3,7 @@ use crate::{io::cache::CacheStore, LoadOptions};
 use job_system::{JobSystem, BoxAnySend, JobHandle, JobResult};
 use log::{debug, error, info, warn};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
     path::PathBuf,
     time::{Duration, Instant},
@@ -78,6 +78,9 @@ pub struct IoHandler {
     queue: PriorityQueue<u64, QueueItem>,
     inflight_jobs: HashMap<u64, JobInfo>,
     finished_jobs: HashMap<u64, BoxAnySend>,
+    /// Loads that failed. The error is logged when the failure is polled, after that only the
+    /// handle is kept so the failure can still be shown.
+    failed_jobs: HashSet<u64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -97,16 +100,19 @@ pub enum LoadState {
 
 impl IoHandler {
     pub fn new(remote_delay: Duration) -> Self {
-        let settings = IoSettings {
+        Self::with_settings(IoSettings {
             cache_dir: CACHE_DIR.to_string(),
             remote_delay,
-        };
+        })
+    }
 
+    pub fn with_settings(settings: IoSettings) -> Self {
         Self {
             cache_store: CacheStore::new(&settings.cache_dir).unwrap(),
             time: Instant::now() - Duration::from_secs(60),
             inflight_jobs: HashMap::with_capacity(256),
             finished_jobs: HashMap::with_capacity(256),
+            failed_jobs: HashSet::new(),
             queue: PriorityQueue::new(),
             settings,
             id_counter: 1,
@@ -125,7 +131,7 @@ impl IoHandler {
 
         // If data is in cache we can just start the job directly
         if self.cache_store.contains_key(url) {
-            let t = Self::schedule_job_with_callback(job_system, url, DataSource::Cache, callback);
+            let t = self.schedule_job_with_callback(job_system, url, DataSource::Cache, callback);
             self.inflight_jobs.insert(id, JobInfo::new(t, url));
         } else {
             self.queue.push(id, QueueItem::new(callback, url.to_owned(), priority));
@@ -134,6 +140,22 @@ impl IoHandler {
         IoHandle(id)
     }
 
+    /// Loads a file from the local file system, bypassing the cache and the remote queue.
+    pub fn load_local_with_callback(
+        &mut self,
+        path: &str,
+        callback: Callback,
+        job_system: &JobSystem,
+    ) -> IoHandle {
+        let id = self.id_counter;
+        self.id_counter += 1;
+
+        let t = self.schedule_job_with_callback(job_system, path, DataSource::Local, callback);
+        self.inflight_jobs.insert(id, JobInfo::new(t, path));
+
+        IoHandle(id)
+    }
+
     pub fn load_image(
         &mut self,
         url: &str,
@@ -165,8 +187,8 @@ impl IoHandler {
         if self.time.elapsed() > self.settings.remote_delay {
             self.time = Instant::now();
             if let Some((id, job)) = self.queue.pop() {
-                let t = Self::schedule_job_with_callback(
-                    &job_system,
+                let t = self.schedule_job_with_callback(
+                    job_system,
                     &job.url,
                     DataSource::Remote,
                     job.callback,
@@ -177,6 +199,7 @@ impl IoHandler {
     }
 
     fn schedule_job_with_callback(
+        &self,
         jobs: &JobSystem,
         url: &str,
         ds: DataSource,
@@ -190,7 +213,10 @@ impl IoHandler {
                     Box::new(move |data| callback(data)),
                 )
             },
-            Box::new(url.to_string()),
+            Box::new(JobRequest {
+                url: url.to_string(),
+                cache_dir: self.settings.cache_dir.clone(),
+            }),
         )
         .unwrap()
     }
@@ -219,13 +245,29 @@ impl IoHandler {
         if self.finished_jobs.contains_key(&handle.0) {
             self.finished_jobs.get(&handle.0)?.downcast_ref::<T>()
         } else {
-            if let LoadState::Loaded(data) = self.return_loaded(handle, LoadPriority::Normal) {
-                self.finished_jobs.insert(handle.0, data);
+            match self.return_loaded(handle, LoadPriority::Normal) {
+                LoadState::Loaded(data) => {
+                    self.finished_jobs.insert(handle.0, data);
+                }
+                LoadState::Failed(e) => {
+                    if let Some(job) = self.inflight_jobs.remove(&handle.0) {
+                        error!("Failed to load {}: {}", job.url, e);
+                    }
+
+                    self.failed_jobs.insert(handle.0);
+                }
+                _ => {}
             }
             self.finished_jobs.get(&handle.0)?.downcast_ref::<T>()
         }
     }
 
+    /// True if the load failed. Loads are polled by [IoHandler::get_loaded_as] so this is
+    /// set after it has been called for the handle.
+    pub fn has_failed(&self, handle: IoHandle) -> bool {
+        self.failed_jobs.contains(&handle.0)
+    }
+
     /// Hint the priority of the handle. This is useful for example if we want to load
     /// a low priority image in the background. The code may not extract the data directly
     /// but if it needs something to be visible it can hint the priority to load the data.
@@ -234,16 +276,30 @@ impl IoHandler {
             item.priority = priority;
         });
     }
+
+    /// Number of loads waiting to be started
+    pub fn queued_count(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Number of loads that have been started but not finished
+    pub fn inflight_count(&self) -> usize {
+        self.inflight_jobs.len()
+    }
+}
+
+/// Urls that needs to be fetched from the network (or the cache). Everything else is a local file.
+pub fn is_remote_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
 }
 
 /// Directory to store cached images
-#[allow(dead_code)]
 const CACHE_DIR: &str = "target/cache";
 
 /// Writes the data to the cache directory
-fn write_to_cache(url: &str, data: &[u8]) -> io::Result<()> {
+fn write_to_cache(url: &str, cache_dir: &str, data: &[u8]) -> io::Result<()> {
     let mut cache_path = PathBuf::with_capacity(128);
-    CacheStore::get_cache_path(url, CACHE_DIR, &mut cache_path);
+    CacheStore::get_cache_path(url, cache_dir, &mut cache_path);
 
     debug!("Start write to cache: {} -> {:?}", url, cache_path);
 
@@ -263,7 +319,7 @@ fn write_to_cache(url: &str, data: &[u8]) -> io::Result<()> {
 /// the progress. We will log an error in the log that something went wrong so the user
 /// can know about it.
 #[allow(dead_code)]
-pub fn read_data_from_remote(url: &str) -> io::Result<Vec<u8>> {
+pub fn read_data_from_remote(url: &str, cache_dir: &str) -> io::Result<Vec<u8>> {
     // Fetch the image from the URL
     info!("Start read from remote: {}", url);
 
@@ -279,7 +335,7 @@ pub fn read_data_from_remote(url: &str) -> io::Result<Vec<u8>> {
 
     info!("Done  read from remote: {} (size {})", url, bytes.len());
 
-    match write_to_cache(url, &bytes) {
+    match write_to_cache(url, cache_dir, &bytes) {
         Ok(_) => Ok(bytes),
         Err(e) => {
             error!(
@@ -293,9 +349,9 @@ pub fn read_data_from_remote(url: &str) -> io::Result<Vec<u8>> {
 
 /// Read the data from the cache.
 #[allow(dead_code)]
-fn read_data_from_cache(url: &str) -> io::Result<Vec<u8>> {
+fn read_data_from_cache(url: &str, cache_dir: &str) -> io::Result<Vec<u8>> {
     let mut cache_path = PathBuf::with_capacity(128);
-    CacheStore::get_cache_path(url, CACHE_DIR, &mut cache_path);
+    CacheStore::get_cache_path(url, cache_dir, &mut cache_path);
 
     debug!("Start read from cache: {} -> {:?}", url, cache_path);
 
@@ -317,18 +373,27 @@ fn read_data_from_cache(url: &str) -> io::Result<Vec<u8>> {
 enum DataSource {
     Cache,
     Remote,
+    Local,
 }
 
 type BinaryCallback = Box<dyn Fn(&[u8]) -> BoxAnySend>;
 
+/// Data passed to the load jobs
+struct JobRequest {
+    url: String,
+    cache_dir: String,
+}
+
 // And an enum for how to handle the raw data
 
 fn read_data(data: BoxAnySend, source: DataSource, callback: BinaryCallback) -> JobResult<BoxAnySend> {
-    let url = data.downcast::<String>().unwrap();
+    let request = data.downcast::<JobRequest>().unwrap();
+    let (url, cache_dir) = (request.url.as_str(), request.cache_dir.as_str());
 
     let data = match source {
-        DataSource::Cache => read_data_from_cache(&url)?,
-        DataSource::Remote => read_data_from_remote(&url)?,
+        DataSource::Cache => read_data_from_cache(url, cache_dir)?,
+        DataSource::Remote => read_data_from_remote(url, cache_dir)?,
+        DataSource::Local => std::fs::read(url)?,
     };
 
     Ok(callback(&data))
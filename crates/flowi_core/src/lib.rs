@@ -1,34 +1,68 @@
+pub mod accessibility;
+pub mod action_map;
+pub mod animation;
+pub mod background;
 pub mod content_provider;
 pub mod content_selector;
+pub mod debug;
+pub mod effects;
+pub mod flags;
+pub mod focus;
 pub mod font;
+pub mod image_view;
 pub mod input;
 mod internal_error;
 mod io;
+pub mod localization;
+mod measure_cache;
+pub mod overlay;
 pub mod primitives;
 pub mod render;
+pub mod rich_text;
+pub mod scale;
 pub mod signal;
+pub mod state_store;
+pub mod text_effects;
+pub mod theme;
+pub mod touch;
 pub mod widgets;
 
 pub mod image;
 
 pub mod render_api;
 
-use crate::input::Input;
+pub use crate::accessibility::{AccessNode, AccessStates, AccessTree, Announcement, Politeness, Role};
+pub use crate::action_map::{ActionMap, InputBinding};
+pub use crate::animation::{Animation, Easing};
+pub use crate::background::BackgroundMode;
+pub use crate::debug::DebugStats;
+pub use crate::effects::{BoxEffects, DeclarationEffects, Gradient, Shadow};
+pub use crate::image_view::{ImageFit, ImageLoadState, ImageOptions};
+pub use crate::localization::{Date, Localization, LocalizationError};
+pub use crate::flags::BoxFlags;
+pub use crate::focus::{FocusDirection, FocusScope};
+pub use crate::state_store::{PersistentState, Retention, StateError};
+pub use crate::theme::{StyleVar, Theme, ThemeColor};
+use crate::theme::{FontSize, Radius, Spacing};
+use crate::input::{Input, MouseButton, MouseSource};
 use glam::Vec4;
 
 use arena_allocator::Arena;
 use background_worker::WorkSystem;
+use bumpalo::Bump;
 use clay_layout::{
     render_commands::RenderCommand as ClayRenderCommand, render_commands::RenderCommandConfig,
+    render_commands::Custom,
+    render_commands::CornerRadii,
     Clay, Clay_Dimensions, Clay_StringSlice, Clay_TextElementConfig,
 };
-use font::{CachedString};
-use internal_error::InternalResult;
+use font::{CachedString, FontLoadState};
 pub use io::io::IoHandler;
 use job_system::JobSystem;
 use signal::Signal;
-use std::cell::UnsafeCell;
+use std::cell::{Cell, Ref, RefCell};
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::time::Duration;
 use tracy_client::span;
 
@@ -48,11 +82,14 @@ pub use clay_layout::{
 };
 
 pub use render_api::{
-    Color, DrawBorderData, DrawImage, DrawRectRoundedData, DrawTextBufferData, RenderCommand,
-    RenderType, Renderer, SoftwareRenderData, StringSlice,
+    BlurData, Color, DrawBorderData, DrawImage, DrawRectRoundedData, DrawTextBufferData,
+    GradientData, GradientKind, RenderCommand, RenderType, Renderer, ScissorData, ShadowData,
+    SoftwareRenderData, StringSlice,
 };
 
 pub use crate::image::image::{ImageInfo, LoadOptions};
+pub use crate::rich_text::TextSpan;
+pub use crate::text_effects::TextEffects;
 pub use crate::io::io::*;
 pub use job_system;
 
@@ -62,7 +99,7 @@ use crate::image::Resize;
 
 type FlowiKey = u64;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 #[allow(dead_code)]
 pub struct ItemState {
     pub aabb: Vec4,
@@ -70,55 +107,120 @@ pub struct ItemState {
     pub was_clicked: bool,
     pub active: f32,
     pub frame: u64,
+    /// Mouse buttons that were pressed on the item and not yet released, one bit per button
+    pub(crate) pressed_buttons: u8,
+    /// Mouse buttons that are dragging the item, one bit per button
+    pub(crate) dragging_buttons: u8,
 }
 
-pub enum BackgroundMode {
-    AlignTopRight,
+/// Draw data for Clay custom elements. The custom data of the element is the index + 1
+/// into `State::custom_draws`, which is reset every frame.
+pub(crate) enum CustomDraw {
+    RichText {
+        config: font::RichGeneratorConfig,
+        colors: Vec<Color>,
+    },
+    Text {
+        config: font::GeneratorConfig,
+        color: Color,
+        effect_colors: [Option<Color>; text_effects::EFFECT_LAYER_COUNT],
+    },
+    Effects(effects::BoxEffects),
+    Image(image_view::ImageDraw),
 }
 
-#[allow(dead_code)]
-struct BackgroundImage {
-    handle: IoHandle,
-    mode: BackgroundMode,
+/// Text generation and the active font. Shared with the Clay measure text callback, which
+/// is called while elements are declared and in `end`.
+pub(crate) struct TextState {
+    pub(crate) generator: RefCell<font::TextGenerator>,
+    pub(crate) active_font: Cell<FontHandle>,
+}
+
+impl TextState {
+    fn measure(&self, text: &str, config: &TextConfig) -> Dimensions {
+        // Fonts that are still loading are measured as empty
+        let size = self
+            .generator
+            .borrow_mut()
+            .measure_text_size_with_config(
+                text,
+                self.active_font.get(),
+                config.font_size as _,
+                config.letter_spacing,
+                None,
+            )
+            .unwrap_or_default();
+
+        Dimensions::new(size.0 as _, size.1 as _)
+    }
 }
 
+/// Each subsystem has its own cell so widgets can be nested freely. Borrows of a subsystem
+/// are kept short and never held while calling user code or declaring text (which calls
+/// back into `text`).
 #[allow(dead_code)]
 pub(crate) struct State<'a> {
-    pub(crate) text_generator: font::TextGenerator,
-    pub(crate) io_handler: IoHandler,
-    pub(crate) input: Input,
+    pub(crate) text: Rc<TextState>,
+    pub(crate) io_handler: RefCell<IoHandler>,
+    pub(crate) input: RefCell<Input>,
     pub(crate) primitives: Arena,
     pub(crate) hot_item: FlowiKey,
-    pub(crate) current_frame: u64,
+    pub(crate) current_frame: Cell<u64>,
     pub(crate) layout: Clay<'a>,
-    pub(crate) button_id: u32,
+    pub(crate) button_id: Cell<u32>,
     pub(crate) renderer: Box<dyn Renderer>,
     pub(crate) bg_worker: WorkSystem,
-    pub(crate) item_states: HashMap<u32, ItemState>, // TODO: Arena hashmap
-    pub(crate) active_font: FontHandle,
-    pub(crate) background_image: Option<BackgroundImage>,
-    pub(crate) screen_size: (usize, usize),
-    pub(crate) delta_time: f32,
-    pub(crate) focus_id: Option<Id>,
+    pub(crate) item_states: RefCell<HashMap<u32, ItemState>>, // TODO: Arena hashmap
+    pub(crate) background: RefCell<background::BackgroundState>,
+    pub(crate) screen_size: Cell<(usize, usize)>,
+    pub(crate) delta_time: Cell<f32>,
+    pub(crate) focus_id: Cell<Option<Id>>,
     pub(crate) job_system: JobSystem,
-    pub(crate) screen_area: f32x4,
-    pub(crate) fonts: Vec<FontHandle>,
+    pub(crate) screen_area: Cell<f32x4>,
+    pub(crate) fonts: RefCell<Vec<FontHandle>>,
+    pub(crate) custom_draws: RefCell<Vec<CustomDraw>>,
+    pub(crate) action_map: RefCell<ActionMap>,
+    pub(crate) focus: RefCell<focus::FocusState>,
+    pub(crate) widget_states: RefCell<HashMap<u32, widgets::WidgetState>>,
+    /// Strings generated by widgets during the frame, Clay keeps pointers to them until `end`
+    pub(crate) frame_strings: Bump,
+    pub(crate) theme: RefCell<Theme>,
+    /// Values replaced by `push_style` to restore in `pop_style`
+    pub(crate) style_stack: RefCell<Vec<StyleVar>>,
+    pub(crate) animations: RefCell<animation::Animations>,
+    pub(crate) overlay: RefCell<overlay::OverlayState>,
+    pub(crate) state_store: RefCell<state_store::StateStore>,
+    pub(crate) debug: RefCell<debug::DebugState>,
+    pub(crate) scale: RefCell<scale::ScaleState>,
+    pub(crate) localization: RefCell<Localization>,
+    pub(crate) accessibility: RefCell<accessibility::AccessibilityState>,
+    /// Reference to `text` owned by the Clay measure callback, released on drop
+    pub(crate) text_measure_data: *const TextState,
+}
+
+impl Drop for State<'_> {
+    fn drop(&mut self) {
+        // Clay doesn't call the measure function after the last layout so the reference given
+        // to it can be released here
+        unsafe { drop(Rc::from_raw(self.text_measure_data)) };
+    }
 }
 
-#[allow(dead_code)]
 pub struct Ui<'a> {
-    state: UnsafeCell<State<'a>>,
+    state: State<'a>,
 }
 
 /// The user can ask the system for action response for a given input. This allows us to make better
 /// choices for various inputs. Actions can be something like MoveDown,Up,etc. and we can map
 /// this to keyboard, mouse, gamepad, etc.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ActionResponse {
     None,
     Pressed,
     Holding(f32),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum InputAction {
     MoveUp,
     MoveDown,
@@ -126,9 +228,16 @@ pub enum InputAction {
     MoveRight,
     Select,
     Cancel,
+    PageUp,
+    PageDown,
+    Back,
+    Menu,
+    /// Closes the application
+    Quit,
 }
 
 // TODO: We likely need something better than this
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FontStyle {
     Default,
     Bold,
@@ -148,45 +257,73 @@ struct ItemStatus {
 
 impl<'a> Ui<'_> {
     pub fn new(renderer: Box<dyn Renderer>) -> Box<Self> {
-        let io_handler = IoHandler::new(Duration::from_millis(500));
+        Self::with_io_handler(renderer, IoHandler::new(Duration::from_millis(500)))
+    }
+
+    /// Creates the Ui with a custom IoHandler, for example one using a different cache directory.
+    pub fn with_io_handler(renderer: Box<dyn Renderer>, io_handler: IoHandler) -> Box<Self> {
         let bg_worker = WorkSystem::new(2);
 
-        let reserve_size = 1024 * 1024 * 1024;
+        let text = Rc::new(TextState {
+            generator: RefCell::new(font::TextGenerator::new(&bg_worker)),
+            active_font: Cell::new(0),
+        });
+
+        // Clay gets its own reference to the text state, so the pointer stays valid for as long
+        // as the callback is registered
+        let text_measure_data = Rc::into_raw(Rc::clone(&text));
+
+        // Miri backs the whole reservation with memory, so a smaller one is used under it
+        let reserve_size = if cfg!(miri) { 16 * 1024 * 1024 } else { 1024 * 1024 * 1024 };
         let state = State {
-            io_handler,
-            text_generator: font::TextGenerator::new(&bg_worker),
+            io_handler: RefCell::new(io_handler),
+            text,
+            text_measure_data,
             hot_item: 0,
-            input: Input::new(),
-            current_frame: 0,
+            input: RefCell::new(Input::new()),
+            current_frame: Cell::new(0),
             primitives: Arena::new(reserve_size).unwrap(),
-            layout: Clay::new(Dimensions::new(1280.0, 720.0)),
-            item_states: HashMap::new(),
-            button_id: 0,
+            // The size is set by begin each frame
+            layout: Clay::new(Dimensions::new(0.0, 0.0)),
+            item_states: RefCell::new(HashMap::new()),
+            button_id: Cell::new(0),
             renderer,
             bg_worker,
-            active_font: 0,
-            background_image: None,
-            screen_size: (0, 0),
-            delta_time: 0.0,
-            focus_id: None,
-            screen_area: f32x4::new_splat(0.0),
+            background: RefCell::new(background::BackgroundState::default()),
+            screen_size: Cell::new((0, 0)),
+            delta_time: Cell::new(0.0),
+            focus_id: Cell::new(None),
+            screen_area: Cell::new(f32x4::new_splat(0.0)),
             job_system: JobSystem::new(2).unwrap(),
-            fonts: vec![0; 16],
+            fonts: RefCell::new(vec![0; 16]),
+            custom_draws: RefCell::new(Vec::new()),
+            action_map: RefCell::new(ActionMap::new()),
+            focus: RefCell::new(focus::FocusState::new()),
+            widget_states: RefCell::new(HashMap::new()),
+            frame_strings: Bump::new(),
+            theme: RefCell::new(Theme::default()),
+            style_stack: RefCell::new(Vec::new()),
+            animations: RefCell::new(animation::Animations::default()),
+            overlay: RefCell::new(overlay::OverlayState::default()),
+            state_store: RefCell::new(state_store::StateStore::default()),
+            debug: RefCell::new(debug::DebugState::default()),
+            scale: RefCell::new(scale::ScaleState::default()),
+            localization: RefCell::new(Localization::default()),
+            accessibility: RefCell::new(accessibility::AccessibilityState::default()),
         };
 
-        let data = Box::new(Ui {
-            state: UnsafeCell::new(state),
-        });
+        // Clay culls scissor starts of offscreen elements but keeps their ends, so culling is
+        // done in end() instead to keep the clip stack balanced
+        state.layout.enable_culling(false);
 
-        // This is a hack. To be fixed later
         unsafe {
-            let raw_ptr = Box::into_raw(data);
             Clay::set_measure_text_function_unsafe(
                 Self::measure_text_trampoline,
-                raw_ptr as _,
+                state.text_measure_data as _,
             );
-            Box::from_raw(raw_ptr)
         }
+
+        Box::new(Ui { state })
     }
 
     unsafe extern "C" fn measure_text_trampoline(
@@ -200,154 +337,111 @@ impl<'a> Ui<'_> {
         ));
 
         let text_config = TextConfig::from(*config);
-        let ui = &*(user_data as *const Ui);
+        let text_state = &*(user_data as *const TextState);
 
-        ui.measure_text(text, &text_config).into()
+        text_state.measure(text, &text_config).into()
     }
 
     #[inline]
     pub fn job_system(&self) -> &JobSystem {
-        let state = unsafe { &mut *self.state.get() };
-        &state.job_system
+        &self.state.job_system
     }
 
-    fn measure_text(&self, text: &str, config: &TextConfig) -> Dimensions {
-        let state = unsafe { &mut *self.state.get() };
-        // TODO: Proper error handling
-        let size = state
-            .text_generator
-            .measure_text_size(text, state.active_font, config.font_size as _)
-            .unwrap();
+    pub fn text_size(&self, text: &str, font_size: u32) -> Dimensions {
+        let text_state = &self.state.text;
+        let size = text_state
+            .generator
+            .borrow_mut()
+            .measure_text_size(text, text_state.active_font.get(), font_size as _)
+            .unwrap_or_default();
 
         Dimensions::new(size.0 as _, size.1 as _)
     }
 
-    pub fn text_size(&self, text: &str, font_size: u32) -> Dimensions {
-        let state = unsafe { &mut *self.state.get() };
-        let size = state
-            .text_generator
-            .measure_text_size(text, state.active_font, font_size as _)
-            .unwrap();
+    /// Size of the text when wrapped at `wrap_width`
+    pub fn text_size_wrapped(&self, text: &str, font_size: u32, wrap_width: f32) -> Dimensions {
+        let text_state = &self.state.text;
+        let size = text_state
+            .generator
+            .borrow_mut()
+            .measure_text_size_with_config(
+                text,
+                text_state.active_font.get(),
+                font_size,
+                0,
+                Some(wrap_width as u32),
+            )
+            .unwrap_or_default();
 
         Dimensions::new(size.0 as _, size.1 as _)
     }
 
     pub fn set_font(&self, font_id: FontHandle) {
-        let state = unsafe { &mut *self.state.get() };
-        state.active_font = font_id;
+        self.state.text.active_font.set(font_id);
     }
 
     pub fn register_font(&self, font_id: FontHandle, _font_style: FontStyle) {
-        let state = unsafe { &mut *self.state.get() };
-        state.fonts[font_id as usize] = font_id;
+        self.state.fonts.borrow_mut()[font_id as usize] = font_id;
     }
 
     pub fn select_font(&self, font_style: FontStyle) {
-        let state = unsafe { &mut *self.state.get() };
-        state.active_font = state.fonts[font_style as usize];
+        let font = self.state.fonts.borrow()[font_style as usize];
+        self.state.text.active_font.set(font);
     }
 
+    /// Starts a new frame at the output size in pixels. The renderer is resized when the
+    /// size changes.
     pub fn begin(&mut self, delta_time: f32, width: usize, height: usize) {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &mut self.state;
+
+        if state.screen_size.get() != (width, height) {
+            state.renderer.set_window_size(width as _, height as _);
+        }
+
+        state.scale.get_mut().update(width, height);
         state
             .layout
             .layout_dimensions(Dimensions::new(width as f32, height as f32));
         state.layout.begin();
         //state.io_handler.update();
         state.primitives.rewind();
-        state.button_id = 0;
-        state.screen_size = (width, height);
-        state.delta_time = delta_time;
-        state.screen_area = f32x4::new(0.0, 0.0, width as f32, height as f32);
+        state.button_id.set(0);
+        state.custom_draws.get_mut().clear();
+        state.focus.get_mut().begin();
+        state.overlay.get_mut().begin();
+        state.frame_strings.reset();
+        state.accessibility.get_mut().begin();
+        state.screen_size.set((width, height));
+        state.delta_time.set(delta_time);
+        state.screen_area.set(f32x4::new(0.0, 0.0, width as f32, height as f32));
     }
 
     pub fn with_layout<F: FnOnce(&Ui)>(&self, declaration: &Declaration, f: F) {
-        let state = unsafe { &mut *self.state.get() };
-
-        state.layout.with(declaration, |_clay| {
+        self.state.layout.with(declaration, |_clay| {
             f(self);
         });
     }
 
     pub fn button_with_layout(&self, name: &str, declaration: &Declaration) -> Signal {
-        let state = unsafe { &mut *self.state.get() };
         let mut signal = Signal::new();
 
-        state.layout.with(declaration, |_clay| {
+        self.state.layout.with(declaration, |_clay| {
             signal = self.button_test(name);
         });
 
         signal
     }
 
-    pub fn image(&self, _handle: IoHandle) {
-        //let state = unsafe { &mut *self.state.get() };
-
-        /*
-        if let Some(image) = state.io_handler.get_loaded_as::<ImageInfo>(handle) {
-            let source_dimensions = Dimensions::new(image.width as _, image.height as _);
-
-            unsafe {
-                state.layout.with(
-                    &Declaration::new()
-                        .id(state.layout.id("image_test"))
-                        .layout()
-                        .width(fixed!(source_dimensions.width as _))
-                        .height(fixed!(source_dimensions.height as _))
-                        .end()
-                        .image()
-                        .data_ptr(image.data.as_ptr() as _)
-                        .source_dimensions(source_dimensions)
-                        .end(),
-                    |_ui| {},
-                );
-            }
-        }
-
-         */
-    }
-
-    pub fn image_with_opts(&self, id: Id, handle: IoHandle, opacity: f32, size: (f32, f32)) {
-        let state = unsafe { &mut *self.state.get() };
-
-        if let Some(image) = state.io_handler.get_loaded_as::<ImageInfo>(handle) {
-            let source_dimensions = Dimensions::new(image.width as _, image.height as _);
-
-            unsafe {
-                state.layout.with(
-                    Declaration::new()
-                        .id(id)
-                        .layout()
-                        .width(fixed!(size.0))
-                        .height(fixed!(size.1))
-                        .end()
-                        .image()
-                        .data_ptr(image.data.as_ptr() as _)
-                        .source_dimensions(source_dimensions)
-                        .end()
-                        .background_color(ClayColor::rgba(0.0, 0.0, 255.0, 255.0 * opacity)),
-                    |_ui| {},
-                );
-            }
-        } else {
-            state.layout.with(
-                Declaration::new()
-                    .id(id)
-                    .layout()
-                    .width(fixed!(size.0))
-                    .height(fixed!(size.1))
-                    .end()
-                    .background_color(ClayColor::rgba(0.0, 0.0, 255.0, 255.0 * opacity)),
-                |_ui| {},
-            );
-        }
+    /// Same as [Ui::image_view] with the default options and the given opacity
+    pub fn image_with_opts(&self, id: Id, handle: IoHandle, opacity: f32, size: (f32, f32)) -> Signal {
+        self.image_view(id, handle, size, &ImageOptions::new().opacity(opacity))
     }
 
     pub fn text_with_layout(&self, text: &str, font_size: u32, col: ClayColor, decl: &Declaration) {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
         state.layout.with(decl, |_clay| {
-            let font_id = state.active_font;
-            let _ = state.text_generator.queue_generate_text(
+            let font_id = state.text.active_font.get();
+            let _ = state.text.generator.borrow_mut().queue_generate_text(
                 text,
                 font_size,
                 font_id,
@@ -366,41 +460,80 @@ impl<'a> Ui<'_> {
         });
     }
 
+    /// Draws text with a shadow, outline and/or glow to keep it readable on top of images
+    #[rustfmt::skip]
+    pub fn text_with_effects_with_layout(
+        &self,
+        text: &str,
+        font_size: u32,
+        col: ClayColor,
+        effects: &TextEffects,
+        decl: &Declaration,
+    ) {
+        let state = &self.state;
+        let font_id = state.text.active_font.get();
+        let mut generator = state.text.generator.borrow_mut();
+
+        let Some(size) = generator.measure_text_size(text, font_id, font_size) else {
+            return;
+        };
+
+        let config = font::GeneratorConfig::new(text, font_size, font_id, effects.into());
+        let _ = generator.queue_generate(config.clone(), &state.bg_worker);
+        drop(generator);
+
+        let mut custom_draws = state.custom_draws.borrow_mut();
+
+        custom_draws.push(CustomDraw::Text {
+            config,
+            color: Self::color(col),
+            effect_colors: effects.colors().map(|c| c.map(Self::color)),
+        });
+
+        // Custom data is the index + 1 into the custom draws as null means no custom data
+        let custom_index = custom_draws.len();
+        drop(custom_draws);
+
+        state.layout.with(decl, |_clay| {
+            unsafe {
+                state.layout.with(Declaration::new()
+                    .layout()
+                        .width(fixed!(size.0))
+                        .height(fixed!(size.1))
+                    .end()
+                    .custom()
+                        .data_ptr(custom_index as _)
+                    .end(), |_clay| {});
+            }
+        });
+    }
+
     pub fn load_with_callback(
         &self,
         url: &str,
         priority: LoadPriority,
         callback: Callback,
     ) -> IoHandle {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
         state
             .io_handler
+            .borrow_mut()
             .load_with_callback(url, callback, priority, &state.job_system)
     }
 
     pub fn set_focus_id(&self, id: Id) {
-        let state = unsafe { &mut *self.state.get() };
-        state.focus_id = Some(id);
+        self.state.focus_id.set(Some(id));
     }
 
     pub fn return_loaded(&self, handle: IoHandle, priority: LoadPriority) -> LoadState {
-        let state = unsafe { &mut *self.state.get() };
-        state.io_handler.return_loaded(handle, priority)
-    }
-
-    /*
-    pub fn get_image(&self, handle: ImageHandle) -> Option<&RenderImage> {
-        let state = unsafe { &mut *self.state.get() };
-        state.io_handler.get_loaded_as::<RenderImage>(handle)
+        self.state.io_handler.borrow_mut().return_loaded(handle, priority)
     }
 
-     */
-
     pub fn update_scroll_containers(&self, scroll_delta: (f32, f32)) {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
         state
             .layout
-            .update_scroll_containers(false, scroll_delta.into(), state.delta_time);
+            .update_scroll_containers(false, scroll_delta.into(), state.delta_time.get());
     }
 
     fn bounding_box(render_command: &ClayRenderCommand) -> [f32; 4] {
@@ -408,6 +541,10 @@ impl<'a> Ui<'_> {
         [bb.x, bb.y, bb.x + bb.width, bb.y + bb.height]
     }
 
+    fn corner_radii(radii: &CornerRadii) -> [f32; 4] {
+        [radii.top_left, radii.top_right, radii.bottom_left, radii.bottom_right]
+    }
+
     fn color(color: ClayColor) -> Color {
         Color {
             r: color.r,
@@ -418,8 +555,7 @@ impl<'a> Ui<'_> {
     }
 
     pub fn update_scroll(&self, id: Id, scroll_pos: (f32, f32)) {
-        let state = unsafe { &mut *self.state.get() };
-        if let Some(scroll_container) = state.layout.scroll_container_data(id) {
+        if let Some(scroll_container) = self.state.layout.scroll_container_data(id) {
             unsafe {
                 (*scroll_container.scrollPosition).x = scroll_pos.0;
                 (*scroll_container.scrollPosition).y = scroll_pos.1;
@@ -429,12 +565,13 @@ impl<'a> Ui<'_> {
 
     #[inline]
     pub fn delta_time(&self) -> f32 {
-        let state = unsafe { &mut *self.state.get() };
-        state.delta_time
+        self.state.delta_time.get()
     }
 
     pub fn end(&mut self) {
-        let state = unsafe { &mut *self.state.get() };
+        // Declared last so they end up on top of everything else
+        self.draw_toasts();
+        self.draw_debug_overlay();
 
         let zone = span!("rendering");
         zone.emit_color(0x00FF00);
@@ -443,77 +580,107 @@ impl<'a> Ui<'_> {
 
         {
         let _ = span!("binning");
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
 
         // TODO: Don't iterate over all boxes twice
-        let focus_id = if let Some(id) = state.focus_id {
+        let focus_id = if let Some(id) = state.focus_id.get() {
             id.id
         } else {
             state.layout.id("").id
         };
 
-        let anime_rate = 1.0 - 2f32.powf(-8.0 * state.delta_time);
+        let mut io_handler = state.io_handler.borrow_mut();
 
+        state.background.borrow_mut().push_commands(
+            &mut io_handler,
+            state.screen_size.get(),
+            state.delta_time.get(),
+            &mut primitives,
+        );
 
-        if let Some(bg_image) = state.background_image.as_ref() {
-            if let Some(image) = state.io_handler.get_loaded_as::<ImageInfo>(bg_image.handle) {
-                let width = state.screen_size.0 as f32;
+        // Scissor start that hasn't got the corner radii of its element yet
+        let mut open_scissor: Option<(u32, usize)> = None;
+        let mut item_states = state.item_states.borrow_mut();
+        let screen_area = state.screen_area.get();
+        let delta_time = state.delta_time.get();
+        let current_frame = state.current_frame.get();
+        let mut layout_commands = 0;
+        let mut culled_commands = 0;
+        // Text declared by Clay's inspector isn't queued by a widget so it's generated here
+        let generate_text = state.debug.borrow().enabled;
 
-                let x0 = width - image.width as f32;
-                let y0 = 0.0;
-                let x1 = width;
-                let y1 = image.height as _;
+        for command in state.layout.end() {
+            let aabb = Self::bounding_box(&command);
+            layout_commands += 1;
 
-                let render_command = RenderCommand {
-                    bounding_box: [x0, y0, x1, y1],
-                    render_type: RenderType::DrawBackground(DrawImage {
-                        rounded_corners: [0.0, 0.0, 0.0, 0.0],
-                        width: image.width as _,
-                        height: image.height as _,
-                        stride: image.stride as _,
-                        handle: image.data.as_ptr() as _,
-                        rounding: false,
-                    }),
-                    color: Color::new(1.0, 1.0, 1.0, 1.0),
-                };
+            // Scissors are kept even if they are empty or outside the screen so the renderer
+            // always gets a balanced clip stack
+            match command.config {
+                RenderCommandConfig::ScissorStart() => {
+                    open_scissor = Some((command.id, primitives.len()));
+                    primitives.push(RenderCommand {
+                        bounding_box: aabb,
+                        render_type: RenderType::ScissorStart(ScissorData { corners: [0.0; 4] }),
+                        color: Color::new(0.0, 0.0, 0.0, 0.0),
+                    });
+                    continue;
+                }
 
-                primitives.push(render_command);
-            }
-        }
+                RenderCommandConfig::ScissorEnd() => {
+                    primitives.push(RenderCommand {
+                        bounding_box: aabb,
+                        render_type: RenderType::ScissorEnd,
+                        color: Color::new(0.0, 0.0, 0.0, 0.0),
+                    });
+                    continue;
+                }
 
-        for command in state.layout.end() {
-            let aabb = Self::bounding_box(&command);
+                // The background of a scroll container comes right after its scissor start
+                RenderCommandConfig::Rectangle(ref config) => {
+                    if let Some((id, index)) = open_scissor.take() {
+                        if let RenderType::ScissorStart(ref mut data) = primitives[index].render_type {
+                            if id == command.id {
+                                data.corners = Self::corner_radii(&config.corner_radii);
+                            }
+                        }
+                    }
+                }
+
+                _ => {}
+            }
 
             // Skip if we have no bounding box
             if aabb[0] == 0.0 && aabb[1] == 0.0 && aabb[2] == 0.0 && aabb[3] == 0.0 {
+                culled_commands += 1;
                 continue;
             }
 
             let t_aabb = f32x4::new(aabb[0], aabb[1], aabb[2], aabb[3]);
 
             // Skip if the item is outside the screen
-            if !f32x4::test_intersect(state.screen_area, t_aabb) {
+            if !f32x4::test_intersect(screen_area, t_aabb) {
+                culled_commands += 1;
                 continue;
             }
 
-            let item = state.item_states.entry(command.id).or_insert(ItemState {
+            let item = item_states.entry(command.id).or_insert(ItemState {
                 ..Default::default()
             });
 
             let is_active = if command.id == focus_id.id { 1.0 } else { 0.0 };
 
-            item.active += anime_rate * (is_active - item.active);
+            item.active = animation::exponential_step(item.active, is_active, 8.0, delta_time);
             item.aabb = Vec4::new(aabb[0], aabb[1], aabb[2], aabb[3]);
-            item.frame = state.current_frame;
+            item.frame = current_frame;
+
+            if let RenderCommandConfig::Custom(ref custom) = command.config {
+                Self::push_custom_draw(state, custom, aabb, &mut primitives);
+                continue;
+            }
 
             let (cmd, color) = match command.config {
                 RenderCommandConfig::Rectangle(ref config) => {
-                    let corners = [
-                        config.corner_radii.top_left,
-                        config.corner_radii.top_right,
-                        config.corner_radii.bottom_left,
-                        config.corner_radii.bottom_right,
-                    ];
+                    let corners = Self::corner_radii(&config.corner_radii);
 
                     if corners.iter().all(|&x| x == 0.0) {
                         (RenderType::DrawRect, Self::color(config.color))
@@ -528,7 +695,18 @@ impl<'a> Ui<'_> {
                 RenderCommandConfig::Text(ref config) => {
                     let text = StringSlice::new(config.text);
 
-                    let gen = if let Some(text_data) = state.text_generator.get_text(
+                    if generate_text {
+                        let _ = state.text.generator.borrow_mut().queue_generate_text(
+                            text.as_str(),
+                            config.font_size as _,
+                            config.font_id as _,
+                            &state.bg_worker,
+                        );
+                    }
+
+                    let generator = state.text.generator.borrow();
+
+                    let gen = if let Some(text_data) = generator.get_text(
                         text.as_str(),
                         config.font_size as _,
                         config.font_id as _,
@@ -538,6 +716,8 @@ impl<'a> Ui<'_> {
                             handle: text_data.id,
                             width: text_data.width as _,
                             height: text_data.height as _,
+                            sub_pixel_steps_x: text_data.sub_pixel_step_x as _,
+                            ..Default::default()
                         }
                     } else {
                         DrawTextBufferData::default()
@@ -554,7 +734,6 @@ impl<'a> Ui<'_> {
                         //stride: (image.dimensions.width as u32 + 1) as _, // HACK
                         stride: image.dimensions.width as u32, // HACK
                         handle: image.data as _,
-                        rounding: false,
                     }),
                     Self::color(image.background_color),
                 ),
@@ -583,17 +762,6 @@ impl<'a> Ui<'_> {
                     )
                 }
 
-                RenderCommandConfig::ScissorStart() => {
-                    (RenderType::ScissorStart, Color::new(0.0, 0.0, 0.0, 0.0))
-                }
-
-                RenderCommandConfig::ScissorEnd() => {
-                    (RenderType::ScissorEnd, Color::new(0.0, 0.0, 0.0, 0.0))
-                }
-
-                RenderCommandConfig::Custom(_) => {
-                    (RenderType::Custom, Color::new(1.0, 1.0, 1.0, 1.0))
-                }
                 _ => (RenderType::None, Color::new(1.0, 1.0, 1.0, 1.0)),
             };
 
@@ -603,22 +771,45 @@ impl<'a> Ui<'_> {
                 color,
             };
 
-            // ignore scissor for now
-            if let RenderType::ScissorStart = cmd.render_type {
-                continue;
-            }
-
-            if let RenderType::ScissorEnd = cmd.render_type {
-                continue;
-            }
-
             primitives.push(cmd);
         }
+
+        let mut debug = state.debug.borrow_mut();
+        debug.stats.layout_commands = layout_commands;
+        debug.stats.culled_commands = culled_commands;
+        debug.stats.render_commands = primitives.len();
+        debug.stats.cached_texts = state.text.generator.borrow().cached_text_count();
+        debug.stats.io_queued = io_handler.queued_count();
+        debug.stats.io_inflight = io_handler.inflight_count();
+        debug.add_frame_time(delta_time);
+        }
+
+        self.update_focus_navigation();
+
+        let state = &mut self.state;
+
+        debug_assert!(state.style_stack.get_mut().is_empty(), "push_style without matching pop_style");
+
+        {
+            let zone = span!("measure cache");
+            let stats = state.text.generator.borrow_mut().end_frame();
+            state.debug.get_mut().stats.measured_texts = stats.entries;
+            zone.emit_value(stats.hits as u64);
+            zone.emit_text(&format!(
+                "hits {} misses {} evicted {} entries {}",
+                stats.hits, stats.misses, stats.evicted, stats.entries
+            ));
         }
 
         // remove all items that doesn't match the current frame
-        state.item_states
-            .retain(|_, item| item.frame == state.current_frame);
+        let current_frame = state.current_frame.get();
+        state.item_states.get_mut()
+            .retain(|_, item| item.frame == current_frame);
+        state.accessibility.get_mut().end_frame(state.item_states.get_mut());
+        state.widget_states.get_mut()
+            .retain(|_, widget| widget.frame == current_frame);
+        state.animations.get_mut().end_frame(current_frame);
+        state.state_store.get_mut().end_frame(current_frame);
 
         {
             let _ = span!("render");
@@ -627,26 +818,130 @@ impl<'a> Ui<'_> {
 
         // Generate primitives from all boxes
         //state.generate_primitives();
-        state.current_frame += 1;
+        state.current_frame.set(current_frame + 1);
+
+        let input = state.input.get_mut();
+        input.mouse_pos_prev = input.mouse_pos;
+    }
+
+    fn push_custom_draw(
+        state: &State,
+        custom: &Custom,
+        aabb: [f32; 4],
+        primitives: &mut Vec<RenderCommand>,
+    ) {
+        let custom_draws = state.custom_draws.borrow();
+        let generator = state.text.generator.borrow();
+
+        let Some(draw) = (custom.data as usize)
+            .checked_sub(1)
+            .and_then(|index| custom_draws.get(index))
+        else {
+            return;
+        };
+
+        match draw {
+            CustomDraw::RichText { config, colors } => {
+                // Not generated yet
+                let Some(text) = generator.get_rich_text(config) else {
+                    return;
+                };
+
+                for (layer, color) in text.layers.iter().zip(colors) {
+                    primitives.push(RenderCommand {
+                        bounding_box: aabb,
+                        render_type: RenderType::DrawTextBuffer(DrawTextBufferData {
+                            data: layer.data,
+                            handle: layer.id,
+                            width: layer.width as _,
+                            height: layer.height as _,
+                            sub_pixel_steps_x: layer.sub_pixel_step_x as _,
+                            ..Default::default()
+                        }),
+                        color: *color,
+                    });
+                }
+
+                for underline in &text.underlines {
+                    primitives.push(RenderCommand {
+                        bounding_box: [
+                            aabb[0] + underline.rect[0],
+                            aabb[1] + underline.rect[1],
+                            aabb[0] + underline.rect[2],
+                            aabb[1] + underline.rect[3],
+                        ],
+                        render_type: RenderType::DrawRect,
+                        color: colors[underline.layer as usize],
+                    });
+                }
+            }
+
+            CustomDraw::Text {
+                config,
+                color,
+                effect_colors,
+            } => {
+                let Some(text) = generator.get_generated(config) else {
+                    return;
+                };
+
+                let mut effects = [TextEffectLayer::default(); text_effects::EFFECT_LAYER_COUNT];
+
+                for ((effect, data), color) in effects
+                    .iter_mut()
+                    .zip(text.effect_layers)
+                    .zip(effect_colors)
+                {
+                    if let Some(color) = color {
+                        *effect = TextEffectLayer { data, color: *color };
+                    }
+                }
+
+                // The buffer is padded to fit the effects so move it back to keep the text
+                // at its layout position
+                let padding = text.padding as f32;
+
+                primitives.push(RenderCommand {
+                    bounding_box: [
+                        aabb[0] - padding,
+                        aabb[1] - padding,
+                        aabb[2] + padding,
+                        aabb[3] + padding,
+                    ],
+                    render_type: RenderType::DrawTextBuffer(DrawTextBufferData {
+                        data: text.data,
+                        handle: text.id,
+                        width: text.width as _,
+                        height: text.height as _,
+                        sub_pixel_steps_x: text.sub_pixel_step_x as _,
+                        effects,
+                    }),
+                    color: *color,
+                });
+            }
 
-        state.input.mouse_pos_prev = state.input.mouse_pos;
+            CustomDraw::Effects(effects) => {
+                let background = Self::color(custom.background_color);
+                let corners = Self::corner_radii(&custom.corner_radii);
+                effects.push_commands(aabb, background, corners, primitives);
+            }
+
+            CustomDraw::Image(image) => image.push_commands(aabb, primitives),
+        }
     }
 
     #[inline]
     pub fn id(&self, name: &str) -> Id {
-        let state = unsafe { &mut *self.state.get() };
-        state.layout.id(name)
+        self.state.layout.id(name)
     }
 
     #[inline]
     pub fn id_index(&self, name: &str, index: u32) -> Id {
-        let state = unsafe { &mut *self.state.get() };
-        state.layout.id_index(name, index)
+        self.state.layout.id_index(name, index)
     }
 
     pub fn is_visible(&self, id: Id) -> bool {
-        let state = unsafe { &mut *self.state.get() };
-        if let Some(state) = state.item_states.get(&id.id.id) {
+        if let Some(state) = self.state.item_states.borrow().get(&id.id.id) {
             state.aabb != Vec4::ZERO 
         } else {
             false
@@ -654,43 +949,61 @@ impl<'a> Ui<'_> {
     }
 
     pub fn hint_load_priority(&self, handle: IoHandle, priority: LoadPriority) {
-        let state = unsafe { &mut *self.state.get() };
-        state.io_handler.hint_priority(handle, priority);
+        self.state.io_handler.borrow_mut().hint_priority(handle, priority);
+    }
+
+    pub fn input(&self) -> Ref<'_, Input> {
+        self.state.input.borrow()
     }
 
-    pub fn input(&self) -> &mut Input {
-        let state = unsafe { &mut *self.state.get() };
-        &mut state.input
+    /// Used by the backend to update the input before `update` and `begin`
+    pub fn input_mut(&mut self) -> &mut Input {
+        self.state.input.get_mut()
     }
 
-    pub fn get_input_action(&self, _action: InputAction) -> ActionResponse {
-        ActionResponse::None
+    /// Returns the response for the action based on the bindings in the current [ActionMap]
+    pub fn get_input_action(&self, action: InputAction) -> ActionResponse {
+        let state = &self.state;
+        state.action_map.borrow().response(action, &state.input.borrow())
     }
 
-    pub fn load_font(&self, path: &str) -> InternalResult<FontHandle> {
-        let state = unsafe { &mut *self.state.get() };
-        state.text_generator.load_font(path, &state.bg_worker)
+    /// Replaces the bindings used by `get_input_action`, such as a map loaded with
+    /// [ActionMap::load]
+    pub fn set_action_map(&self, action_map: ActionMap) {
+        *self.state.action_map.borrow_mut() = action_map;
+    }
+
+    /// Starts loading a font from a file or url. The handle can be used directly and text
+    /// using it will show up once the font has been loaded. Use `font_load_state` to check
+    /// the progress.
+    pub fn load_font(&self, path: &str) -> FontHandle {
+        let state = &self.state;
+        state.text.generator.borrow_mut().load_font(
+            path,
+            &mut state.io_handler.borrow_mut(),
+            &state.job_system,
+        )
+    }
+
+    pub fn font_load_state(&self, font_id: FontHandle) -> FontLoadState {
+        self.state.text.generator.borrow().font_load_state(font_id)
     }
 
     pub fn load_image(&self, url: &str, load_options: Option<LoadOptions>) -> IoHandle {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
         let opts = load_options.unwrap_or_default();
-        state.io_handler.load_image(url, opts, &state.job_system)
+        state.io_handler.borrow_mut().load_image(url, opts, &state.job_system)
     }
 
     pub fn load_background_image(&self, url: &str) -> IoHandle {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
+        let screen_size = state.screen_size.get();
         let opts = LoadOptions {
             resize: Resize::IntegerVignette,
-            target_size: (state.screen_size.0 as _, state.screen_size.1 as _),
+            target_size: (screen_size.0 as _, screen_size.1 as _),
             ..Default::default()
         };
-        state.io_handler.load_image(url, opts, &state.job_system)
-    }
-
-    pub fn set_background_image(&self, handle: IoHandle, mode: BackgroundMode) {
-        let state = unsafe { &mut *self.state.get() };
-        state.background_image = Some(BackgroundImage { handle, mode });
+        state.io_handler.borrow_mut().load_image(url, opts, &state.job_system)
     }
 
     pub fn queue_generate_text(
@@ -699,98 +1012,129 @@ impl<'a> Ui<'_> {
         font_size: u32,
         font_id: FontHandle,
     ) -> Option<CachedString> {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &mut self.state;
         state
-            .text_generator
+            .text
+            .generator
+            .borrow_mut()
             .queue_generate_text(text, font_size, font_id, &state.bg_worker)
     }
 
     #[rustfmt::skip]
     pub fn button(&self, text: &str) -> Signal {
-        let state = unsafe { &mut *self.state.get() };
-        let id_name = text;
-        let mut signal = Signal::new();
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(text);
+        let font_size = theme.font_size(FontSize::Heading);
+        let font_id = state.text.active_font.get();
 
         // TODO: Cache
-        let text_size = state.text_generator.measure_text_size(text, state.active_font, 36).unwrap();
+        let text_size = state.text.generator.borrow_mut().measure_text_size(text, font_id, font_size).unwrap_or_default();
 
         state.layout.with(Declaration::new()
+            .id(id)
             .layout()
-                .width(fixed!(text_size.0 + 16.0))
+                .width(fixed!(text_size.0 + theme.spacing(Spacing::Medium) as f32))
                 .child_alignment(Alignment::new(LayoutAlignmentX::Center, LayoutAlignmentY::Center))
                 .padding(Padding::all(0))
             .end()
-                .corner_radius().all(16.0)
+                .corner_radius().all(theme.radius(Radius::Large))
             .end()
-                .background_color(ClayColor::rgba(152.0, 20.0, 31.0, 255.0)), |_ui|
+                .background_color(theme.color(ThemeColor::Accent)), |_ui|
             {
-                let font_id = state.active_font;
                 // TODO: Fix me
-                let _ = state.text_generator.queue_generate_text(text, 36, font_id, &state.bg_worker);
+                let _ = state.text.generator.borrow_mut().queue_generate_text(text, font_size, font_id, &state.bg_worker);
 
                 state.layout.text(text, TextConfig::new()
                     .font_id(font_id as u16)
-                    .font_size(36)
-                    .color(ClayColor::rgba(255.0, 255.0, 255.0, 255.0))
+                    .font_size(font_size as _)
+                    .color(theme.color(ThemeColor::Text))
                     .end());
-
-                let id = state.layout.id(id_name);
-
-                if let Some(item) = state.item_states.get_mut(&id.id.id) {
-                    signal = self.signal(item);
-                }
             },
         );
 
-        state.button_id += 1;
-        signal
+        state.button_id.set(state.button_id.get() + 1);
+        self.access_node(id, || AccessNode::new(Role::Button, text));
+        self.signal_id(id)
     }
 
     #[rustfmt::skip]
     pub fn button_test(&self, text: &str) -> Signal {
-        let state = unsafe { &mut *self.state.get() };
-        let id_name = text;
-        let mut signal = Signal::new();
+        let state = &self.state;
+        let id = state.layout.id(text);
+        let theme = self.theme();
+        let font_size = theme.font_size(FontSize::Heading);
+        let color = theme.color(ThemeColor::Text);
+
+        let font_id = state.text.active_font.get();
+        let _ = state.text.generator.borrow_mut().queue_generate_text(text, font_size, font_id, &state.bg_worker);
+
+        // Text elements don't carry the id so the text is wrapped in an element that does
+        state.layout.with(Declaration::new().id(id), |_ui| {
+            state.layout.text(text, TextConfig::new()
+                .font_id(font_id as u16)
+                .font_size(font_size as _)
+                .color(color)
+                .end());
+        });
 
-        let font_id = state.active_font;
-        let _ = state.text_generator.queue_generate_text(text, 36, font_id, &state.bg_worker);
+        state.button_id.set(state.button_id.get() + 1);
+        self.signal_id(id)
+    }
 
-        state.layout.text(text, TextConfig::new()
-            .font_id(font_id as u16)
-            .font_size(36)
-            .color(ClayColor::rgba(255.0, 255.0, 255.0, 205.0))
-            .end());
+    /// State of the item from the last frame it was drawn
+    pub fn item_state(&self, id: Id) -> Option<ItemState> {
+        self.state.item_states.borrow().get(&id.id.id).cloned()
+    }
 
-        let id = state.layout.id(id_name);
+    /// Moves the focus between the registered focusable items using the move actions
+    fn update_focus_navigation(&self) {
+        let directions = [
+            (InputAction::MoveUp, FocusDirection::Up),
+            (InputAction::MoveDown, FocusDirection::Down),
+            (InputAction::MoveLeft, FocusDirection::Left),
+            (InputAction::MoveRight, FocusDirection::Right),
+        ];
+
+        for (action, direction) in directions {
+            // The focused widget uses the action itself
+            if self.state.focus.borrow().is_captured(direction) {
+                continue;
+            }
 
-        if let Some(item) = state.item_states.get_mut(&id.id.id) {
-            signal = self.signal(item);
+            if self.get_input_action(action) == ActionResponse::Pressed {
+                self.navigate_focus(direction);
+                break;
+            }
         }
+    }
 
-    /*
-        if let Some(aabb) = state.layout.bounding_box(id) {
-            let item = state.item_states.entry(id.id.id).or_insert(ItemState {
-                aabb: Vec4::new(aabb.x, aabb.y, aabb.x + aabb.width, aabb.y + aabb.height),
-                ..Default::default()
-            });
+    /// Computes the signal for an element with an id. Elements that don't emit any render
+    /// commands (such as plain containers) get their bounds from the layout instead.
+    fn signal_id(&self, id: Id) -> Signal {
+        let state = &self.state;
+
+        let bounds = state.layout.bounding_box(id);
+        let mut item_states = state.item_states.borrow_mut();
+        let item = item_states.entry(id.id.id).or_default();
+
+        if let Some(aabb) = bounds {
             item.aabb = Vec4::new(aabb.x, aabb.y, aabb.x + aabb.width, aabb.y + aabb.height);
-            signal = self.signal(item)
+            item.frame = state.current_frame.get();
         }
 
-     */
+        let mut signal = self.signal(item);
+        drop(item_states);
 
-        state.button_id += 1;
-        signal
-    }
+        if self.is_focused(id) && self.get_input_action(InputAction::Select) == ActionResponse::Pressed {
+            signal.flags.insert(signal::SignalFlags::KEYBOARD_PRESSED);
+        }
 
-    pub fn item_state(&self, id: Id) -> Option<&ItemState> {
-        let state = unsafe { &mut *self.state.get() };
-        state.item_states.get(&id.id.id)
+        signal
     }
 
-    #[allow(dead_code)]
     fn signal(&self, item_state: &mut ItemState) -> Signal {
-        let state = unsafe { &mut *self.state.get() };
+        let state = &self.state;
 
         let mut signal = Signal::new();
 
@@ -798,7 +1142,17 @@ impl<'a> Ui<'_> {
             pos.x >= aabb.x && pos.y >= aabb.y && pos.x < aabb.z && pos.y < aabb.w
         }
 
-        let is_hovered = if contains(item_state.aabb, state.input.mouse_pos) {
+        // Items below an open modal don't get any mouse input
+        if state.overlay.borrow().is_input_blocked() {
+            item_state.was_hovered = false;
+            item_state.pressed_buttons = 0;
+            item_state.dragging_buttons = 0;
+            return signal;
+        }
+
+        let input = state.input.borrow();
+
+        let is_hovered = if contains(item_state.aabb, input.mouse_pos) {
             signal.flags.insert(signal::SignalFlags::HOVERING);
             true
         } else {
@@ -815,6 +1169,59 @@ impl<'a> Ui<'_> {
             item_state.was_hovered = false;
         }
 
+        for (index, flags) in signal::BUTTON_FLAGS.iter().enumerate() {
+            let button = &input.mouse_buttons[index];
+            let mask = 1u8 << index;
+
+            if button.clicked && is_hovered {
+                signal.flags.insert(flags.pressed);
+                item_state.pressed_buttons |= mask;
+
+                if button.double_clicked {
+                    signal.flags.insert(flags.double_clicked);
+                }
+            }
+
+            if item_state.pressed_buttons & mask == 0 {
+                continue;
+            }
+
+            // The item keeps tracking the button after the mouse leaves it so drags work
+            let drag_delta = input.mouse_pos - button.clicked_pos;
+            let is_dragging = item_state.dragging_buttons & mask != 0;
+
+            if button.down {
+                if !is_dragging && drag_delta.length() > input.settings.mouse_threshold {
+                    signal.flags.insert(flags.drag_start);
+                    item_state.dragging_buttons |= mask;
+                }
+
+                if item_state.dragging_buttons & mask != 0 {
+                    signal.flags.insert(flags.dragging);
+                    signal.drag_delta = drag_delta;
+                }
+            } else {
+                signal.flags.insert(flags.released);
+
+                if is_dragging {
+                    signal.flags.insert(flags.drag_end);
+                    signal.drag_delta = drag_delta;
+                } else if is_hovered {
+                    signal.flags.insert(flags.clicked);
+                }
+
+                item_state.pressed_buttons &= !mask;
+                item_state.dragging_buttons &= !mask;
+            }
+        }
+
+        let wheel = input.mouse_wheel();
+
+        if is_hovered && wheel != glam::Vec2::ZERO {
+            signal.flags.insert(signal::SignalFlags::SCROLLED);
+            signal.scroll = wheel;
+        }
+
         /*
         let mut signal = Signal::new();
         let box_area = box_area.as_mut_unsafe();
@@ -829,20 +1236,52 @@ impl<'a> Ui<'_> {
         signal
     }
 
-    pub fn renderer(&mut self) -> &Box<dyn Renderer> {
-        let state = unsafe { &mut *self.state.get() };
-        &state.renderer
+    pub fn renderer(&mut self) -> &dyn Renderer {
+        self.state.renderer.as_ref()
     }
 
     pub fn update(&mut self) {
-        let state = unsafe { &mut *self.state.get() };
-        state.text_generator.update();
-        state.io_handler.update(&state.job_system);
+        let state = &mut self.state;
+
+        // Dragging scroll containers is enabled for touch input, Clay keeps them scrolling
+        // with some momentum after the touch is released
+        let input = state.input.get_mut();
+        let is_touch = input.mouse_source() != MouseSource::Mouse || input.touch.is_touching();
+
+        if input.mouse_pos().is_finite() {
+            let pos = input.mouse_pos();
+            let is_down = input.is_mouse_down(MouseButton::Left) || input.touch.is_touching();
+            state.layout.pointer_state((pos.x, pos.y).into(), is_down);
+        }
+
+        state
+            .layout
+            .update_scroll_containers(is_touch, (0.0, 0.0).into(), input.delta_time);
+
+        let io_handler = state.io_handler.get_mut();
+
+        if state
+            .text
+            .generator
+            .borrow_mut()
+            .update(io_handler, &state.bg_worker)
+        {
+            state.layout.reset_measure_text_cache();
+        }
+
+        io_handler.update(&state.job_system);
     }
 
-    pub fn get_text(&self, text: &str, size: u32, handle: FontHandle) -> Option<&CachedString> {
-        let state = unsafe { &mut *self.state.get() };
-        state.text_generator.get_text(text, size, handle)
+    /// Touch gestures recognized during the last input update
+    pub fn gestures(&self) -> Ref<'_, [touch::Gesture]> {
+        Ref::map(self.state.input.borrow(), |input| input.gestures())
+    }
+
+    pub fn get_text(&self, text: &str, size: u32, handle: FontHandle) -> Option<Ref<'_, CachedString>> {
+        Ref::filter_map(self.state.text.generator.borrow(), |generator| {
+            generator.get_text(text, size, handle)
+        })
+        .ok()
     }
 }
 
@@ -851,3 +1290,365 @@ pub struct ApplicationSettings {
     pub width: usize,
     pub height: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::DummyRenderer;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    // Clay has a single global context so only one Ui can be used at a time. Under Miri the
+    // Clay C library is replaced by a Rust stand-in, run with
+    // MIRIFLAGS="-Zmiri-disable-isolation -Zmiri-tree-borrows -Zmiri-ignore-leaks" cargo +nightly miri test -p flowi_core
+    // The worker threads are still running when the tests end, which ignore-leaks allows.
+    // Tree borrows is needed as crossbeam-epoch, used by the job system, fails stacked borrows.
+    static UI_LOCK: Mutex<()> = Mutex::new(());
+
+    // Miri runs the background jobs much slower
+    const TIMEOUT: Duration = Duration::from_secs(if cfg!(miri) { 600 } else { 10 });
+
+    fn frame<F: FnOnce(&Ui)>(ui: &mut Ui, f: F) {
+        ui.update();
+        ui.begin(1.0 / 60.0, 640, 480);
+        f(ui);
+        ui.end();
+    }
+
+    /// Runs frames until `done` returns true
+    fn wait_for<F: Fn(&Ui) -> bool>(ui: &mut Ui, done: F) {
+        let start = Instant::now();
+
+        while !done(ui) {
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+            frame(ui, |_| {});
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Cache directory in the system temp dir that is removed when dropped
+    struct TempCache(std::path::PathBuf);
+
+    impl TempCache {
+        /// Creates the cache with the local `files` stored under the given urls
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("flowi_core_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let mut cache_path = std::path::PathBuf::new();
+            for (url, file) in files {
+                io::cache::CacheStore::get_cache_path(url, &dir, &mut cache_path);
+                // fs::copy uses copy_file_range which Miri doesn't support
+                std::fs::write(&cache_path, std::fs::read(file).unwrap()).unwrap();
+            }
+
+            Self(dir)
+        }
+
+        /// Ui that loads urls from this cache
+        fn ui(&self) -> Box<Ui<'static>> {
+            let io_handler = IoHandler::with_settings(io::io::IoSettings {
+                cache_dir: self.0.to_str().unwrap().to_string(),
+                remote_delay: Duration::from_millis(0),
+            });
+
+            Ui::with_io_handler(Box::new(DummyRenderer {}), io_handler)
+        }
+    }
+
+    impl Drop for TempCache {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_nested_layouts() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ui = Ui::new(Box::new(DummyRenderer {}));
+
+        let mut on = false;
+        let mut value = 0.5;
+        let mut text = String::from("text");
+        let mut selected = 0;
+        let mut open = true;
+
+        for _ in 0..3 {
+            frame(&mut ui, |ui| {
+                ui.with_layout(&Declaration::new(), |ui| {
+                    ui.with_focus_scope(FocusScope::new(), |ui| {
+                        ui.with_style(&[StyleVar::Spacing(Spacing::Medium, 4)], |ui| {
+                            ui.toggle("toggle", &mut on);
+                            ui.slider("slider", &mut value, 0.0..=1.0, 0.1);
+
+                            ui.with_layout(&Declaration::new(), |ui| {
+                                ui.text_field("text", &mut text);
+                                ui.dropdown("dropdown", &["a", "b"], &mut selected);
+                                ui.tooltip(ui.id("toggle"), "tooltip");
+                            });
+                        });
+                    });
+
+                    ui.modal("modal", &mut open, |ui| {
+                        ui.with_layout(&Declaration::new(), |ui| {
+                            ui.animate(ui.id("modal"), "x", 1.0f32, Animation::exponential(8.0));
+                            ui.button_test("ok");
+                        });
+                    });
+                });
+
+                ui.show_toast("toast");
+            });
+        }
+
+        assert!(ui.item_state(ui.id("toggle")).is_some());
+        assert_eq!(ui.theme(), Theme::default().scaled(ui.ui_scale()));
+    }
+
+    #[test]
+    fn test_debug_overlay() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ui = Ui::new(Box::new(DummyRenderer {}));
+        let mut on = false;
+
+        ui.toggle_debug_overlay();
+        assert!(ui.is_debug_overlay_enabled());
+        ui.input_mut().mouse_pos = glam::Vec2::new(20.0, 20.0);
+
+        for _ in 0..3 {
+            frame(&mut ui, |ui| {
+                ui.toggle("toggle", &mut on);
+            });
+        }
+
+        let stats = ui.debug_stats();
+        assert!(stats.render_commands > 0);
+        assert!((stats.frame_time - 1.0 / 60.0).abs() < 1e-4);
+
+
+        ui.set_debug_overlay(false);
+        frame(&mut ui, |ui| {
+            ui.toggle("toggle", &mut on);
+        });
+        // The stats panel and Clay's inspector are gone
+        assert!(ui.debug_stats().layout_commands < stats.layout_commands);
+
+        let visible = ui.debug_stats();
+
+        // A box outside the screen is counted as culled
+        frame(&mut ui, |ui| {
+            ui.toggle("toggle", &mut on);
+            ui.with_layout(Declaration::new()
+                .layout()
+                    .width(fixed!(10.0))
+                    .height(fixed!(10.0))
+                .end()
+                .floating()
+                    .attach_to(clay_layout::elements::FloatingAttachToElement::Root)
+                    .offset((2000.0, 2000.0).into())
+                .end()
+                .background_color(ClayColor::rgba(255.0, 0.0, 0.0, 255.0)), |_| {});
+        });
+
+        let offscreen = ui.debug_stats();
+        assert_eq!(offscreen.culled_commands, visible.culled_commands + 1);
+        assert_eq!(offscreen.render_commands, visible.render_commands);
+    }
+
+    #[test]
+    fn test_default_localization() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let ui = Ui::new(Box::new(DummyRenderer {}));
+
+        // The English table is built in so nothing has to be loaded for English text
+        assert_eq!(ui.localize("entry.by"), "by");
+        assert_eq!(ui.format_date(Date::new(1993, 12, 27)), "December 27, 1993");
+
+        ui.set_localization(Localization::parse("sv", "entry.by = av").unwrap());
+        assert_eq!(ui.localize("entry.by"), "av");
+        assert_eq!(ui.localize("month.1"), "January");
+    }
+
+    #[test]
+    fn test_accessibility_tree() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ui = Ui::new(Box::new(DummyRenderer {}));
+        let mut on = true;
+        let mut selected = 1;
+
+        // Nothing is collected until a bridge enables it
+        ui.announce("ignored", Politeness::Polite);
+        ui.set_accessibility_enabled(true);
+
+        frame(&mut ui, |ui| {
+            ui.toggle("toggle", &mut on);
+            ui.list("list", &["a", "b"], &mut selected, 2);
+            ui.announce("hello", Politeness::Polite);
+        });
+
+        {
+            let tree = ui.accessibility_tree();
+            let toggle = tree.find(ui.id("toggle")).unwrap();
+            assert_eq!((toggle.role, toggle.label.as_str()), (Role::Switch, "toggle"));
+            assert!(toggle.states.contains(AccessStates::CHECKED));
+            assert!(toggle.bounds[2] > toggle.bounds[0]);
+
+            let (list_index, _) = tree.nodes().iter().enumerate().find(|(_, node)| node.role == Role::List).unwrap();
+            let items: Vec<_> = tree.children(Some(list_index)).map(|(_, node)| node.label.as_str()).collect();
+            assert_eq!(items, ["a", "b"]);
+            assert!(tree.children(Some(list_index)).nth(1).unwrap().1.states.contains(AccessStates::SELECTED));
+        }
+
+        let announcements = ui.take_announcements();
+        assert_eq!(announcements.len(), 1);
+        assert_eq!(announcements[0].text, "hello");
+        assert!(ui.take_announcements().is_empty());
+    }
+
+    #[test]
+    fn test_text_measured_during_layout() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ui = Ui::new(Box::new(DummyRenderer {}));
+
+        let font = ui.load_font("../flowi/data/montserrat-regular.ttf");
+        ui.set_font(font);
+
+        // Fonts that are still loading are measured as empty
+        assert_eq!(ui.text_size("Hello", 20).width, 0.0);
+
+        wait_for(&mut ui, |ui| ui.font_load_state(font) == FontLoadState::Loaded);
+
+        let mut inner_size = Dimensions::new(0.0, 0.0);
+
+        frame(&mut ui, |ui| {
+            ui.with_layout(&Declaration::new(), |ui| {
+                ui.with_layout(Declaration::new().id(ui.id("label")), |ui| {
+                    ui.text_with_layout("Hello", 20, ClayColor::rgba(255.0, 255.0, 255.0, 255.0), &Declaration::new());
+                    inner_size = ui.text_size("Hello", 20);
+                });
+            });
+        });
+
+        let label = ui.state.layout.bounding_box(ui.id("label")).unwrap();
+
+        assert!(inner_size.width > 0.0);
+        assert_eq!(label.width, inner_size.width);
+    }
+
+    #[test]
+    fn test_font_load_from_url() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let url = "https://localhost/flowi_core/montserrat-regular.ttf";
+        let cache = TempCache::new("font_load", &[(url, "../flowi/data/montserrat-regular.ttf")]);
+        let mut ui = cache.ui();
+
+        let font = ui.load_font(url);
+        ui.set_font(font);
+
+        wait_for(&mut ui, |ui| ui.font_load_state(font) == FontLoadState::Loaded);
+
+        assert!(ui.text_size("Hello", 20).width > 0.0);
+    }
+
+    #[test]
+    fn test_rich_text_spans_measured() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ui = Ui::new(Box::new(DummyRenderer {}));
+
+        let font = ui.load_font("../flowi/data/montserrat-regular.ttf");
+        ui.set_font(font);
+        wait_for(&mut ui, |ui| ui.font_load_state(font) == FontLoadState::Loaded);
+
+        let spans = [TextSpan::new("Hello").size(20), TextSpan::new(" world").size(20)];
+        let mut sizes = (Dimensions::new(0.0, 0.0), Dimensions::new(0.0, 0.0));
+
+        frame(&mut ui, |ui| {
+            ui.with_layout(&Declaration::new(), |ui| {
+                ui.with_layout(Declaration::new().id(ui.id("rich")), |ui| {
+                    ui.rich_text_with_layout(&spans, &Declaration::new());
+                });
+            });
+
+            sizes = (ui.text_size("Hello", 20), ui.text_size("Hello world", 20));
+        });
+
+        let rich = ui.state.layout.bounding_box(ui.id("rich")).unwrap();
+
+        // The spans are shaped as one line so they measure the same as the joined text
+        assert!(rich.width > sizes.0.width);
+        assert!((rich.width - sizes.1.width).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_image_load() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Images are only read from the cache or fetched remotely
+        let url = "https://localhost/flowi_core/solid_red.png";
+        let cache = TempCache::new("image_load", &[(url, "data/png/solid_red.png")]);
+        let mut ui = cache.ui();
+
+        let handle = ui.load_image(url, None);
+        let id = ui.id("image");
+
+        // The image is declared while it's loading and once it has been loaded
+        let is_loaded = |ui: &Ui| {
+            frame_image(ui, id, handle);
+            let mut io_handler = ui.state.io_handler.borrow_mut();
+            io_handler.get_loaded_as::<ImageInfo>(handle).is_some()
+        };
+
+        fn frame_image(ui: &Ui, id: Id, handle: IoHandle) {
+            ui.with_layout(&Declaration::new(), |ui| {
+                ui.image_with_opts(id, handle, 1.0, (32.0, 32.0));
+            });
+        }
+
+        wait_for(&mut ui, |ui| {
+            let state = &ui.state;
+            let mut io_handler = state.io_handler.borrow_mut();
+            io_handler.get_loaded_as::<ImageInfo>(handle).is_some()
+        });
+
+        frame(&mut ui, |ui| {
+            assert!(is_loaded(ui));
+        });
+
+        let image = ui.item_state(id).unwrap();
+        assert_eq!(image.aabb.z - image.aabb.x, 32.0);
+    }
+
+    #[test]
+    fn test_image_load_error() {
+        let _lock = UI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ui = Ui::new(Box::new(DummyRenderer {}));
+
+        let handle = ui.load_image("data/png/does_not_exist.png", None);
+        let id = ui.id("image");
+
+        let start = Instant::now();
+
+        // The error is picked up when the image is declared
+        while !ui.state.io_handler.borrow().has_failed(handle) {
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+
+            frame(&mut ui, |ui| {
+                ui.with_layout(&Declaration::new(), |ui| {
+                    ui.image_view(id, handle, (32.0, 32.0), &ImageOptions::new().corner_radius(4.0));
+                });
+            });
+
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(ui.image_load_state(handle), ImageLoadState::Failed);
+        // The failed job has been reported and dropped
+        assert_eq!(ui.state.io_handler.borrow().inflight_count(), 0);
+
+        // The error glyph is still laid out at the size of the image
+        let image = ui.item_state(id).unwrap();
+        assert_eq!(image.aabb.z - image.aabb.x, 32.0);
+    }
+}
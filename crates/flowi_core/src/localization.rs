@@ -0,0 +1,362 @@
+use crate::Ui;
+use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LocalizationError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {text}")]
+    Parse { line: usize, text: String },
+}
+
+/// Plural form selected by the count, used as a suffix on the message id such as
+/// `productions.one`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    /// Picks the plural form for `count` using the rules of the language
+    pub fn for_count(language: &str, count: u64) -> Self {
+        let primary = language.split(['-', '_']).next().unwrap_or(language);
+        let (ones, tens) = (count % 10, count % 100);
+        let few = (2..=4).contains(&ones) && !(12..=14).contains(&tens);
+
+        match primary {
+            "ja" | "ko" | "th" | "vi" | "zh" => Self::Other,
+            "fr" | "pt" if count <= 1 => Self::One,
+            "cs" | "sk" if (2..=4).contains(&count) => Self::Few,
+            "pl" if count == 1 => Self::One,
+            "pl" if few => Self::Few,
+            "pl" => Self::Many,
+            "ru" | "uk" if ones == 1 && tens != 11 => Self::One,
+            "ru" | "uk" if few => Self::Few,
+            "ru" | "uk" => Self::Many,
+            _ if count == 1 => Self::One,
+            _ => Self::Other,
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Self::Zero => "zero",
+            Self::One => "one",
+            Self::Few => "few",
+            Self::Many => "many",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Calendar date where the month and day can be unknown, such as for releases that are only
+/// known by year
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Date {
+    pub year: i32,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u8, day: u8) -> Self {
+        Self {
+            year,
+            month: Some(month),
+            day: Some(day),
+        }
+    }
+
+    /// Parses `YYYY-MM-DD`, `YYYY-MM` or `YYYY`
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next().map(str::parse::<u8>).transpose().ok()?;
+        let day = parts.next().map(str::parse::<u8>).transpose().ok()?;
+
+        if month.is_some_and(|month| !(1..=12).contains(&month))
+            || day.is_some_and(|day| !(1..=31).contains(&day))
+        {
+            return None;
+        }
+
+        Some(Self { year, month, day })
+    }
+}
+
+/// String table for one language. Each line in the file has the format
+///
+/// ```text
+/// # Comment
+/// menu.settings = Settings
+/// entry.released = Released {date}
+/// productions.one = {count} production
+/// productions.other = {count} productions
+/// date.full = {month_name} {day}, {year}
+/// month.1 = January
+/// ```
+///
+/// `{name}` is replaced by the argument with the same name. Plural forms are selected with
+/// `.zero`, `.one`, `.few`, `.many` and `.other` suffixes, `.zero` is optional for every
+/// language. Dates use the `date.full`, `date.month` and `date.year` patterns.
+#[derive(Debug, Clone)]
+pub struct Localization {
+    language: String,
+    strings: HashMap<String, String>,
+}
+
+/// English table that is used until another language is set, it's also the fallback for
+/// messages missing in other languages
+const BUILTIN_STRINGS: &str = include_str!("../data/lang/en.lang");
+
+impl Default for Localization {
+    /// The built-in English table
+    fn default() -> Self {
+        Self::parse("en", BUILTIN_STRINGS).expect("Invalid built-in strings")
+    }
+}
+
+impl Localization {
+    /// Empty table where every message is shown by its id
+    pub fn new(language: &str) -> Self {
+        Self {
+            language: language.to_string(),
+            strings: HashMap::new(),
+        }
+    }
+
+    /// Loads a string table from a file. The language is the file name without the
+    /// extension, such as `sv` for `data/lang/sv.lang`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LocalizationError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let language = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("en");
+        Self::parse(language, &text)
+    }
+
+    /// Parses a string table from a string
+    pub fn parse(language: &str, text: &str) -> Result<Self, LocalizationError> {
+        let mut localization = Self::new(language);
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((id, value)) = line.split_once('=') else {
+                return Err(LocalizationError::Parse {
+                    line: index + 1,
+                    text: format!("Expected 'id = Text', got '{}'", line),
+                });
+            };
+
+            localization.strings.insert(id.trim().to_string(), value.trim().to_string());
+        }
+
+        Ok(localization)
+    }
+
+    /// Uses the strings of `fallback` for messages missing in this table, such as the
+    /// English table for a partial translation
+    pub fn with_fallback(mut self, fallback: &Localization) -> Self {
+        for (id, value) in &fallback.strings {
+            self.strings.entry(id.clone()).or_insert_with(|| value.clone());
+        }
+
+        self
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The text of the message, or the id if the message is missing
+    pub fn get<'a>(&'a self, id: &'a str) -> &'a str {
+        self.strings.get(id).map_or(id, String::as_str)
+    }
+
+    /// The message with the placeholders replaced by `args`
+    pub fn format(&self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        replace_placeholders(self.get(id), args)
+    }
+
+    /// The plural form of the message for `count`. `{count}` is replaced by the count.
+    pub fn format_plural(&self, id: &str, count: u64, args: &[(&str, &dyn Display)]) -> String {
+        let category = PluralCategory::for_count(&self.language, count);
+        let zero = (count == 0).then_some(PluralCategory::Zero);
+
+        let text = zero
+            .into_iter()
+            .chain([category, PluralCategory::Other])
+            .find_map(|category| self.strings.get(&format!("{}.{}", id, category.suffix())))
+            .map_or(self.get(id), String::as_str);
+
+        let mut all_args: Vec<(&str, &dyn Display)> = vec![("count", &count)];
+        all_args.extend_from_slice(args);
+        replace_placeholders(text, &all_args)
+    }
+
+    /// Formats the date with the pattern for the parts that are known. Falls back to ISO
+    /// 8601 if the table has no date patterns.
+    pub fn format_date(&self, date: Date) -> String {
+        let (id, default) = match (date.month, date.day) {
+            (Some(_), Some(_)) => ("date.full", "{year}-{month}-{day}"),
+            (Some(_), None) => ("date.month", "{year}-{month}"),
+            _ => ("date.year", "{year}"),
+        };
+
+        let month = date.month.unwrap_or(1);
+        let day = date.day.unwrap_or(1);
+        let month_name = self.get_month_name(month);
+        let (padded_month, padded_day) = (format!("{:02}", month), format!("{:02}", day));
+        let pattern = self.strings.get(id).map_or(default, String::as_str);
+
+        replace_placeholders(
+            pattern,
+            &[
+                ("year", &date.year),
+                ("month", &padded_month),
+                ("day", &padded_day),
+                ("day_number", &day),
+                ("month_name", &month_name),
+            ],
+        )
+    }
+
+    fn get_month_name(&self, month: u8) -> String {
+        let id = format!("month.{}", month);
+        self.strings.get(&id).cloned().unwrap_or_else(|| month.to_string())
+    }
+}
+
+/// Replaces `{name}` in `text` with the matching argument. Unknown placeholders are kept.
+fn replace_placeholders(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let arg = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            args.iter().find(|(arg, _)| *arg == name).map(|(_, value)| (end, value))
+        });
+
+        match arg {
+            Some((end, value)) => {
+                let _ = write!(output, "{}", value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+impl Ui<'_> {
+    /// Switches the language. Messages that are missing in the table are shown in English. Generated text is dropped so nothing from the previous language
+    /// is kept around.
+    pub fn set_localization(&self, localization: Localization) {
+        *self.state.localization.borrow_mut() = localization.with_fallback(&Localization::default());
+        self.state.text.generator.borrow_mut().clear_text_cache();
+    }
+
+    /// Language of the current string table
+    pub fn language(&self) -> String {
+        self.state.localization.borrow().language().to_string()
+    }
+
+    /// The text of the message in the current language
+    pub fn localize(&self, id: &str) -> &str {
+        self.frame_str(self.state.localization.borrow().get(id))
+    }
+
+    /// The message with `{name}` placeholders replaced by `args`
+    pub fn localize_args(&self, id: &str, args: &[(&str, &dyn Display)]) -> &str {
+        let text = self.state.localization.borrow().format(id, args);
+        self.frame_str(&text)
+    }
+
+    /// The plural form of the message for `count`, see [Localization::format_plural]
+    pub fn localize_plural(&self, id: &str, count: u64, args: &[(&str, &dyn Display)]) -> &str {
+        let text = self.state.localization.borrow().format_plural(id, count, args);
+        self.frame_str(&text)
+    }
+
+    /// The date formatted for the current language
+    pub fn format_date(&self, date: Date) -> &str {
+        let text = self.state.localization.borrow().format_date(date);
+        self.frame_str(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWEDISH: &str = "
+        # Swedish
+        menu.settings = Inställningar
+        entry.by = av {author}
+        productions.zero = Inga produktioner
+        productions.one = {count} produktion
+        productions.other = {count} produktioner
+        date.full = {day_number} {month_name} {year}
+        date.month = {month_name} {year}
+        month.12 = december
+    ";
+
+    #[test]
+    fn test_format() {
+        let sv = Localization::parse("sv", SWEDISH).unwrap();
+
+        assert_eq!(sv.get("menu.settings"), "Inställningar");
+        assert_eq!(sv.get("menu.search"), "menu.search");
+        assert_eq!(sv.format("entry.by", &[("author", &"Spaceballs")]), "av Spaceballs");
+        assert_eq!(sv.format("entry.by", &[]), "av {author}");
+
+        assert_eq!(sv.format_plural("productions", 0, &[]), "Inga produktioner");
+        assert_eq!(sv.format_plural("productions", 1, &[]), "1 produktion");
+        assert_eq!(sv.format_plural("productions", 21, &[]), "21 produktioner");
+
+        assert_eq!(sv.format_date(Date::parse("1993-12-27").unwrap()), "27 december 1993");
+        assert_eq!(sv.format_date(Date::parse("1993-12").unwrap()), "december 1993");
+        assert_eq!(Localization::new("en").format_date(Date::new(1993, 2, 7)), "1993-02-07");
+        assert_eq!(Date::parse("1993-13-01"), None);
+
+        let en = Localization::parse("en", "menu.search = Search").unwrap();
+        assert_eq!(sv.with_fallback(&en).get("menu.search"), "Search");
+
+        assert!(matches!(
+            Localization::parse("sv", "menu.settings"),
+            Err(LocalizationError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_plural_rules() {
+        let categories = |language| [1, 2, 5, 21].map(|count| PluralCategory::for_count(language, count));
+
+        use PluralCategory::*;
+        assert_eq!(categories("en-GB"), [One, Other, Other, Other]);
+        assert_eq!(categories("ru"), [One, Few, Many, One]);
+        assert_eq!(categories("pl"), [One, Few, Many, Many]);
+        assert_eq!(categories("ja"), [Other, Other, Other, Other]);
+        assert_eq!(PluralCategory::for_count("fr", 0), One);
+    }
+}
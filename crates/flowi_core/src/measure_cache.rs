@@ -0,0 +1,248 @@
+use crate::font::{FontHandle, RichGeneratorConfig};
+use fxhash::FxHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Number of frames an unused measurement is kept around before it's evicted
+const MAX_UNUSED_FRAMES: u64 = 120;
+
+/// Everything that can change the size of a measured string
+#[derive(Debug, Clone, Copy, Hash, PartialEq)]
+pub(crate) struct MeasureKey<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) font: FontHandle,
+    pub(crate) size: u32,
+    pub(crate) letter_spacing: u16,
+    pub(crate) wrap_width: Option<u32>,
+}
+
+struct MeasureEntry {
+    text: String,
+    font: FontHandle,
+    size: u32,
+    letter_spacing: u16,
+    wrap_width: Option<u32>,
+    result: (f32, f32),
+    last_used: u64,
+}
+
+impl MeasureEntry {
+    fn matches(&self, key: &MeasureKey) -> bool {
+        self.font == key.font
+            && self.size == key.size
+            && self.letter_spacing == key.letter_spacing
+            && self.wrap_width == key.wrap_width
+            && self.text == key.text
+    }
+}
+
+struct RichMeasureEntry {
+    result: (f32, f32),
+    last_used: u64,
+}
+
+/// Stats for the frame that just ended
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct MeasureCacheStats {
+    pub(crate) hits: u32,
+    pub(crate) misses: u32,
+    pub(crate) evicted: u32,
+    pub(crate) entries: u32,
+}
+
+/// Caches text measurements so Clay's layout passes don't have to shape the same strings
+/// over and over again. Entries are stored by the hash of the key so lookups don't need to
+/// allocate, the full key is kept in the entry to verify the match. Rich text is keyed by
+/// its config as it's already owned by the caller.
+#[derive(Default)]
+pub(crate) struct MeasureCache {
+    entries: HashMap<u64, MeasureEntry>,
+    rich_entries: HashMap<RichGeneratorConfig, RichMeasureEntry>,
+    frame: u64,
+    stats: MeasureCacheStats,
+}
+
+impl MeasureCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn hash_key(key: &MeasureKey) -> u64 {
+        let mut hasher = FxHasher::default();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the cached size or measures the text. Failed measurements (such as for fonts
+    /// that are still loading) are not cached.
+    pub(crate) fn get_or_measure<F>(&mut self, key: MeasureKey, measure: F) -> Option<(f32, f32)>
+    where
+        F: FnOnce() -> Option<(f32, f32)>,
+    {
+        let hash = Self::hash_key(&key);
+
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            if entry.matches(&key) {
+                entry.last_used = self.frame;
+                self.stats.hits += 1;
+                return Some(entry.result);
+            }
+        }
+
+        self.stats.misses += 1;
+        let result = measure()?;
+
+        // On a hash collision the old entry is replaced
+        self.entries.insert(
+            hash,
+            MeasureEntry {
+                text: key.text.to_string(),
+                font: key.font,
+                size: key.size,
+                letter_spacing: key.letter_spacing,
+                wrap_width: key.wrap_width,
+                result,
+                last_used: self.frame,
+            },
+        );
+
+        Some(result)
+    }
+
+    /// Same as [MeasureCache::get_or_measure] for rich text
+    pub(crate) fn get_or_measure_rich<F>(&mut self, config: &RichGeneratorConfig, measure: F) -> Option<(f32, f32)>
+    where
+        F: FnOnce() -> Option<(f32, f32)>,
+    {
+        if let Some(entry) = self.rich_entries.get_mut(config) {
+            entry.last_used = self.frame;
+            self.stats.hits += 1;
+            return Some(entry.result);
+        }
+
+        self.stats.misses += 1;
+        let result = measure()?;
+
+        self.rich_entries.insert(
+            config.clone(),
+            RichMeasureEntry {
+                result,
+                last_used: self.frame,
+            },
+        );
+
+        Some(result)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.rich_entries.clear();
+    }
+
+    /// Evicts entries that haven't been used for a while and returns the stats for the
+    /// frame that ended.
+    pub(crate) fn end_frame(&mut self) -> MeasureCacheStats {
+        let frame = self.frame;
+        let count = self.entries.len() + self.rich_entries.len();
+
+        self.entries
+            .retain(|_, entry| entry.last_used + MAX_UNUSED_FRAMES >= frame);
+        self.rich_entries
+            .retain(|_, entry| entry.last_used + MAX_UNUSED_FRAMES >= frame);
+
+        let entries = self.entries.len() + self.rich_entries.len();
+        let mut stats = self.stats;
+        stats.evicted = (count - entries) as u32;
+        stats.entries = entries as u32;
+
+        self.stats = MeasureCacheStats::default();
+        self.frame += 1;
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str) -> MeasureKey<'_> {
+        MeasureKey {
+            text,
+            font: 0,
+            size: 36,
+            letter_spacing: 0,
+            wrap_width: None,
+        }
+    }
+
+    #[test]
+    fn test_measure_cache_hit_and_miss() {
+        let mut cache = MeasureCache::new();
+
+        assert_eq!(cache.get_or_measure(key("test"), || Some((10.0, 20.0))), Some((10.0, 20.0)));
+        // Cached value is used so the measure function isn't called
+        assert_eq!(cache.get_or_measure(key("test"), || unreachable!()), Some((10.0, 20.0)));
+
+        let wrapped = MeasureKey {
+            wrap_width: Some(100),
+            ..key("test")
+        };
+        assert_eq!(cache.get_or_measure(wrapped, || Some((5.0, 40.0))), Some((5.0, 40.0)));
+
+        let stats = cache.end_frame();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 2);
+    }
+
+    #[test]
+    fn test_measure_cache_skips_failed() {
+        let mut cache = MeasureCache::new();
+        assert_eq!(cache.get_or_measure(key("test"), || None), None);
+        assert_eq!(cache.get_or_measure(key("test"), || Some((1.0, 1.0))), Some((1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_measure_cache_rich_text() {
+        let mut cache = MeasureCache::new();
+        let span = |text: &str, font| crate::font::RichSpanConfig {
+            text: text.to_string(),
+            font_handle: font,
+            size: 36,
+            layer: 0,
+            underline: false,
+        };
+        let config = RichGeneratorConfig {
+            spans: vec![span("by ", 0), span("Spaceballs", 1)],
+            wrap_width: None,
+        };
+
+        assert_eq!(cache.get_or_measure_rich(&config, || Some((10.0, 20.0))), Some((10.0, 20.0)));
+        assert_eq!(cache.get_or_measure_rich(&config, || unreachable!()), Some((10.0, 20.0)));
+
+        // A different font for a span is a different measurement
+        let mut bold = config.clone();
+        bold.spans[0].font_handle = 1;
+        assert_eq!(cache.get_or_measure_rich(&bold, || Some((12.0, 20.0))), Some((12.0, 20.0)));
+
+        let stats = cache.end_frame();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+    }
+
+    #[test]
+    fn test_measure_cache_eviction() {
+        let mut cache = MeasureCache::new();
+        cache.get_or_measure(key("old"), || Some((1.0, 1.0)));
+
+        for _ in 0..=MAX_UNUSED_FRAMES {
+            cache.get_or_measure(key("new"), || Some((2.0, 2.0)));
+            assert_eq!(cache.end_frame().evicted, 0);
+        }
+
+        cache.get_or_measure(key("new"), || Some((2.0, 2.0)));
+        let stats = cache.end_frame();
+        assert_eq!(stats.evicted, 1);
+        assert_eq!(stats.entries, 1);
+    }
+}
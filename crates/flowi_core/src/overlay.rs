@@ -0,0 +1,309 @@
+use crate::focus::{FocusDirection, FocusScope};
+use crate::theme::{FontSize, Radius, Spacing};
+use crate::{fixed, ActionResponse, Alignment, ClayColor, Declaration, Id, InputAction, LayoutAlignmentX};
+use crate::{LayoutAlignmentY, LayoutDirection, Padding, ThemeColor, Ui};
+use clay_layout::elements::{FloatingAttachPointType, FloatingAttachToElement, PointerCaptureMode};
+use glam::Vec4;
+use std::collections::{HashMap, VecDeque};
+
+/// Number of toasts shown at the same time, the rest wait in the queue
+const MAX_VISIBLE_TOASTS: usize = 3;
+const TOAST_DURATION: f32 = 3.0;
+const TOAST_FADE_TIME: f32 = 0.25;
+/// Time an item has to be hovered or focused before its tooltip is shown
+const TOOLTIP_DELAY: f32 = 0.5;
+
+const MODAL_Z_INDEX: i16 = 200;
+const TOAST_Z_INDEX: i16 = 300;
+const TOOLTIP_Z_INDEX: i16 = 400;
+
+const MODAL_DIM_COLOR: ClayColor = ClayColor::rgba(0.0, 0.0, 0.0, 160.0);
+
+struct Toast {
+    text: String,
+    duration: f32,
+    remaining: f32,
+}
+
+impl Toast {
+    /// Fades in when shown and out before it's dismissed
+    fn opacity(&self) -> f32 {
+        let shown = self.duration - self.remaining;
+        (shown / TOAST_FADE_TIME).min(self.remaining / TOAST_FADE_TIME).clamp(0.0, 1.0)
+    }
+}
+
+/// State of modals, toasts and tooltips
+#[derive(Default)]
+pub(crate) struct OverlayState {
+    toasts: VecDeque<Toast>,
+    /// Focus before each open modal was opened, restored when it closes
+    open_modals: HashMap<u32, Option<Id>>,
+    /// A modal is being built, items declared inside it still get input
+    modal_depth: u32,
+    /// A modal was shown this frame
+    modal_shown: bool,
+    /// A modal was shown last frame so items outside of it don't get input
+    modal_blocking: bool,
+    /// Item with a pending tooltip and how long it has been hovered or focused
+    tooltip: Option<(u32, f32)>,
+}
+
+impl OverlayState {
+    pub(crate) fn begin(&mut self) {
+        self.modal_blocking = self.modal_shown;
+        self.modal_shown = false;
+        self.modal_depth = 0;
+    }
+
+    /// True if mouse input is blocked for items declared at this point
+    pub(crate) fn is_input_blocked(&self) -> bool {
+        self.modal_blocking && self.modal_depth == 0
+    }
+
+    fn update_toasts(&mut self, delta_time: f32) {
+        for toast in self.toasts.iter_mut().take(MAX_VISIBLE_TOASTS) {
+            toast.remaining -= delta_time;
+        }
+
+        self.toasts.retain(|toast| toast.remaining > 0.0);
+    }
+}
+
+impl Ui<'_> {
+    /// Shows a dialog on top of everything else while `open` is true. Focus stays within the
+    /// dialog, items outside of it don't get mouse input and the cancel action closes it.
+    /// The focus from before the dialog was opened is restored when it closes.
+    #[rustfmt::skip]
+    pub fn modal<F: FnOnce(&Ui)>(&self, title: &str, open: &mut bool, f: F) {
+        let state = &self.state;
+        let id = state.layout.id(title);
+
+        if !*open {
+            if let Some(prev_focus) = state.overlay.borrow_mut().open_modals.remove(&id.id.id) {
+                state.focus_id.set(prev_focus);
+            }
+            return;
+        }
+
+        {
+            let mut overlay = state.overlay.borrow_mut();
+            overlay.open_modals.entry(id.id.id).or_insert(state.focus_id.get());
+            overlay.modal_shown = true;
+            overlay.modal_depth += 1;
+        }
+
+        let theme = self.theme();
+        let (width, height) = state.screen_size.get();
+        let scope = FocusScope::new().contain(true).wrap_y(true);
+        let mut focus_scope = 0;
+
+        // Full screen layer that dims everything below and captures the pointer
+        state.layout.with(Declaration::new()
+            .id(state.layout.id_index(title, 1))
+            .layout()
+                .width(fixed!(width as f32))
+                .height(fixed!(height as f32))
+                .child_alignment(Alignment::new(LayoutAlignmentX::Center, LayoutAlignmentY::Center))
+            .end()
+            .floating()
+                .attach_to(FloatingAttachToElement::Root)
+                .z_index(MODAL_Z_INDEX)
+                .pointer_capture_mode(PointerCaptureMode::Capture)
+            .end()
+            .background_color(MODAL_DIM_COLOR), |_| {
+                state.layout.with(Declaration::new()
+                    .id(id)
+                    .layout()
+                        .direction(LayoutDirection::TopToBottom)
+                        .padding(Padding::all(theme.spacing(Spacing::Large)))
+                        .child_gap(theme.spacing(Spacing::Medium))
+                    .end()
+                    .corner_radius().all(theme.radius(Radius::Large)).end()
+                    .background_color(theme.color(ThemeColor::Surface)), |_| {
+                        self.themed_text(title, FontSize::Heading, theme.color(ThemeColor::Text));
+
+                        focus_scope = state.focus.borrow_mut().push_scope(scope);
+                        f(self);
+                        state.focus.borrow_mut().pop_scope();
+                    });
+            });
+
+        state.overlay.borrow_mut().modal_depth -= 1;
+
+        // Pull the focus into the dialog
+        let focus = state.focus.borrow();
+
+        if !state.focus_id.get().is_some_and(|focus_id| focus.scope_contains(focus_scope, focus_id)) {
+            if let Some(default) = focus.default_item_in(focus_scope) {
+                state.focus_id.set(Some(default));
+            }
+        }
+
+        // Widgets that use the move actions themselves (such as an open dropdown) handle cancel
+        let widget_captured = focus.is_captured(FocusDirection::Up);
+        drop(focus);
+
+        if !widget_captured && self.get_input_action(InputAction::Cancel) == ActionResponse::Pressed {
+            *open = false;
+
+            if let Some(prev_focus) = state.overlay.borrow_mut().open_modals.remove(&id.id.id) {
+                state.focus_id.set(prev_focus);
+            }
+        }
+    }
+
+    /// Queues a short notification that is dismissed after a few seconds
+    pub fn show_toast(&self, text: &str) {
+        self.show_toast_for(text, TOAST_DURATION);
+    }
+
+    /// Queues a notification that is shown for `duration` seconds
+    pub fn show_toast_for(&self, text: &str, duration: f32) {
+        self.state.overlay.borrow_mut().toasts.push_back(Toast {
+            text: text.to_string(),
+            duration,
+            remaining: duration,
+        });
+    }
+
+    /// Shows `text` next to the item after it has been hovered or focused for a moment. Call
+    /// it every frame after the item has been declared.
+    #[rustfmt::skip]
+    pub fn tooltip(&self, id: Id, text: &str) {
+        let state = &self.state;
+
+        let aabb = self.item_state(id).map(|item| item.aabb).filter(|aabb| *aabb != Vec4::ZERO);
+        let pos = state.input.borrow().mouse_pos;
+        let mut overlay = state.overlay.borrow_mut();
+        let hovered = !overlay.is_input_blocked()
+            && aabb.is_some_and(|a| pos.x >= a.x && pos.y >= a.y && pos.x < a.z && pos.y < a.w);
+
+        if !hovered && !self.is_focused(id) {
+            if overlay.tooltip.is_some_and(|(item, _)| item == id.id.id) {
+                overlay.tooltip = None;
+            }
+            return;
+        }
+
+        let time = match overlay.tooltip {
+            Some((item, time)) if item == id.id.id => time + state.delta_time.get(),
+            _ => 0.0,
+        };
+
+        overlay.tooltip = Some((id.id.id, time));
+        drop(overlay);
+
+        if time < TOOLTIP_DELAY {
+            return;
+        }
+
+        let theme = self.theme();
+        let text = self.frame_str(text);
+
+        state.layout.with(Declaration::new()
+            .layout()
+                .padding(Padding::all(theme.spacing(Spacing::Small)))
+            .end()
+            .floating()
+                .attach_to(FloatingAttachToElement::ElementWithId)
+                .parent_id(id.id.id)
+                .attach_points(FloatingAttachPointType::CenterTop, FloatingAttachPointType::CenterBottom)
+                .offset((0.0, theme.spacing(Spacing::Small) as f32).into())
+                .z_index(TOOLTIP_Z_INDEX)
+                .pointer_capture_mode(PointerCaptureMode::Passthrough)
+            .end()
+            .corner_radius().all(theme.radius(Radius::Small)).end()
+            .background_color(theme.color(ThemeColor::Control)), |_| {
+                self.themed_text(text, FontSize::Small, theme.color(ThemeColor::Text));
+            });
+    }
+
+    /// Declares the visible toasts, called at the end of the frame so they are on top
+    #[rustfmt::skip]
+    pub(crate) fn draw_toasts(&self) {
+        let state = &self.state;
+        let mut overlay = state.overlay.borrow_mut();
+        overlay.update_toasts(state.delta_time.get());
+
+        if overlay.toasts.is_empty() {
+            return;
+        }
+
+        let theme = self.theme();
+        let margin = theme.spacing(Spacing::Large) as f32;
+
+        state.layout.with(Declaration::new()
+            .layout()
+                .direction(LayoutDirection::TopToBottom)
+                .child_gap(theme.spacing(Spacing::Small))
+                .child_alignment(Alignment::new(LayoutAlignmentX::Center, LayoutAlignmentY::Top))
+            .end()
+            .floating()
+                .attach_to(FloatingAttachToElement::Root)
+                .attach_points(FloatingAttachPointType::CenterBottom, FloatingAttachPointType::CenterBottom)
+                .offset((0.0, -margin).into())
+                .z_index(TOAST_Z_INDEX)
+                .pointer_capture_mode(PointerCaptureMode::Passthrough)
+            .end(), |_| {
+                for toast in overlay.toasts.iter().take(MAX_VISIBLE_TOASTS) {
+                    let opacity = toast.opacity();
+                    let fade = |c: ClayColor| ClayColor::rgba(c.r, c.g, c.b, c.a * opacity);
+
+                    state.layout.with(Declaration::new()
+                        .layout()
+                            .padding(Padding::all(theme.spacing(Spacing::Medium)))
+                        .end()
+                        .corner_radius().all(theme.radius(Radius::Medium)).end()
+                        .background_color(fade(theme.color(ThemeColor::Surface))), |_| {
+                            self.widget_text(&toast.text, fade(theme.color(ThemeColor::Text)));
+                        });
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toast_queue() {
+        let mut overlay = OverlayState::default();
+
+        for i in 0..4 {
+            overlay.toasts.push_back(Toast {
+                text: format!("toast {}", i),
+                duration: 1.0,
+                remaining: 1.0,
+            });
+        }
+
+        // Only the visible toasts count down
+        overlay.update_toasts(0.6);
+        overlay.update_toasts(0.6);
+        assert_eq!(overlay.toasts.len(), 1);
+        assert_eq!(overlay.toasts[0].text, "toast 3");
+        assert_eq!(overlay.toasts[0].opacity(), 0.0);
+
+        overlay.update_toasts(0.5);
+        assert!((overlay.toasts[0].opacity() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_modal_blocks_input_outside() {
+        let mut overlay = OverlayState {
+            modal_shown: true,
+            ..Default::default()
+        };
+        overlay.begin();
+        assert!(overlay.is_input_blocked());
+
+        overlay.modal_depth += 1;
+        assert!(!overlay.is_input_blocked());
+
+        // Not shown during the frame so the next one isn't blocked
+        overlay.begin();
+        assert!(!overlay.is_input_blocked());
+    }
+}
@@ -1,6 +1,7 @@
 use std::hash::{Hash, Hasher};
 use fxhash::FxHasher;
 use raw_window_handle::RawWindowHandle;
+use crate::text_effects::EFFECT_LAYER_COUNT;
 
 pub type ImageHandle = u64;
 pub type FontHandle = u64;
@@ -65,20 +66,60 @@ pub struct DrawRectRoundedData {
     pub corners: [f32; 4],
 }
 
+/// Starts clipping everything drawn until the matching [RenderType::ScissorEnd] to the
+/// bounding box of the command. Scissors nest and the clip rect is the intersection of all
+/// active ones.
+#[derive(Debug)]
+pub struct ScissorData {
+    /// Corner radii of the clip rect in the same order as [DrawRectRoundedData::corners]
+    pub corners: [f32; 4],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GradientKind {
+    /// Changes along the unit `direction` from one side of the bounding box to the other
+    Linear { direction: [f32; 2] },
+    /// Changes from `center` outwards to `radius`, both in screen coordinates
+    Radial { center: [f32; 2], radius: f32 },
+}
+
+/// Gradient from the color of the command to `end_color`
+#[derive(Debug)]
+pub struct GradientData {
+    pub end_color: Color,
+    pub kind: GradientKind,
+    pub corners: [f32; 4],
+}
+
+/// Rounded rect with blurred edges in the color of the command. The bounding box includes
+/// the blur, the shape is the bounding box inset by `blur`.
+#[derive(Debug)]
+pub struct ShadowData {
+    pub corners: [f32; 4],
+    pub blur: f32,
+}
+
+/// Blurs what has been drawn below the bounding box
+#[derive(Debug)]
+pub struct BlurData {
+    pub radius: f32,
+    pub corners: [f32; 4],
+}
+
 #[derive(Debug)]
 pub struct DrawBorderData {
     pub outer_radius: [f32; 4],
     pub inner_radius: [f32; 4],
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub struct DrawImage {
     pub width: u32,
     pub height: u32,
     pub stride: u32,
     pub handle: *const i16,
+    /// Images with a corner radius above zero are drawn with rounded corners
     pub rounded_corners: [f32; 4],
-    pub rounding: bool,
 }
 
 #[derive(Debug)]
@@ -88,12 +129,34 @@ pub struct DrawTextData {
     pub font_handle: FontHandle,
 }
 
+/// Coverage drawn below a text buffer (shadow, glow or outline) with its own color. Uses
+/// the same size and sub pixel layout as the text buffer it belongs to.
+#[derive(Debug, Copy, Clone)]
+pub struct TextEffectLayer {
+    pub data: RawVoidPtr,
+    pub color: Color,
+}
+
+impl Default for TextEffectLayer {
+    fn default() -> Self {
+        Self {
+            data: RawVoidPtr::default(),
+            color: Color::new(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct DrawTextBufferData {
     pub data: RawVoidPtr,
     pub handle: TextHandle,
     pub width: u16,
     pub height: u16,
+    /// Number of horizontal sub pixel variants stored after each other in `data`. Each
+    /// variant is `width * height` in size and shifted right by `1 / sub_pixel_steps_x`.
+    pub sub_pixel_steps_x: u8,
+    /// Effect layers in draw order. Layers with null data are skipped.
+    pub effects: [TextEffectLayer; EFFECT_LAYER_COUNT],
 }
 
 #[derive(Debug)]
@@ -102,9 +165,12 @@ pub enum RenderType {
     DrawBackground(DrawImage),
     DrawRectRounded(DrawRectRoundedData),
     DrawBorder(DrawBorderData),
+    DrawGradient(GradientData),
+    DrawShadow(ShadowData),
+    BackdropBlur(BlurData),
     DrawTextBuffer(DrawTextBufferData),
     DrawImage(DrawImage),
-    ScissorStart,
+    ScissorStart(ScissorData),
     ScissorEnd,
     Custom,
     None,
@@ -149,6 +215,43 @@ impl Hash for DrawRectRoundedData {
     }
 }
 
+impl Hash for ScissorData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.corners.hash_stable(state);
+    }
+}
+
+impl Hash for GradientData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let c = &self.end_color;
+        [c.r, c.g, c.b, c.a].hash_stable(state);
+        self.corners.hash_stable(state);
+
+        match self.kind {
+            GradientKind::Linear { direction } => {
+                [direction[0], direction[1], 0.0, 0.0].hash_stable(state);
+            }
+            GradientKind::Radial { center, radius } => {
+                [center[0], center[1], radius, 1.0].hash_stable(state);
+            }
+        }
+    }
+}
+
+impl Hash for ShadowData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.corners.hash_stable(state);
+        float_to_stable_int(self.blur).hash(state);
+    }
+}
+
+impl Hash for BlurData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.corners.hash_stable(state);
+        float_to_stable_int(self.radius).hash(state);
+    }
+}
+
 impl Hash for DrawBorderData {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.outer_radius.hash_stable(state);
@@ -164,7 +267,6 @@ impl Hash for DrawImage {
         // Hash the pointer address as usize
         (self.handle as usize).hash(state);
         self.rounded_corners.hash_stable(state);
-        self.rounding.hash(state);
     }
 }
 
@@ -185,6 +287,12 @@ impl Hash for DrawTextBufferData {
         self.handle.hash(state);
         self.width.hash(state);
         self.height.hash(state);
+        self.sub_pixel_steps_x.hash(state);
+
+        for effect in &self.effects {
+            (effect.data.0 as usize).hash(state);
+            [effect.color.r, effect.color.g, effect.color.b, effect.color.a].hash_stable(state);
+        }
     }
 }
 
@@ -207,13 +315,24 @@ impl Hash for RenderType {
             RenderType::DrawBorder(data) => {
                 data.hash(state);
             },
+            RenderType::DrawGradient(data) => {
+                data.hash(state);
+            },
+            RenderType::DrawShadow(data) => {
+                data.hash(state);
+            },
+            RenderType::BackdropBlur(data) => {
+                data.hash(state);
+            },
             RenderType::DrawTextBuffer(data) => {
                 data.hash(state);
             },
             RenderType::DrawImage(image) => {
                 image.hash(state);
             },
-            RenderType::ScissorStart |
+            RenderType::ScissorStart(data) => {
+                data.hash(state);
+            },
             RenderType::ScissorEnd |
             RenderType::Custom |
             RenderType::None => {
@@ -457,7 +576,6 @@ mod tests {
                 stride: 300,
                 handle: std::ptr::null(),
                 rounded_corners: [5.0, 5.0, 5.0, 5.0],
-                rounding: true,
             }),
         };
 
@@ -470,7 +588,6 @@ mod tests {
                 stride: 300,
                 handle: std::ptr::null(),
                 rounded_corners: [5.0, 5.0, 5.0, 5.0],
-                rounding: true,
             }),
         };
 
@@ -0,0 +1,132 @@
+use crate::font::{RichGeneratorConfig, RichSpanConfig};
+use crate::{fixed, ClayColor, CustomDraw, Declaration, FontStyle, Ui};
+
+/// A run of text within a rich text element. All spans passed to
+/// [`Ui::rich_text_with_layout`] are shaped together so kerning, baseline alignment and
+/// wrapping are shared across spans.
+#[derive(Debug, Clone, Copy)]
+pub struct TextSpan<'a> {
+    pub text: &'a str,
+    pub font: FontStyle,
+    pub color: ClayColor,
+    pub size: u32,
+    pub underline: bool,
+}
+
+impl<'a> TextSpan<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            font: FontStyle::Default,
+            color: ClayColor::rgba(255.0, 255.0, 255.0, 255.0),
+            size: 36,
+            underline: false,
+        }
+    }
+
+    #[inline]
+    pub fn font(mut self, font: FontStyle) -> Self {
+        self.font = font;
+        self
+    }
+
+    #[inline]
+    pub fn color(mut self, color: ClayColor) -> Self {
+        self.color = color;
+        self
+    }
+
+    #[inline]
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    #[inline]
+    pub fn underline(mut self, underline: bool) -> Self {
+        self.underline = underline;
+        self
+    }
+}
+
+impl Ui<'_> {
+    /// Lays out a sequence of spans as a single line of text inside `decl`
+    pub fn rich_text_with_layout(&self, spans: &[TextSpan], decl: &Declaration) {
+        self.rich_text_internal(spans, None, decl);
+    }
+
+    /// Same as [`Ui::rich_text_with_layout`] but wraps the text at `wrap_width`
+    pub fn rich_text_wrapped_with_layout(
+        &self,
+        spans: &[TextSpan],
+        wrap_width: f32,
+        decl: &Declaration,
+    ) {
+        self.rich_text_internal(spans, Some(wrap_width as u32), decl);
+    }
+
+    #[rustfmt::skip]
+    fn rich_text_internal(&self, spans: &[TextSpan], wrap_width: Option<u32>, decl: &Declaration) {
+        let state = &self.state;
+        let fonts = state.fonts.borrow();
+
+        // Spans sharing a color are rendered into the same layer. The colors themselves
+        // are not part of the generator config so changing them doesn't regenerate the text.
+        let mut colors = Vec::with_capacity(spans.len());
+        let mut config = RichGeneratorConfig {
+            spans: Vec::with_capacity(spans.len()),
+            wrap_width,
+        };
+
+        for span in spans {
+            let layer = match colors.iter().position(|c: &ClayColor| *c == span.color) {
+                Some(layer) => layer,
+                None => {
+                    colors.push(span.color);
+                    colors.len() - 1
+                }
+            };
+
+            config.spans.push(RichSpanConfig {
+                text: span.text.to_string(),
+                font_handle: fonts[span.font as usize],
+                size: span.size,
+                layer: layer as u32,
+                underline: span.underline,
+            });
+        }
+
+        let mut generator = state.text.generator.borrow_mut();
+
+        let Some(size) = generator.measure_rich_text_size(&config) else {
+            return;
+        };
+
+        generator.queue_generate_rich_text(&config, &state.bg_worker);
+        drop(generator);
+
+        let mut custom_draws = state.custom_draws.borrow_mut();
+
+        custom_draws.push(CustomDraw::RichText {
+            config,
+            colors: colors.into_iter().map(Self::color).collect(),
+        });
+
+        // Custom data is the index + 1 into the custom draws as null means no custom data
+        let custom_index = custom_draws.len();
+        drop(custom_draws);
+
+        state.layout.with(decl, |_clay| {
+            unsafe {
+                state.layout.with(Declaration::new()
+                    .layout()
+                        .width(fixed!(size.0))
+                        .height(fixed!(size.1))
+                    .end()
+                    .custom()
+                        .data_ptr(custom_index as _)
+                    .end(), |_clay| {});
+            }
+        });
+    }
+}
@@ -0,0 +1,100 @@
+use crate::Ui;
+
+/// Output size the theme and widget sizes are designed for
+pub const DEFAULT_REFERENCE_SIZE: (f32, f32) = (1280.0, 720.0);
+
+/// Factor between layout units and output pixels. Layouts are written in units of the
+/// reference size and scaled to fit the output so they look the same in every video mode.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct ScaleState {
+    /// Extra factor set by the user, such as for DPI or larger text
+    user_scale: f32,
+    /// `None` disables fitting to the output size
+    reference_size: Option<(f32, f32)>,
+    /// Combined scale for the current frame
+    scale: f32,
+}
+
+impl Default for ScaleState {
+    fn default() -> Self {
+        Self {
+            user_scale: 1.0,
+            reference_size: Some(DEFAULT_REFERENCE_SIZE),
+            scale: 1.0,
+        }
+    }
+}
+
+impl ScaleState {
+    /// Updates the scale for the output size, called at the start of each frame
+    pub(crate) fn update(&mut self, width: usize, height: usize) {
+        let fit = match self.reference_size {
+            Some((ref_width, ref_height)) if width > 0 && height > 0 => {
+                (width as f32 / ref_width).min(height as f32 / ref_height)
+            }
+            _ => 1.0,
+        };
+
+        self.scale = fit * self.user_scale;
+    }
+
+    #[inline]
+    pub(crate) fn scale(&self) -> f32 {
+        self.scale
+    }
+}
+
+impl Ui<'_> {
+    /// Extra scale on top of fitting the reference size, such as the DPI factor of the
+    /// display. Applied from the next frame.
+    pub fn set_ui_scale(&self, scale: f32) {
+        self.state.scale.borrow_mut().user_scale = scale.max(0.1);
+    }
+
+    /// Output size the layout is designed for, the ui is scaled to fit the output with the
+    /// aspect ratio kept. `None` uses 1 unit per pixel. Defaults to 1280x720.
+    pub fn set_reference_size(&self, size: Option<(f32, f32)>) {
+        self.state.scale.borrow_mut().reference_size =
+            size.filter(|(width, height)| *width > 0.0 && *height > 0.0);
+    }
+
+    /// Number of pixels per layout unit in the current frame
+    pub fn ui_scale(&self) -> f32 {
+        self.state.scale.borrow().scale()
+    }
+
+    /// Converts layout units to pixels. Theme values are already scaled, this is for sizes
+    /// that are passed directly to the layout.
+    #[inline]
+    pub fn scaled(&self, units: f32) -> f32 {
+        units * self.ui_scale()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_reference_size() {
+        let mut scale = ScaleState::default();
+
+        scale.update(1280, 720);
+        assert_eq!(scale.scale(), 1.0);
+
+        scale.update(1920, 1080);
+        assert_eq!(scale.scale(), 1.5);
+
+        // PAL is limited by the width
+        scale.update(720, 576);
+        assert_eq!(scale.scale(), 0.5625);
+
+        scale.user_scale = 2.0;
+        scale.update(1280, 720);
+        assert_eq!(scale.scale(), 2.0);
+
+        scale.reference_size = None;
+        scale.update(1920, 1080);
+        assert_eq!(scale.scale(), 2.0);
+    }
+}
@@ -1,29 +1,104 @@
 use bitflags::bitflags;
+use glam::Vec2;
 
 bitflags! {
     #[derive(Copy, Clone, Debug)]
     pub struct SignalFlags: u32 {
         const LEFT_PRESSED = 1 << 0;
+        const RIGHT_PRESSED = 1 << 1;
+        const MIDDLE_PRESSED = 1 << 2;
+
+        const LEFT_DRAGGING = 1 << 3;
+        const RIGHT_DRAGGING = 1 << 4;
+        const MIDDLE_DRAGGING = 1 << 5;
+
+        const LEFT_DRAG_START = 1 << 6;
+        const RIGHT_DRAG_START = 1 << 7;
+        const MIDDLE_DRAG_START = 1 << 8;
+
+        const LEFT_DRAG_END = 1 << 9;
+        const RIGHT_DRAG_END = 1 << 10;
+        const MIDDLE_DRAG_END = 1 << 11;
+
         const LEFT_RELEASED = 1 << 12;
+        const RIGHT_RELEASED = 1 << 13;
+        const MIDDLE_RELEASED = 1 << 14;
+
         const LEFT_CLICKED = 1 << 15;
+        const RIGHT_CLICKED = 1 << 16;
+        const MIDDLE_CLICKED = 1 << 17;
+
         const LEFT_DOUBLE_CLICKED = 1 << 18;
+        const RIGHT_DOUBLE_CLICKED = 1 << 19;
+        const MIDDLE_DOUBLE_CLICKED = 1 << 20;
 
         const KEYBOARD_PRESSED = 1 << 24;
         const HOVERING = 1 << 25;
         const MOUSE_OVER = 1 << 26;
         const ENTER_HOVER = 1 << 27;
         const EXIT_HOVER = 1 << 28;
+        const SCROLLED = 1 << 29;
+        /// The value of a widget was changed by the user
+        const CHANGED = 1 << 30;
 
         const PRESSED = Self::LEFT_PRESSED.bits() | Self::KEYBOARD_PRESSED.bits();
         const RELEASED = Self::LEFT_RELEASED.bits();
         const CLICKED = Self::LEFT_CLICKED.bits() | Self::KEYBOARD_PRESSED.bits();
         const DOUBLE_CLICKED = Self::LEFT_DOUBLE_CLICKED.bits();
+        const DRAGGING = Self::LEFT_DRAGGING.bits();
+        const DRAG_START = Self::LEFT_DRAG_START.bits();
+        const DRAG_END = Self::LEFT_DRAG_END.bits();
     }
 }
 
+/// Flags for each mouse button in [crate::input::MouseButton] order
+pub(crate) const BUTTON_FLAGS: [ButtonFlags; 3] = [
+    ButtonFlags {
+        pressed: SignalFlags::LEFT_PRESSED,
+        released: SignalFlags::LEFT_RELEASED,
+        clicked: SignalFlags::LEFT_CLICKED,
+        double_clicked: SignalFlags::LEFT_DOUBLE_CLICKED,
+        dragging: SignalFlags::LEFT_DRAGGING,
+        drag_start: SignalFlags::LEFT_DRAG_START,
+        drag_end: SignalFlags::LEFT_DRAG_END,
+    },
+    ButtonFlags {
+        pressed: SignalFlags::RIGHT_PRESSED,
+        released: SignalFlags::RIGHT_RELEASED,
+        clicked: SignalFlags::RIGHT_CLICKED,
+        double_clicked: SignalFlags::RIGHT_DOUBLE_CLICKED,
+        dragging: SignalFlags::RIGHT_DRAGGING,
+        drag_start: SignalFlags::RIGHT_DRAG_START,
+        drag_end: SignalFlags::RIGHT_DRAG_END,
+    },
+    ButtonFlags {
+        pressed: SignalFlags::MIDDLE_PRESSED,
+        released: SignalFlags::MIDDLE_RELEASED,
+        clicked: SignalFlags::MIDDLE_CLICKED,
+        double_clicked: SignalFlags::MIDDLE_DOUBLE_CLICKED,
+        dragging: SignalFlags::MIDDLE_DRAGGING,
+        drag_start: SignalFlags::MIDDLE_DRAG_START,
+        drag_end: SignalFlags::MIDDLE_DRAG_END,
+    },
+];
+
+pub(crate) struct ButtonFlags {
+    pub(crate) pressed: SignalFlags,
+    pub(crate) released: SignalFlags,
+    pub(crate) clicked: SignalFlags,
+    pub(crate) double_clicked: SignalFlags,
+    pub(crate) dragging: SignalFlags,
+    pub(crate) drag_start: SignalFlags,
+    pub(crate) drag_end: SignalFlags,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Signal {
     pub flags: SignalFlags,
+    /// Mouse movement since the drag started
+    pub drag_delta: Vec2,
+    /// Mouse wheel movement while hovering the item
+    pub scroll: Vec2,
 }
 
 impl Signal {
@@ -31,27 +106,64 @@ impl Signal {
     pub fn new() -> Self {
         Self {
             flags: SignalFlags::empty(),
+            drag_delta: Vec2::ZERO,
+            scroll: Vec2::ZERO,
         }
     }
 
     #[inline]
     pub fn clicked(&self) -> bool {
-        self.flags.contains(SignalFlags::CLICKED)
+        self.flags.intersects(SignalFlags::CLICKED)
     }
 
     #[inline]
     pub fn double_clicked(&self) -> bool {
-        self.flags.contains(SignalFlags::DOUBLE_CLICKED)
+        self.flags.intersects(SignalFlags::DOUBLE_CLICKED)
+    }
+
+    #[inline]
+    pub fn right_clicked(&self) -> bool {
+        self.flags.intersects(SignalFlags::RIGHT_CLICKED)
+    }
+
+    #[inline]
+    pub fn middle_clicked(&self) -> bool {
+        self.flags.intersects(SignalFlags::MIDDLE_CLICKED)
     }
 
     #[inline]
     pub fn pressed(&self) -> bool {
-        self.flags.contains(SignalFlags::PRESSED)
+        self.flags.intersects(SignalFlags::PRESSED)
     }
 
     #[inline]
     pub fn released(&self) -> bool {
-        self.flags.contains(SignalFlags::RELEASED)
+        self.flags.intersects(SignalFlags::RELEASED)
+    }
+
+    #[inline]
+    pub fn dragging(&self) -> bool {
+        self.flags.intersects(SignalFlags::DRAGGING)
+    }
+
+    #[inline]
+    pub fn drag_started(&self) -> bool {
+        self.flags.intersects(SignalFlags::DRAG_START)
+    }
+
+    #[inline]
+    pub fn drag_ended(&self) -> bool {
+        self.flags.intersects(SignalFlags::DRAG_END)
+    }
+
+    #[inline]
+    pub fn scrolled(&self) -> bool {
+        self.flags.intersects(SignalFlags::SCROLLED)
+    }
+
+    #[inline]
+    pub fn changed(&self) -> bool {
+        self.flags.contains(SignalFlags::CHANGED)
     }
 
     #[inline]
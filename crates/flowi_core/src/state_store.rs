@@ -0,0 +1,300 @@
+use crate::{Id, Ui};
+use std::any::{Any, TypeId};
+use std::cell::RefMut;
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// How long an entry in the state store is kept
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Retention {
+    /// Removed at the end of the first frame it isn't accessed in
+    Frame,
+    /// Kept until it's removed with [Ui::remove_state]
+    Persistent,
+}
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("Failed to read or write state file")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {text}")]
+    Parse { line: usize, text: String },
+}
+
+/// Values that can be written with [Ui::save_state] and restored on the next run
+pub trait PersistentState: Sized {
+    fn save(&self) -> String;
+    fn restore(text: &str) -> Option<Self>;
+}
+
+macro_rules! impl_persistent_state {
+    ($($t:ty),*) => {
+        $(
+            impl PersistentState for $t {
+                fn save(&self) -> String {
+                    self.to_string()
+                }
+
+                fn restore(text: &str) -> Option<Self> {
+                    text.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_persistent_state!(bool, i32, u32, i64, u64, usize, f32, f64, String);
+
+struct Entry {
+    value: Box<dyn Any>,
+    frame: u64,
+    retention: Retention,
+    /// Set for entries created with [Ui::saved_state]
+    save: Option<fn(&dyn Any) -> String>,
+}
+
+/// Typed values keyed by item id. Unlike the item states these can outlive the item so
+/// scroll positions and selections survive the item being offscreen for a while.
+#[derive(Default)]
+pub(crate) struct StateStore {
+    entries: HashMap<(u32, TypeId), Entry>,
+    /// Values read by `load` that haven't been claimed by `saved_state` yet
+    restored: HashMap<u32, String>,
+}
+
+impl StateStore {
+    fn entry<T: Default + 'static>(
+        &mut self,
+        id: u32,
+        retention: Retention,
+        frame: u64,
+        save: Option<fn(&dyn Any) -> String>,
+        init: impl FnOnce(&mut Self) -> T,
+    ) -> &mut T {
+        let key = (id, TypeId::of::<T>());
+
+        if !self.entries.contains_key(&key) {
+            let value = init(self);
+            self.entries.insert(
+                key,
+                Entry {
+                    value: Box::new(value),
+                    frame,
+                    retention,
+                    save,
+                },
+            );
+        }
+
+        let entry = self.entries.get_mut(&key).unwrap();
+        entry.frame = frame;
+        // Asking for persistent retention upgrades an existing entry, never the reverse
+        if retention == Retention::Persistent {
+            entry.retention = retention;
+        }
+
+        entry.value.downcast_mut::<T>().unwrap()
+    }
+
+    pub(crate) fn get<T: Default + 'static>(&mut self, id: u32, retention: Retention, frame: u64) -> &mut T {
+        self.entry(id, retention, frame, None, |_| T::default())
+    }
+
+    pub(crate) fn get_saved<T: PersistentState + Default + 'static>(&mut self, id: u32, frame: u64) -> &mut T {
+        let save: fn(&dyn Any) -> String = |value| value.downcast_ref::<T>().unwrap().save();
+
+        self.entry(id, Retention::Persistent, frame, Some(save), |store| {
+            store
+                .restored
+                .remove(&id)
+                .and_then(|text| T::restore(&text))
+                .unwrap_or_default()
+        })
+    }
+
+    pub(crate) fn remove(&mut self, id: u32) {
+        self.entries.retain(|(entry_id, _), _| *entry_id != id);
+        self.restored.remove(&id);
+    }
+
+    pub(crate) fn end_frame(&mut self, frame: u64) {
+        self.entries
+            .retain(|_, entry| entry.retention == Retention::Persistent || entry.frame == frame);
+    }
+
+    /// One `id = value` line per saved entry, sorted by id to keep the output stable.
+    /// Restored values that haven't been used yet are kept as is.
+    pub(crate) fn save(&self) -> String {
+        let mut lines: Vec<(u32, String)> = self
+            .entries
+            .iter()
+            .filter_map(|((id, _), entry)| entry.save.map(|save| (*id, escape(&save(entry.value.as_ref())))))
+            .chain(self.restored.iter().map(|(id, text)| (*id, escape(text))))
+            .collect();
+
+        lines.sort_by_key(|(id, _)| *id);
+        lines.iter().map(|(id, text)| format!("{} = {}\n", id, text)).collect()
+    }
+
+    pub(crate) fn load(&mut self, text: &str) -> Result<(), StateError> {
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |text: String| StateError::Parse {
+                line: index + 1,
+                text,
+            };
+
+            let Some((id, value)) = line.split_once(" = ") else {
+                return Err(parse_error(format!("Expected 'Id = Value', got '{}'", line)));
+            };
+
+            let id = id
+                .trim()
+                .parse()
+                .map_err(|_| parse_error(format!("Invalid id '{}'", id)))?;
+
+            self.restored.insert(id, unescape(value));
+        }
+
+        Ok(())
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+
+    output
+}
+
+impl Ui<'_> {
+    /// Returns the state of type `T` stored for the item, created with `T::default()` the
+    /// first time it's used. The state is removed at the end of a frame it isn't accessed
+    /// in. The returned borrow has to be dropped before the store is used again.
+    pub fn state<T: Default + 'static>(&self, id: Id) -> RefMut<'_, T> {
+        self.state_with(id, Retention::Frame)
+    }
+
+    /// Same as [Ui::state] with the given retention
+    pub fn state_with<T: Default + 'static>(&self, id: Id, retention: Retention) -> RefMut<'_, T> {
+        let frame = self.state.current_frame.get();
+        RefMut::map(self.state.state_store.borrow_mut(), |store| {
+            store.get::<T>(id.id.id, retention, frame)
+        })
+    }
+
+    /// Persistent state that is included in [Ui::save_state] and restored from [Ui::load_state].
+    /// Only one saved type can be used per id.
+    pub fn saved_state<T: PersistentState + Default + 'static>(&self, id: Id) -> RefMut<'_, T> {
+        let frame = self.state.current_frame.get();
+        RefMut::map(self.state.state_store.borrow_mut(), |store| {
+            store.get_saved::<T>(id.id.id, frame)
+        })
+    }
+
+    /// Removes all state stored for the item
+    pub fn remove_state(&self, id: Id) {
+        self.state.state_store.borrow_mut().remove(id.id.id);
+    }
+
+    /// Serializes all state created with [Ui::saved_state]
+    pub fn save_state(&self) -> String {
+        self.state.state_store.borrow().save()
+    }
+
+    /// Loads state written by [Ui::save_state]. The values are restored the first time
+    /// [Ui::saved_state] is called for their ids.
+    pub fn load_state(&self, text: &str) -> Result<(), StateError> {
+        self.state.state_store.borrow_mut().load(text)
+    }
+
+    /// Writes [Ui::save_state] to a file
+    pub fn save_state_file<P: AsRef<Path>>(&self, path: P) -> Result<(), StateError> {
+        std::fs::write(path, self.save_state())?;
+        Ok(())
+    }
+
+    /// Loads a file written by [Ui::save_state_file]
+    pub fn load_state_file<P: AsRef<Path>>(&self, path: P) -> Result<(), StateError> {
+        let text = std::fs::read_to_string(path)?;
+        self.load_state(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention() {
+        let mut store = StateStore::default();
+
+        *store.get::<usize>(1, Retention::Frame, 0) = 5;
+        *store.get::<usize>(2, Retention::Persistent, 0) = 6;
+        // Same id with another type is a separate entry
+        *store.get::<f32>(1, Retention::Frame, 0) = 1.5;
+        store.end_frame(0);
+
+        assert_eq!(*store.get::<usize>(1, Retention::Frame, 1), 5);
+        store.end_frame(1);
+
+        // Not accessed during frame 2
+        store.end_frame(2);
+        assert_eq!(*store.get::<usize>(1, Retention::Frame, 3), 0);
+        assert_eq!(*store.get::<f32>(1, Retention::Frame, 3), 0.0);
+        assert_eq!(*store.get::<usize>(2, Retention::Frame, 3), 6);
+
+        store.remove(2);
+        assert_eq!(*store.get::<usize>(2, Retention::Frame, 3), 0);
+    }
+
+    #[test]
+    fn test_save_and_restore() {
+        let mut store = StateStore::default();
+        *store.get_saved::<usize>(10, 0) = 3;
+        *store.get_saved::<String>(2, 0) = "Row\\1\nRow 2".to_owned();
+        *store.get::<usize>(3, Retention::Persistent, 0) = 7;
+
+        let text = store.save();
+        assert_eq!(text, "2 = Row\\\\1\\nRow 2\n10 = 3\n");
+
+        let mut store = StateStore::default();
+        store.load(&text).unwrap();
+        assert_eq!(*store.get_saved::<usize>(10, 0), 3);
+        assert_eq!(store.get_saved::<String>(2, 0).as_str(), "Row\\1\nRow 2");
+
+        // Values that fail to restore fall back to the default
+        store.load("4 = not a number").unwrap();
+        assert_eq!(*store.get_saved::<usize>(4, 0), 0);
+
+        assert!(matches!(store.load("5 3"), Err(StateError::Parse { line: 1, .. })));
+    }
+
+    #[test]
+    fn test_unclaimed_values_are_saved() {
+        let mut store = StateStore::default();
+        store.load("# comment\n\n7 = 1\n").unwrap();
+        assert_eq!(store.save(), "7 = 1\n");
+    }
+}
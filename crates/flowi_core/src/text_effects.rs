@@ -0,0 +1,351 @@
+use crate::ClayColor;
+
+/// Shadow drawn behind the text, offset and blurred.
+#[derive(Debug, Clone, Copy)]
+pub struct TextShadow {
+    pub offset: (i32, i32),
+    pub blur_radius: u32,
+    pub color: ClayColor,
+}
+
+/// Solid stroke around the glyphs.
+#[derive(Debug, Clone, Copy)]
+pub struct TextOutline {
+    pub width: u32,
+    pub color: ClayColor,
+}
+
+/// Soft glow around the glyphs.
+#[derive(Debug, Clone, Copy)]
+pub struct TextGlow {
+    pub radius: u32,
+    pub color: ClayColor,
+}
+
+/// Effects used to keep text readable on top of busy backgrounds. The effects are generated
+/// together with the text buffer and drawn in the order shadow, glow, outline, text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextEffects {
+    pub shadow: Option<TextShadow>,
+    pub outline: Option<TextOutline>,
+    pub glow: Option<TextGlow>,
+}
+
+impl TextEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn shadow(mut self, offset: (i32, i32), blur_radius: u32, color: ClayColor) -> Self {
+        self.shadow = Some(TextShadow {
+            offset,
+            blur_radius,
+            color,
+        });
+        self
+    }
+
+    #[inline]
+    pub fn outline(mut self, width: u32, color: ClayColor) -> Self {
+        self.outline = Some(TextOutline { width, color });
+        self
+    }
+
+    #[inline]
+    pub fn glow(mut self, radius: u32, color: ClayColor) -> Self {
+        self.glow = Some(TextGlow { radius, color });
+        self
+    }
+
+    /// Colors of the generated effect layers in draw order
+    pub(crate) fn colors(&self) -> [Option<ClayColor>; EFFECT_LAYER_COUNT] {
+        [
+            self.shadow.map(|s| s.color),
+            self.glow.map(|g| g.color),
+            self.outline.map(|o| o.color),
+        ]
+    }
+}
+
+/// Number of effect layers a text buffer can have (shadow, glow and outline)
+pub const EFFECT_LAYER_COUNT: usize = 3;
+
+/// The part of [`TextEffects`] that affects the generated buffers. Colors are left out so
+/// they can be changed without generating the text again.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub(crate) struct TextEffectsConfig {
+    pub(crate) shadow: Option<((i32, i32), u32)>,
+    pub(crate) outline: Option<u32>,
+    pub(crate) glow: Option<u32>,
+}
+
+impl From<&TextEffects> for TextEffectsConfig {
+    fn from(effects: &TextEffects) -> Self {
+        Self {
+            shadow: effects.shadow.map(|s| (s.offset, s.blur_radius)),
+            outline: effects.outline.map(|o| o.width),
+            glow: effects.glow.map(|g| g.radius),
+        }
+    }
+}
+
+impl TextEffectsConfig {
+    /// Number of pixels the text buffer is padded with on each side to fit the effects
+    pub(crate) fn padding(&self) -> u32 {
+        let shadow = self.shadow.map_or(0, |((x, y), blur)| {
+            x.unsigned_abs().max(y.unsigned_abs()) + blur * 3
+        });
+        let outline = self.outline.unwrap_or(0);
+        let glow = self.glow.map_or(0, |radius| radius * 3);
+
+        shadow.max(outline).max(glow)
+    }
+
+    /// Generates the effect layers for one coverage buffer. Layers that aren't enabled are
+    /// returned as `None`.
+    pub(crate) fn generate(
+        &self,
+        coverage: &[i16],
+        width: usize,
+        height: usize,
+    ) -> [Option<Vec<i16>>; EFFECT_LAYER_COUNT] {
+        let shadow = self.shadow.map(|(offset, blur_radius)| {
+            let blurred = blur(coverage, width, height, blur_radius as usize);
+            shift(&blurred, width, height, offset)
+        });
+
+        let glow = self.glow.map(|radius| {
+            // Blurring spreads the intensity out so it's boosted to make the glow visible
+            blur(coverage, width, height, radius as usize)
+                .iter()
+                .map(|&v| (v as i32 * 2).min(i16::MAX as i32) as i16)
+                .collect()
+        });
+
+        let outline = self
+            .outline
+            .map(|outline_width| dilate(coverage, width, height, outline_width as usize));
+
+        [shadow, glow, outline]
+    }
+}
+
+/// Max filter over a circular area with the given radius. Separated into a row pass that
+/// finds the max over each half width a row of the circle can have, and a column pass that
+/// combines the rows using the half width of the circle at their distance from the center.
+fn dilate(input: &[i16], width: usize, height: usize, radius: usize) -> Vec<i16> {
+    let size = width * height;
+
+    // Layer k has the max over x - k..=x + k of each row
+    let mut row_max = vec![0i16; size * (radius + 1)];
+    row_max[..size].copy_from_slice(input);
+
+    for k in 1..=radius {
+        let (prev, current) = row_max.split_at_mut(k * size);
+        let prev = &prev[(k - 1) * size..];
+
+        for y in 0..height {
+            let row = y * width;
+            for x in 0..width {
+                let mut max = prev[row + x];
+                if x >= k {
+                    max = max.max(input[row + x - k]);
+                }
+                if x + k < width {
+                    max = max.max(input[row + x + k]);
+                }
+                current[row + x] = max;
+            }
+        }
+    }
+
+    let mut output = vec![0i16; size];
+    let r = radius as isize;
+
+    for dy in -r..=r {
+        let half_width = (r * r - dy * dy).isqrt() as usize;
+        let layer = &row_max[half_width * size..(half_width + 1) * size];
+
+        for y in 0..height as isize {
+            let sy = y + dy;
+            if sy < 0 || sy >= height as isize {
+                continue;
+            }
+
+            let src = &layer[sy as usize * width..][..width];
+            let dst = &mut output[y as usize * width..][..width];
+            for (d, &s) in dst.iter_mut().zip(src) {
+                *d = (*d).max(s);
+            }
+        }
+    }
+
+    output
+}
+
+/// Separable box blur in one direction. `step` is 1 for horizontal and `width` for vertical.
+/// Pixels outside of the line count as zero.
+fn box_blur_line(input: &[i16], output: &mut [i16], start: usize, len: usize, step: usize, radius: usize) {
+    if len == 0 {
+        return;
+    }
+
+    let size = (radius * 2 + 1) as i32;
+    let value = |i: usize| input[start + i * step] as i32;
+
+    // Running sum of the window around i
+    let mut sum: i32 = (0..=radius.min(len - 1)).map(value).sum();
+
+    for i in 0..len {
+        output[start + i * step] = (sum / size) as i16;
+
+        if i + radius + 1 < len {
+            sum += value(i + radius + 1);
+        }
+        if i >= radius {
+            sum -= value(i - radius);
+        }
+    }
+}
+
+/// Approximates a gaussian blur by running a separable box blur three times
+fn blur(input: &[i16], width: usize, height: usize, radius: usize) -> Vec<i16> {
+    let mut output = input.to_vec();
+
+    if radius == 0 {
+        return output;
+    }
+
+    let mut temp = vec![0i16; width * height];
+
+    for _ in 0..3 {
+        for y in 0..height {
+            box_blur_line(&output, &mut temp, y * width, width, 1, radius);
+        }
+
+        for x in 0..width {
+            box_blur_line(&temp, &mut output, x, height, width, radius);
+        }
+    }
+
+    output
+}
+
+/// Moves the contents of the buffer, filling uncovered areas with zero
+fn shift(input: &[i16], width: usize, height: usize, offset: (i32, i32)) -> Vec<i16> {
+    let mut output = vec![0i16; width * height];
+
+    for y in 0..height as i32 {
+        let sy = y - offset.1;
+        if sy < 0 || sy >= height as i32 {
+            continue;
+        }
+
+        for x in 0..width as i32 {
+            let sx = x - offset.0;
+            if sx < 0 || sx >= width as i32 {
+                continue;
+            }
+
+            output[(y * width as i32 + x) as usize] = input[(sy * width as i32 + sx) as usize];
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_pixel(width: usize, height: usize) -> Vec<i16> {
+        let mut data = vec![0i16; width * height];
+        data[(height / 2) * width + width / 2] = i16::MAX;
+        data
+    }
+
+    fn idx(x: usize, y: usize, width: usize) -> usize {
+        y * width + x
+    }
+
+    #[test]
+    fn test_dilate() {
+        let data = single_pixel(5, 5);
+        let output = dilate(&data, 5, 5, 1);
+        assert_eq!(output[idx(1, 2, 5)], i16::MAX);
+        assert_eq!(output[idx(2, 1, 5)], i16::MAX);
+        // Corners are outside of the circle
+        assert_eq!(output[idx(1, 1, 5)], 0);
+    }
+
+    #[test]
+    fn test_dilate_matches_circle() {
+        let (width, height, radius) = (13, 11, 4);
+        let data: Vec<i16> = (0..width * height).map(|i| ((i * 7919) % 251) as i16 * 100).collect();
+        let output = dilate(&data, width, height, radius);
+        let r = radius as isize;
+
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let mut max = 0;
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        let (sx, sy) = (x + dx, y + dy);
+                        let inside = (0..width as isize).contains(&sx) && (0..height as isize).contains(&sy);
+                        if inside && dx * dx + dy * dy <= r * r {
+                            max = max.max(data[idx(sx as usize, sy as usize, width)]);
+                        }
+                    }
+                }
+                assert_eq!(output[idx(x as usize, y as usize, width)], max);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blur_spreads_and_keeps_energy_centered() {
+        let data = single_pixel(9, 9);
+        let output = blur(&data, 9, 9, 1);
+        let center = output[idx(4, 4, 9)];
+        assert!(center > 0 && center < i16::MAX);
+        assert!(output[idx(3, 4, 9)] > 0);
+        assert_eq!(output[idx(3, 4, 9)], output[idx(5, 4, 9)]);
+    }
+
+    #[test]
+    fn test_box_blur_line() {
+        let input: Vec<i16> = (0..10).map(|i| i * 1000).collect();
+
+        for radius in [0, 1, 3, 12] {
+            let mut output = vec![0i16; input.len()];
+            box_blur_line(&input, &mut output, 0, input.len(), 1, radius);
+
+            let size = (radius * 2 + 1) as i32;
+            for (i, &value) in output.iter().enumerate() {
+                let to = (i + radius).min(input.len() - 1);
+                let sum: i32 = input[i.saturating_sub(radius)..=to].iter().map(|&v| v as i32).sum();
+                assert_eq!(value as i32, sum / size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift() {
+        let data = single_pixel(5, 5);
+        let output = shift(&data, 5, 5, (1, 2));
+        assert_eq!(output[idx(3, 4, 5)], i16::MAX);
+        assert_eq!(output[idx(2, 2, 5)], 0);
+    }
+
+    #[test]
+    fn test_padding() {
+        let config = TextEffectsConfig {
+            shadow: Some(((2, 4), 1)),
+            outline: Some(2),
+            glow: None,
+        };
+        assert_eq!(config.padding(), 7);
+        assert_eq!(TextEffectsConfig::default().padding(), 0);
+    }
+}
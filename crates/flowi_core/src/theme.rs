@@ -0,0 +1,432 @@
+use crate::{ClayColor, Ui};
+use std::path::Path;
+use thiserror::Error;
+
+/// Named colors of a [Theme]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ThemeColor {
+    Text,
+    TextDim,
+    Accent,
+    /// Background of widget rows and panels
+    Surface,
+    /// Background of the focused widget row
+    SurfaceFocused,
+    /// Background of controls such as slider tracks and text fields
+    Control,
+}
+
+const ALL_COLORS: [ThemeColor; 6] = [
+    ThemeColor::Text,
+    ThemeColor::TextDim,
+    ThemeColor::Accent,
+    ThemeColor::Surface,
+    ThemeColor::SurfaceFocused,
+    ThemeColor::Control,
+];
+
+/// Typography scale
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FontSize {
+    Small,
+    Body,
+    Heading,
+    Title,
+}
+
+const ALL_FONT_SIZES: [FontSize; 4] = [FontSize::Small, FontSize::Body, FontSize::Heading, FontSize::Title];
+
+/// Padding and gaps between elements
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Spacing {
+    Small,
+    Medium,
+    Large,
+}
+
+const ALL_SPACINGS: [Spacing; 3] = [Spacing::Small, Spacing::Medium, Spacing::Large];
+
+/// Corner radii
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Radius {
+    Small,
+    Medium,
+    Large,
+}
+
+const ALL_RADII: [Radius; 3] = [Radius::Small, Radius::Medium, Radius::Large];
+
+/// Sizes of specific elements
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Size {
+    /// Height of a widget row
+    RowHeight,
+    /// Width of the control part of a widget, such as a slider track
+    ControlWidth,
+    /// Width of an unfocused item in the content selector
+    ItemWidth,
+    /// Height of an unfocused item in the content selector
+    ItemHeight,
+    /// Number of pixels a content selector item grows with when focused
+    ItemFocusGrow,
+    /// Height of a row of items in the content selector, not counting the row name
+    SelectorRowHeight,
+}
+
+const ALL_SIZES: [Size; 6] = [
+    Size::RowHeight,
+    Size::ControlWidth,
+    Size::ItemWidth,
+    Size::ItemHeight,
+    Size::ItemFocusGrow,
+    Size::SelectorRowHeight,
+];
+
+/// A single theme value. Used to override the theme for a part of the ui with
+/// [Ui::push_style] and [Ui::pop_style].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StyleVar {
+    Color(ThemeColor, ClayColor),
+    FontSize(FontSize, u32),
+    Spacing(Spacing, u16),
+    Radius(Radius, f32),
+    Size(Size, f32),
+}
+
+#[derive(Error, Debug)]
+pub enum ThemeError {
+    #[error("Io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line}: {text}")]
+    Parse { line: usize, text: String },
+}
+
+/// Colors, typography, spacing, radii and sizes used by the widgets. A theme can be loaded
+/// from a file so each system can ship its own look. Each line in the file has the format
+///
+/// ```text
+/// # Comment
+/// Base = Light
+/// Color.Accent = #98141F
+/// FontSize.Body = 28
+/// Spacing.Medium = 16
+/// Radius.Large = 16
+/// Size.ItemWidth = 250
+/// ```
+///
+/// `Base` selects the theme the values are applied on top of (`Dark`, `Light` or
+/// `HighContrast`) and defaults to `Dark`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    colors: [ClayColor; ALL_COLORS.len()],
+    font_sizes: [u32; ALL_FONT_SIZES.len()],
+    spacing: [u16; ALL_SPACINGS.len()],
+    radii: [f32; ALL_RADII.len()],
+    sizes: [f32; ALL_SIZES.len()],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            colors: [
+                ClayColor::rgba(255.0, 255.0, 255.0, 255.0),
+                ClayColor::rgba(255.0, 255.0, 255.0, 160.0),
+                ClayColor::rgba(152.0, 20.0, 31.0, 255.0),
+                ClayColor::rgba(30.0, 30.0, 30.0, 200.0),
+                ClayColor::rgba(70.0, 70.0, 70.0, 230.0),
+                ClayColor::rgba(15.0, 15.0, 15.0, 255.0),
+            ],
+            font_sizes: [20, 28, 36, 48],
+            spacing: [8, 16, 32],
+            radii: [6.0, 8.0, 16.0],
+            sizes: [48.0, 240.0, 250.0, 187.5, 40.0, 360.0],
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            colors: [
+                ClayColor::rgba(20.0, 20.0, 20.0, 255.0),
+                ClayColor::rgba(20.0, 20.0, 20.0, 160.0),
+                ClayColor::rgba(190.0, 30.0, 45.0, 255.0),
+                ClayColor::rgba(235.0, 235.0, 235.0, 220.0),
+                ClayColor::rgba(205.0, 205.0, 205.0, 240.0),
+                ClayColor::rgba(255.0, 255.0, 255.0, 255.0),
+            ],
+            ..Self::dark()
+        }
+    }
+
+    /// Opaque surfaces, bright text and larger fonts for users with low vision
+    pub fn high_contrast() -> Self {
+        Self::dark().with_high_contrast().with_text_scale(1.25)
+    }
+
+    /// The theme with its colors replaced by the high contrast colors. Text on every
+    /// background has a contrast ratio of at least 7:1.
+    pub fn with_high_contrast(&self) -> Self {
+        Self {
+            colors: [
+                ClayColor::rgba(255.0, 255.0, 255.0, 255.0),
+                ClayColor::rgba(230.0, 230.0, 230.0, 255.0),
+                ClayColor::rgba(0.0, 80.0, 200.0, 255.0),
+                ClayColor::rgba(0.0, 0.0, 0.0, 255.0),
+                ClayColor::rgba(90.0, 90.0, 90.0, 255.0),
+                ClayColor::rgba(40.0, 40.0, 40.0, 255.0),
+            ],
+            ..self.clone()
+        }
+    }
+
+    /// The theme with the font sizes multiplied by `scale`. The row heights grow with the
+    /// text so larger text still fits.
+    pub fn with_text_scale(&self, scale: f32) -> Self {
+        let mut theme = self.clone();
+        let scale = scale.max(0.1);
+
+        theme.font_sizes = self.font_sizes.map(|size| (size as f32 * scale).round().max(1.0) as u32);
+        theme.sizes[Size::RowHeight as usize] *= scale.max(1.0);
+        theme.sizes[Size::SelectorRowHeight as usize] *= scale.max(1.0);
+        theme
+    }
+
+    /// Loads a theme from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ThemeError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    /// Parses a theme from a string
+    pub fn parse(text: &str) -> Result<Self, ThemeError> {
+        let mut theme = Self::dark();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |text: String| ThemeError::Parse {
+                line: index + 1,
+                text,
+            };
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(parse_error(format!("Expected 'Name = Value', got '{}'", line)));
+            };
+
+            let (key, value) = (key.trim(), value.trim());
+
+            if key == "Base" {
+                theme = match value {
+                    "Dark" => Self::dark(),
+                    "Light" => Self::light(),
+                    "HighContrast" => Self::high_contrast(),
+                    _ => return Err(parse_error(format!("Unknown base theme '{}'", value))),
+                };
+                continue;
+            }
+
+            let var = parse_style_var(key, value).ok_or_else(|| {
+                parse_error(format!("Invalid value '{}' for '{}'", value, key))
+            })?;
+
+            theme.apply(var);
+        }
+
+        Ok(theme)
+    }
+
+    #[inline]
+    pub fn color(&self, color: ThemeColor) -> ClayColor {
+        self.colors[color as usize]
+    }
+
+    #[inline]
+    pub fn font_size(&self, size: FontSize) -> u32 {
+        self.font_sizes[size as usize]
+    }
+
+    #[inline]
+    pub fn spacing(&self, spacing: Spacing) -> u16 {
+        self.spacing[spacing as usize]
+    }
+
+    #[inline]
+    pub fn radius(&self, radius: Radius) -> f32 {
+        self.radii[radius as usize]
+    }
+
+    #[inline]
+    pub fn size(&self, size: Size) -> f32 {
+        self.sizes[size as usize]
+    }
+
+    /// The theme with all sizes multiplied by `scale`, colors are unchanged
+    pub fn scaled(&self, scale: f32) -> Self {
+        Self {
+            colors: self.colors,
+            font_sizes: self.font_sizes.map(|size| (size as f32 * scale).round().max(1.0) as u32),
+            spacing: self.spacing.map(|spacing| (spacing as f32 * scale).round() as u16),
+            radii: self.radii.map(|radius| radius * scale),
+            sizes: self.sizes.map(|size| size * scale),
+        }
+    }
+
+    /// Sets a value and returns the value it replaced
+    pub fn apply(&mut self, var: StyleVar) -> StyleVar {
+        match var {
+            StyleVar::Color(id, value) => {
+                StyleVar::Color(id, std::mem::replace(&mut self.colors[id as usize], value))
+            }
+            StyleVar::FontSize(id, value) => {
+                StyleVar::FontSize(id, std::mem::replace(&mut self.font_sizes[id as usize], value))
+            }
+            StyleVar::Spacing(id, value) => {
+                StyleVar::Spacing(id, std::mem::replace(&mut self.spacing[id as usize], value))
+            }
+            StyleVar::Radius(id, value) => {
+                StyleVar::Radius(id, std::mem::replace(&mut self.radii[id as usize], value))
+            }
+            StyleVar::Size(id, value) => {
+                StyleVar::Size(id, std::mem::replace(&mut self.sizes[id as usize], value))
+            }
+        }
+    }
+}
+
+fn find_by_name<T: std::fmt::Debug + Copy>(all: &[T], name: &str) -> Option<T> {
+    all.iter().find(|item| format!("{:?}", item) == name).copied()
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA`
+fn parse_color(value: &str) -> Option<ClayColor> {
+    let hex = value.strip_prefix('#')?;
+
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+
+    Some(ClayColor::u_rgba(channel(0)?, channel(1)?, channel(2)?, alpha))
+}
+
+fn parse_style_var(key: &str, value: &str) -> Option<StyleVar> {
+    let (group, name) = key.split_once('.')?;
+
+    match group {
+        "Color" => Some(StyleVar::Color(find_by_name(&ALL_COLORS, name)?, parse_color(value)?)),
+        "FontSize" => Some(StyleVar::FontSize(find_by_name(&ALL_FONT_SIZES, name)?, value.parse().ok()?)),
+        "Spacing" => Some(StyleVar::Spacing(find_by_name(&ALL_SPACINGS, name)?, value.parse().ok()?)),
+        "Radius" => Some(StyleVar::Radius(find_by_name(&ALL_RADII, name)?, value.parse().ok()?)),
+        "Size" => Some(StyleVar::Size(find_by_name(&ALL_SIZES, name)?, value.parse().ok()?)),
+        _ => None,
+    }
+}
+
+impl Ui<'_> {
+    /// A copy of the current theme, including the values changed with [Ui::push_style] and
+    /// the display settings from [Ui::set_high_contrast] and [Ui::set_text_scale]. Sizes are
+    /// scaled by [Ui::ui_scale] so they can be used directly in the layout.
+    pub fn theme(&self) -> Theme {
+        let display = self.state.accessibility.borrow().display;
+        let theme = self.state.theme.borrow();
+
+        let theme = if display.high_contrast { theme.with_high_contrast() } else { theme.clone() };
+        theme.with_text_scale(display.text_scale).scaled(self.ui_scale())
+    }
+
+    /// Replaces the theme, such as one loaded with [Theme::load]
+    pub fn set_theme(&self, theme: Theme) {
+        *self.state.theme.borrow_mut() = theme;
+    }
+
+    /// Overrides a theme value until the matching [Ui::pop_style]
+    pub fn push_style(&self, var: StyleVar) {
+        let previous = self.state.theme.borrow_mut().apply(var);
+        self.state.style_stack.borrow_mut().push(previous);
+    }
+
+    /// Restores the last `count` values changed with [Ui::push_style]
+    pub fn pop_style(&self, count: usize) {
+        let mut theme = self.state.theme.borrow_mut();
+        let mut style_stack = self.state.style_stack.borrow_mut();
+
+        for _ in 0..count {
+            let previous = style_stack.pop().expect("pop_style called without matching push_style");
+            theme.apply(previous);
+        }
+    }
+
+    /// Runs `f` with the theme values overridden
+    pub fn with_style<F: FnOnce(&Ui)>(&self, vars: &[StyleVar], f: F) {
+        for var in vars {
+            self.push_style(*var);
+        }
+
+        f(self);
+
+        self.pop_style(vars.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let theme = Theme::parse(
+            "# Comment\n\
+             Base = Light\n\
+             Color.Accent = #10203080\n\
+             FontSize.Body = 30\n\
+             Size.ItemWidth = 300.5",
+        )
+        .unwrap();
+
+        assert_eq!(theme.color(ThemeColor::Accent), ClayColor::rgba(16.0, 32.0, 48.0, 128.0));
+        assert_eq!(theme.color(ThemeColor::Text), Theme::light().color(ThemeColor::Text));
+        assert_eq!(theme.font_size(FontSize::Body), 30);
+        assert_eq!(theme.size(Size::ItemWidth), 300.5);
+        assert_eq!(theme.spacing(Spacing::Medium), Theme::dark().spacing(Spacing::Medium));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(Theme::parse("Color.Accent = red"), Err(ThemeError::Parse { line: 1, .. })));
+        assert!(matches!(Theme::parse("\nColor.Nope = #ffffff"), Err(ThemeError::Parse { line: 2, .. })));
+        assert!(Theme::parse("Base = Sepia").is_err());
+        assert!(Theme::parse("FontSize.Body").is_err());
+    }
+
+    #[test]
+    fn test_text_scale() {
+        let theme = Theme::dark().with_text_scale(1.5);
+
+        assert_eq!(theme.font_size(FontSize::Body), 42);
+        assert_eq!(theme.size(Size::RowHeight), 72.0);
+        assert_eq!(theme.size(Size::SelectorRowHeight), 540.0);
+        assert_eq!(theme.size(Size::ItemWidth), Theme::dark().size(Size::ItemWidth));
+        assert_eq!(Theme::parse("Base = HighContrast").unwrap(), Theme::high_contrast());
+    }
+
+    #[test]
+    fn test_apply_restores() {
+        let mut theme = Theme::dark();
+        let previous = theme.apply(StyleVar::Radius(Radius::Large, 2.0));
+
+        assert_eq!(theme.radius(Radius::Large), 2.0);
+        theme.apply(previous);
+        assert_eq!(theme, Theme::dark());
+    }
+}
@@ -0,0 +1,289 @@
+use glam::Vec2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TouchPhase {
+    Began,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
+/// Gestures recognized from the touch points during the last update
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Gesture {
+    /// Short touch that didn't move
+    Tap { pos: Vec2 },
+    /// Touch held in place for `long_press_time`
+    LongPress { pos: Vec2 },
+    /// Single touch being dragged, `delta` is the movement since the last update
+    Pan { pos: Vec2, delta: Vec2 },
+    /// Single touch released while moving, velocity in pixels per second. This is a swipe when
+    /// the velocity is above `fling_min_velocity`
+    Fling { velocity: Vec2 },
+    /// Two touches moving relative to each other. `scale` is the change in distance since the
+    /// last update
+    Pinch { center: Vec2, scale: f32 },
+}
+
+impl Gesture {
+    /// Returns the dominant direction of a fling as a unit vector along x or y
+    pub fn swipe_direction(&self) -> Option<Vec2> {
+        match *self {
+            Gesture::Fling { velocity } if velocity.x.abs() > velocity.y.abs() => {
+                Some(Vec2::new(velocity.x.signum(), 0.0))
+            }
+            Gesture::Fling { velocity } => Some(Vec2::new(0.0, velocity.y.signum())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TouchSettings {
+    /// Distance a touch can move and still be a tap or long press
+    pub touch_slop: f32,
+    pub long_press_time: f32,
+    pub fling_min_velocity: f32,
+}
+
+impl Default for TouchSettings {
+    fn default() -> Self {
+        Self {
+            touch_slop: 10.0,
+            long_press_time: 0.5,
+            fling_min_velocity: 400.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TouchPoint {
+    pub id: u64,
+    pub pos: Vec2,
+    pub start_pos: Vec2,
+    pub start_time: f32,
+    /// Smoothed velocity in pixels per second
+    pub velocity: Vec2,
+    prev_pos: Vec2,
+    moved: bool,
+    long_pressed: bool,
+    /// Has been part of a multi-touch gesture, so it won't emit single touch gestures
+    multi: bool,
+    ended: bool,
+    new: bool,
+}
+
+/// Tracks active touch points and recognizes gestures from them
+#[derive(Debug, Default)]
+pub(crate) struct TouchTracker {
+    pub(crate) settings: TouchSettings,
+    pub(crate) touches: Vec<TouchPoint>,
+    pub(crate) gestures: Vec<Gesture>,
+    pinch_distance: Option<f32>,
+}
+
+impl TouchTracker {
+    pub(crate) fn apply_event(&mut self, id: u64, phase: TouchPhase, pos: Vec2) {
+        match phase {
+            TouchPhase::Began => {
+                self.touches.retain(|touch| touch.id != id);
+                self.touches.push(TouchPoint {
+                    id,
+                    pos,
+                    start_pos: pos,
+                    start_time: 0.0,
+                    velocity: Vec2::ZERO,
+                    prev_pos: pos,
+                    moved: false,
+                    long_pressed: false,
+                    multi: false,
+                    ended: false,
+                    new: true,
+                });
+            }
+            TouchPhase::Moved | TouchPhase::Ended => {
+                if let Some(touch) = self.touches.iter_mut().find(|touch| touch.id == id) {
+                    touch.pos = pos;
+                    touch.ended = phase == TouchPhase::Ended;
+                }
+            }
+            TouchPhase::Cancelled => {
+                self.touches.retain(|touch| touch.id != id);
+            }
+        }
+    }
+
+    pub(crate) fn update(&mut self, time: f32, delta_time: f32) {
+        self.gestures.clear();
+
+        let settings = &self.settings;
+        let slop2 = settings.touch_slop * settings.touch_slop;
+        let is_multi = self.touches.len() > 1;
+
+        for touch in self.touches.iter_mut() {
+            if touch.new {
+                touch.start_time = time;
+                touch.new = false;
+            }
+
+            if delta_time > 0.0 {
+                let velocity = (touch.pos - touch.prev_pos) / delta_time;
+                touch.velocity = touch.velocity.lerp(velocity, 0.5);
+            }
+
+            if touch.pos.distance_squared(touch.start_pos) > slop2 {
+                touch.moved = true;
+            }
+
+            touch.multi |= is_multi;
+
+            if touch.multi {
+                continue;
+            }
+
+            if touch.ended {
+                if !touch.moved && !touch.long_pressed {
+                    self.gestures.push(Gesture::Tap { pos: touch.pos });
+                } else if touch.moved && touch.velocity.length() >= settings.fling_min_velocity {
+                    self.gestures.push(Gesture::Fling {
+                        velocity: touch.velocity,
+                    });
+                }
+            } else if touch.moved {
+                let delta = touch.pos - touch.prev_pos;
+
+                if delta != Vec2::ZERO {
+                    self.gestures.push(Gesture::Pan {
+                        pos: touch.pos,
+                        delta,
+                    });
+                }
+            } else if !touch.long_pressed && time - touch.start_time >= settings.long_press_time {
+                touch.long_pressed = true;
+                self.gestures.push(Gesture::LongPress { pos: touch.pos });
+            }
+        }
+
+        self.update_pinch();
+
+        for touch in self.touches.iter_mut() {
+            touch.prev_pos = touch.pos;
+        }
+
+        self.touches.retain(|touch| !touch.ended);
+    }
+
+    fn update_pinch(&mut self) {
+        let active: Vec<&TouchPoint> = self.touches.iter().filter(|t| !t.ended).collect();
+
+        if active.len() != 2 {
+            self.pinch_distance = None;
+            return;
+        }
+
+        let distance = active[0].pos.distance(active[1].pos);
+        let center = (active[0].pos + active[1].pos) * 0.5;
+
+        if let Some(prev) = self.pinch_distance.filter(|d| *d > 0.0) {
+            if distance != prev {
+                self.gestures.push(Gesture::Pinch {
+                    center,
+                    scale: distance / prev,
+                });
+            }
+        }
+
+        self.pinch_distance = Some(distance);
+    }
+
+    pub(crate) fn is_touching(&self) -> bool {
+        !self.touches.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Time step of a frame and the touch event applied before it
+    type Frame = (f32, Option<(u64, TouchPhase, Vec2)>);
+
+    fn run(tracker: &mut TouchTracker, frames: &[Frame]) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        let mut time = 0.0;
+
+        for (dt, event) in frames {
+            if let Some((id, phase, pos)) = event {
+                tracker.apply_event(*id, *phase, *pos);
+            }
+            time += dt;
+            tracker.update(time, *dt);
+            gestures.extend_from_slice(&tracker.gestures);
+        }
+
+        gestures
+    }
+
+    #[test]
+    fn test_tap_and_long_press() {
+        let mut tracker = TouchTracker::default();
+        let pos = Vec2::new(10.0, 10.0);
+
+        let gestures = run(&mut tracker, &[
+            (0.016, Some((0, TouchPhase::Began, pos))),
+            (0.016, Some((0, TouchPhase::Ended, pos))),
+        ]);
+        assert_eq!(gestures, vec![Gesture::Tap { pos }]);
+
+        let gestures = run(&mut tracker, &[
+            (0.016, Some((0, TouchPhase::Began, pos))),
+            (0.6, None),
+            (0.016, Some((0, TouchPhase::Ended, pos))),
+        ]);
+        assert_eq!(gestures, vec![Gesture::LongPress { pos }]);
+        assert!(!tracker.is_touching());
+    }
+
+    #[test]
+    fn test_fling() {
+        let mut tracker = TouchTracker::default();
+        let dt = 0.016;
+
+        let mut frames = vec![(dt, Some((0, TouchPhase::Began, Vec2::ZERO)))];
+        for i in 1..5 {
+            frames.push((dt, Some((0, TouchPhase::Moved, Vec2::new(0.0, i as f32 * -20.0)))));
+        }
+        frames.push((dt, Some((0, TouchPhase::Ended, Vec2::new(0.0, -100.0)))));
+
+        let gestures = run(&mut tracker, &frames);
+        assert!(matches!(gestures[0], Gesture::Pan { .. }));
+
+        let fling = gestures.last().unwrap();
+        assert!(matches!(fling, Gesture::Fling { .. }));
+        assert_eq!(fling.swipe_direction(), Some(Vec2::new(0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_pinch() {
+        let mut tracker = TouchTracker::default();
+        tracker.apply_event(0, TouchPhase::Began, Vec2::new(0.0, 0.0));
+        tracker.apply_event(1, TouchPhase::Began, Vec2::new(100.0, 0.0));
+        tracker.update(0.0, 0.016);
+
+        tracker.apply_event(1, TouchPhase::Moved, Vec2::new(200.0, 0.0));
+        tracker.update(0.016, 0.016);
+        assert_eq!(
+            tracker.gestures,
+            vec![Gesture::Pinch {
+                center: Vec2::new(100.0, 0.0),
+                scale: 2.0
+            }]
+        );
+
+        // Releasing after a pinch doesn't tap
+        tracker.apply_event(0, TouchPhase::Ended, Vec2::new(0.0, 0.0));
+        tracker.apply_event(1, TouchPhase::Ended, Vec2::new(200.0, 0.0));
+        tracker.update(0.032, 0.016);
+        assert!(tracker.gestures.is_empty());
+    }
+}
@@ -1,119 +1,874 @@
-use crate::Ui;
-use bitflags::bitflags;
+use crate::accessibility::{AccessNode, Role};
+use crate::flags::BoxFlags;
+use crate::input::{Input, Key};
+use crate::signal::{Signal, SignalFlags};
+use crate::theme::{FontSize, Radius, Size, Spacing, ThemeColor};
+use crate::{ActionResponse, ClayColor, Declaration, Id, InputAction, TextConfig, Ui};
+use crate::{fixed, grow, Alignment, LayoutAlignmentX, LayoutAlignmentY, LayoutDirection, Padding};
+use clay_layout::elements::{FloatingAttachPointType, FloatingAttachToElement, PointerCaptureMode};
+use clay_layout::fit;
+use clay_layout::text::TextElementConfigWrapMode;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
 
-bitflags! {
-    pub(crate) struct SignalFlags: u32 {
-        const LEFT_PRESSED             = 1 << 0;
-        const MIDDLE_PRESSED           = 1 << 1;
-        const RIGHT_PRESSED            = 1 << 2;
+const TRACK_HEIGHT: f32 = 8.0;
+const CARET_WIDTH: f32 = 2.0;
 
-        const LEFT_DRAGGING            = 1 << 3;
-        const MIDDLE_DRAGGING          = 1 << 4;
-        const RIGHT_DRAGGING           = 1 << 5;
-/*
-        const LEFT_DOUBLE_DRAGGING     = 1 << 6;
-        const MIDDLE_DOUBLE_DRAGGING   = 1 << 7;
-        const RIGHT_DOUBLE_DRAGGING    = 1 << 8;
+/// State of a widget that has to be kept between frames. Widgets that aren't used during a
+/// frame lose their state.
+#[derive(Debug, Default)]
+pub(crate) struct WidgetState {
+    cursor: TextCursor,
+    /// Dropdown popup is open
+    open: bool,
+    /// Highlighted option in an open dropdown
+    highlight: usize,
+    /// First visible row of a list
+    scroll: usize,
+    pub(crate) frame: u64,
+}
+
+/// Caret and selection of a text field as byte offsets into the text. The selection is the
+/// range between `anchor` and `caret`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub(crate) struct TextCursor {
+    caret: usize,
+    anchor: usize,
+}
+
+impl WidgetState {
+    /// Returns the state for the widget and marks it as used this frame. The state is
+    /// borrowed until the returned value is dropped.
+    fn get(states: &RefCell<HashMap<u32, WidgetState>>, id: Id, frame: u64) -> RefMut<'_, WidgetState> {
+        RefMut::map(states.borrow_mut(), |states| {
+            let widget = states.entry(id.id.id).or_default();
+            widget.frame = frame;
+            widget
+        })
+    }
+}
+
+impl TextCursor {
+    fn selection(&self) -> (usize, usize) {
+        (self.caret.min(self.anchor), self.caret.max(self.anchor))
+    }
+
+    fn has_selection(&self) -> bool {
+        self.caret != self.anchor
+    }
 
-        const LEFT_TRIPLE_DRAGGING     = 1 << 9;
-        const MIDDLE_TRIPLE_DRAGGING   = 1 << 10;
-        const RIGHT_TRIPLE_DRAGGING    = 1 << 11;
-*/
-        const LEFT_CLICKED             = 1 << 15;
-        const MIDDLE_CLICKED           = 1 << 16;
-        const RIGHT_CLICKED            = 1 << 17;
+    fn move_to(&mut self, pos: usize, select: bool) {
+        self.caret = pos;
 
-        const LEFT_RELEASED            = 1 << 12;
-        const MIDDLE_RELEASED          = 1 << 13;
-        const RIGHT_RELEASED           = 1 << 14;
+        if !select {
+            self.anchor = pos;
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum EditCommand {
+    Insert(char),
+    Backspace,
+    Delete,
+    Left { select: bool },
+    Right { select: bool },
+    Home { select: bool },
+    End { select: bool },
+    SelectAll,
+}
 
-        const LEFT_DOUBLE_CLICKED      = 1 << 18;
-        const MIDDLE_DOUBLE_CLICKED    = 1 << 19;
-        const RIGHT_DOUBLE_CLICKED     = 1 << 20;
+fn prev_boundary(text: &str, pos: usize) -> usize {
+    text[..pos].char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+fn next_boundary(text: &str, pos: usize) -> usize {
+    text[pos..].chars().next().map_or(pos, |c| pos + c.len_utf8())
+}
 
-        const LEFT_TRIPLE_CLICKED      = 1 << 21;
-        const MIDDLE_TRIPLE_CLICKED    = 1 << 22;
-        const RIGHT_TRIPLE_CLICKED     = 1 << 23;
+/// Applies an edit to the text and returns true if the text changed
+fn apply_edit(text: &mut String, cursor: &mut TextCursor, command: EditCommand) -> bool {
+    // The text can be changed by the caller between frames
+    cursor.caret = cursor.caret.min(text.len());
+    cursor.anchor = cursor.anchor.min(text.len());
 
-        const KEYBOARD_PRESSED         = 1 << 24;
+    let delete_selection = |text: &mut String, cursor: &mut TextCursor| {
+        let (start, end) = cursor.selection();
+        text.replace_range(start..end, "");
+        cursor.move_to(start, false);
+    };
+
+    match command {
+        EditCommand::Insert(c) => {
+            delete_selection(text, cursor);
+            text.insert(cursor.caret, c);
+            cursor.move_to(cursor.caret + c.len_utf8(), false);
+            true
+        }
+        EditCommand::Backspace | EditCommand::Delete if cursor.has_selection() => {
+            delete_selection(text, cursor);
+            true
+        }
+        EditCommand::Backspace => {
+            let start = prev_boundary(text, cursor.caret);
+            text.replace_range(start..cursor.caret, "");
+            let changed = start != cursor.caret;
+            cursor.move_to(start, false);
+            changed
+        }
+        EditCommand::Delete => {
+            let end = next_boundary(text, cursor.caret);
+            text.replace_range(cursor.caret..end, "");
+            end != cursor.caret
+        }
+        EditCommand::Left { select } => {
+            let pos = if cursor.has_selection() && !select {
+                cursor.selection().0
+            } else {
+                prev_boundary(text, cursor.caret)
+            };
+            cursor.move_to(pos, select);
+            false
+        }
+        EditCommand::Right { select } => {
+            let pos = if cursor.has_selection() && !select {
+                cursor.selection().1
+            } else {
+                next_boundary(text, cursor.caret)
+            };
+            cursor.move_to(pos, select);
+            false
+        }
+        EditCommand::Home { select } => {
+            cursor.move_to(0, select);
+            false
+        }
+        EditCommand::End { select } => {
+            cursor.move_to(text.len(), select);
+            false
+        }
+        EditCommand::SelectAll => {
+            cursor.anchor = 0;
+            cursor.caret = text.len();
+            false
+        }
+    }
+}
 
-        const HOVERING                 = 1 << 25;
-        const MOUSE_OVER               = 1 << 26;
+/// Translates the keyboard and text input of this frame to edit commands
+fn edit_commands(input: &Input) -> Vec<EditCommand> {
+    let shift = input.is_key_down(Key::LeftShift) || input.is_key_down(Key::RightShift);
+    let ctrl = input.is_key_down(Key::LeftCtrl) || input.is_key_down(Key::RightCtrl);
 
-        const ENTER_HOVER              = 1 << 27;
-        const EXIT_HOVER               = 1 << 28;
+    let mut commands: Vec<EditCommand> = input
+        .chars()
+        .iter()
+        .filter(|c| !c.is_control())
+        .map(|c| EditCommand::Insert(*c))
+        .collect();
 
-        // High-level combinations
-        const PRESSED                  = Self::LEFT_PRESSED.bits() | Self::KEYBOARD_PRESSED.bits();
-        const RELEASED                 = Self::LEFT_RELEASED.bits();
+    let keys = [
+        (Key::Backspace, EditCommand::Backspace),
+        (Key::Delete, EditCommand::Delete),
+        (Key::LeftArrow, EditCommand::Left { select: shift }),
+        (Key::RightArrow, EditCommand::Right { select: shift }),
+        (Key::Home, EditCommand::Home { select: shift }),
+        (Key::End, EditCommand::End { select: shift }),
+    ];
 
-        const CLICKED                  = Self::LEFT_CLICKED.bits() | Self::KEYBOARD_PRESSED.bits();
+    for (key, command) in keys {
+        if input.is_key_pressed_repeat(key) {
+            commands.push(command);
+        }
+    }
 
-        const DOUBLE_CLICKED           = Self::LEFT_DOUBLE_CLICKED.bits();
-        const TRIPLE_CLICKED           = Self::LEFT_TRIPLE_CLICKED.bits();
-        const DRAGGING                 = Self::LEFT_DRAGGING.bits();
+    if ctrl && input.is_key_pressed(Key::A) {
+        commands.push(EditCommand::SelectAll);
     }
+
+    commands
+}
+
+/// Number of decimals needed to show values that are multiples of `step`
+fn step_decimals(step: f32) -> usize {
+    (0..4)
+        .find(|decimals| {
+            let scaled = step * 10f32.powi(*decimals as i32);
+            (scaled - scaled.round()).abs() < 1e-3
+        })
+        .unwrap_or(4)
 }
 
-// Define a struct to hold the flags
-pub struct Signals {
-    pub(crate) flags: SignalFlags,
+fn snap_to_step(value: f32, range: &RangeInclusive<f32>, step: f32) -> f32 {
+    let value = if step > 0.0 {
+        range.start() + ((value - range.start()) / step).round() * step
+    } else {
+        value
+    };
+
+    value.clamp(*range.start(), *range.end())
 }
 
-impl Signals {
-    pub fn new() -> Self {
-        Signals {
-            flags: SignalFlags::empty(),
+impl Ui<'_> {
+    /// Keeps a generated string alive until the frame has been rendered as Clay only stores
+    /// a pointer to the text
+    pub(crate) fn frame_str(&self, text: &str) -> &str {
+        self.state.frame_strings.alloc_str(text)
+    }
+
+    pub(crate) fn widget_text(&self, text: &str, color: ClayColor) {
+        self.themed_text(text, FontSize::Body, color);
+    }
+
+    pub(crate) fn themed_text(&self, text: &str, size: FontSize, color: ClayColor) {
+        if text.is_empty() {
+            return;
+        }
+
+        let state = &self.state;
+        let font_size = self.theme().font_size(size);
+        let font_id = state.text.active_font.get();
+        let _ = state.text.generator.borrow_mut().queue_generate_text(text, font_size, font_id, &state.bg_worker);
+
+        state.layout.text(
+            text,
+            TextConfig::new()
+                .font_id(font_id as u16)
+                .font_size(font_size as _)
+                .wrap_mode(TextElementConfigWrapMode::None)
+                .color(color)
+                .end(),
+        );
+    }
+
+    fn spacer(&self) {
+        let state = &self.state;
+        state.layout.with(Declaration::new().layout().width(grow!()).end(), |_| {});
+    }
+
+    /// Lets the focused widget use the move actions on an axis this frame instead of them
+    /// moving the focus
+    pub(crate) fn capture_navigation(&self, id: Id, x: bool, y: bool) {
+        if self.is_focused(id) {
+            self.state.focus.borrow_mut().capture(x, y);
+        }
+    }
+
+    fn action_pressed(&self, action: InputAction) -> bool {
+        self.get_input_action(action) == ActionResponse::Pressed
+    }
+
+    /// Row with the label to the left and the control built by `f` to the right. Pressing
+    /// the row with the mouse focuses it.
+    #[rustfmt::skip]
+    fn widget_row<F: FnOnce(&Ui)>(&self, id: Id, label: &str, focusable: bool, f: F) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+
+        if focusable {
+            self.focusable(id, BoxFlags::DEFAULT_FOCUS_NAV);
+        }
+
+        let color = theme.color(if self.is_focused(id) { ThemeColor::SurfaceFocused } else { ThemeColor::Surface });
+
+        state.layout.with(Declaration::new()
+            .id(id)
+            .layout()
+                .width(grow!())
+                .height(fixed!(theme.size(Size::RowHeight)))
+                .padding(Padding::horizontal(theme.spacing(Spacing::Medium)))
+                .child_gap(theme.spacing(Spacing::Medium))
+                .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Center))
+            .end()
+            .corner_radius().all(theme.radius(Radius::Medium)).end()
+            .background_color(color), |_| {
+                self.widget_text(label, theme.color(ThemeColor::Text));
+                self.spacer();
+                f(self);
+            });
+
+        let signal = self.signal_id(id);
+
+        if focusable && signal.flags.contains(SignalFlags::LEFT_PRESSED) {
+            self.set_focus_id(id);
+        }
+
+        signal
+    }
+
+    /// On/off switch. Clicking the row or pressing select while it's focused flips the value.
+    #[rustfmt::skip]
+    pub fn toggle(&self, label: &str, value: &mut bool) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let on = *value;
+
+        let mut signal = self.widget_row(id, label, true, |_| {
+            let alignment = if on { LayoutAlignmentX::Right } else { LayoutAlignmentX::Left };
+
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fixed!(self.scaled(56.0)))
+                    .height(fixed!(self.scaled(28.0)))
+                    .padding(Padding::all(self.scaled(4.0) as u16))
+                    .child_alignment(Alignment::new(alignment, LayoutAlignmentY::Center))
+                .end()
+                .corner_radius().all(self.scaled(14.0)).end()
+                .background_color(theme.color(if on { ThemeColor::Accent } else { ThemeColor::Control })), |_| {
+                    state.layout.with(Declaration::new()
+                        .layout().width(fixed!(self.scaled(20.0))).height(fixed!(self.scaled(20.0))).end()
+                        .corner_radius().all(self.scaled(10.0)).end()
+                        .background_color(theme.color(ThemeColor::Text)), |_| {});
+                });
+        });
+
+        if signal.clicked() {
+            *value = !*value;
+            signal.flags.insert(SignalFlags::CHANGED);
         }
+
+        self.access_node(id, || AccessNode::new(Role::Switch, label).checked(*value));
+        signal
     }
 
-    #[inline]
-    pub fn is_left_pressed(&self) -> bool {
-        self.flags.contains(SignalFlags::LEFT_PRESSED)
+    /// Horizontal slider. The value can be dragged with the mouse or changed by `step` with
+    /// left/right while the slider is focused.
+    #[rustfmt::skip]
+    pub fn slider(&self, label: &str, value: &mut f32, range: RangeInclusive<f32>, step: f32) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let track_id = state.layout.id_index(label, 1);
+        let track_height = self.scaled(TRACK_HEIGHT);
+
+        let length = range.end() - range.start();
+        let t = if length > 0.0 { ((*value - range.start()) / length).clamp(0.0, 1.0) } else { 0.0 };
+        let value_text = self.frame_str(&format!("{:.*}", step_decimals(step), *value));
+
+        let mut signal = self.widget_row(id, label, true, |_| {
+            state.layout.with(Declaration::new()
+                .id(track_id)
+                .layout()
+                    .width(fixed!(theme.size(Size::ControlWidth)))
+                    .height(fixed!(track_height))
+                .end()
+                .corner_radius().all(track_height * 0.5).end()
+                .background_color(theme.color(ThemeColor::Control)), |_| {
+                    state.layout.with(Declaration::new()
+                        .layout().width(fixed!(theme.size(Size::ControlWidth) * t)).height(grow!()).end()
+                        .corner_radius().all(track_height * 0.5).end()
+                        .background_color(theme.color(ThemeColor::Accent)), |_| {});
+                });
+
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fixed!(self.scaled(72.0)))
+                    .child_alignment(Alignment::new(LayoutAlignmentX::Right, LayoutAlignmentY::Center))
+                .end(), |_| {
+                    self.widget_text(value_text, theme.color(ThemeColor::TextDim));
+                });
+        });
+
+        self.capture_navigation(id, true, false);
+
+        let mut new_value = *value;
+
+        if self.is_focused(id) {
+            if self.action_pressed(InputAction::MoveLeft) {
+                new_value -= step;
+            }
+            if self.action_pressed(InputAction::MoveRight) {
+                new_value += step;
+            }
+        }
+
+        let track = self.signal_id(track_id);
+
+        if track.flags.intersects(SignalFlags::LEFT_PRESSED | SignalFlags::LEFT_DRAGGING) {
+            let aabb = self.item_state(track_id).map(|item| item.aabb).unwrap_or_default();
+            let width = aabb.z - aabb.x;
+
+            if width > 0.0 {
+                let mouse_t = ((state.input.borrow().mouse_pos.x - aabb.x) / width).clamp(0.0, 1.0);
+                new_value = range.start() + mouse_t * length;
+            }
+
+            self.set_focus_id(id);
+        }
+
+        let new_value = snap_to_step(new_value, &range, step);
+
+        if new_value != *value {
+            *value = new_value;
+            signal.flags.insert(SignalFlags::CHANGED);
+        }
+
+        signal.flags |= track.flags & SignalFlags::DRAGGING;
+        self.access_node(id, || AccessNode::new(Role::Slider, label).value(format!("{:.*}", step_decimals(step), *value)));
+        signal
     }
 
-    #[inline]
-    pub fn is_middle_pressed(&self) -> bool {
-        self.flags.contains(SignalFlags::MIDDLE_PRESSED)
+    /// Steps through the options with left/right while focused or by clicking the arrows.
+    /// The selection wraps around at the ends.
+    #[rustfmt::skip]
+    pub fn cycler(&self, label: &str, options: &[&str], selected: &mut usize) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let prev_id = state.layout.id_index(label, 1);
+        let next_id = state.layout.id_index(label, 2);
+        let current = options.get(*selected).copied().unwrap_or("");
+
+        let mut signal = self.widget_row(id, label, true, |_| {
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fixed!(theme.size(Size::ControlWidth)))
+                    .child_alignment(Alignment::new(LayoutAlignmentX::Center, LayoutAlignmentY::Center))
+                .end(), |_| {
+                    state.layout.with(Declaration::new().id(prev_id), |_| {
+                        self.widget_text("<", theme.color(ThemeColor::TextDim));
+                    });
+                    self.spacer();
+                    self.widget_text(current, theme.color(ThemeColor::Text));
+                    self.spacer();
+                    state.layout.with(Declaration::new().id(next_id), |_| {
+                        self.widget_text(">", theme.color(ThemeColor::TextDim));
+                    });
+                });
+        });
+
+        self.capture_navigation(id, true, false);
+
+        if options.is_empty() {
+            self.access_node(id, || AccessNode::new(Role::ComboBox, label));
+            return signal;
+        }
+
+        let focused = self.is_focused(id);
+        let prev = self.signal_id(prev_id).flags.contains(SignalFlags::LEFT_CLICKED)
+            || (focused && self.action_pressed(InputAction::MoveLeft));
+        let next = self.signal_id(next_id).flags.contains(SignalFlags::LEFT_CLICKED)
+            || (focused && self.action_pressed(InputAction::MoveRight))
+            || signal.flags.contains(SignalFlags::KEYBOARD_PRESSED);
+
+        let count = options.len();
+        let old = *selected;
+
+        if prev {
+            *selected = (*selected + count - 1) % count;
+        } else if next {
+            *selected = (*selected + 1) % count;
+        }
+
+        if *selected != old {
+            signal.flags.insert(SignalFlags::CHANGED);
+        }
+
+        self.access_node(id, || AccessNode::new(Role::ComboBox, label).value(options[*selected % count]));
+        signal
     }
 
-    #[inline]
-    pub fn is_right_pressed(&self) -> bool {
-        self.flags.contains(SignalFlags::RIGHT_PRESSED)
+    /// Shows the selected option and opens a popup with all options when clicked. While the
+    /// popup is open the move actions change the highlighted option, select picks it and
+    /// cancel closes the popup.
+    #[rustfmt::skip]
+    pub fn dropdown(&self, label: &str, options: &[&str], selected: &mut usize) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let widget = WidgetState::get(&state.widget_states, id, state.current_frame.get());
+        let (is_open, highlight) = (widget.open, widget.highlight);
+        drop(widget);
+        let current = options.get(*selected).copied().unwrap_or("");
+
+        let mut signal = self.widget_row(id, label, true, |_| {
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fixed!(theme.size(Size::ControlWidth)))
+                    .height(fixed!(theme.size(Size::RowHeight) - self.scaled(12.0)))
+                    .padding(Padding::horizontal(theme.spacing(Spacing::Small)))
+                    .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Center))
+                .end()
+                .corner_radius().all(theme.radius(Radius::Small)).end()
+                .background_color(theme.color(ThemeColor::Control)), |_| {
+                    self.widget_text(current, theme.color(ThemeColor::Text));
+                    self.spacer();
+                    self.widget_text("v", theme.color(ThemeColor::TextDim));
+
+                    if !is_open {
+                        return;
+                    }
+
+                    state.layout.with(Declaration::new()
+                        .layout()
+                            .width(fixed!(theme.size(Size::ControlWidth)))
+                            .direction(LayoutDirection::TopToBottom)
+                            .padding(Padding::vertical(theme.spacing(Spacing::Small) / 2))
+                        .end()
+                        .floating()
+                            .attach_to(FloatingAttachToElement::Parent)
+                            .attach_points(FloatingAttachPointType::LeftTop, FloatingAttachPointType::LeftBottom)
+                            .z_index(100)
+                            .pointer_capture_mode(PointerCaptureMode::Capture)
+                        .end()
+                        .corner_radius().all(theme.radius(Radius::Small)).end()
+                        .background_color(theme.color(ThemeColor::Control)), |_| {
+                            for (index, option) in options.iter().enumerate() {
+                                let color = theme.color(if index == highlight { ThemeColor::Accent } else { ThemeColor::Control });
+
+                                state.layout.with(Declaration::new()
+                                    .id(state.layout.id_index(label, index as u32 + 1))
+                                    .layout()
+                                        .width(grow!())
+                                        .height(fixed!(theme.size(Size::RowHeight) - self.scaled(12.0)))
+                                        .padding(Padding::horizontal(theme.spacing(Spacing::Small)))
+                                        .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Center))
+                                    .end()
+                                    .background_color(color), |_| {
+                                        self.widget_text(option, theme.color(ThemeColor::Text));
+                                    });
+                            }
+                        });
+                });
+        });
+
+        let mut widget = WidgetState::get(&state.widget_states, id, state.current_frame.get());
+        let old = *selected;
+
+        if widget.open {
+            self.capture_navigation(id, true, true);
+
+            let count = options.len();
+
+            if self.action_pressed(InputAction::MoveUp) {
+                widget.highlight = widget.highlight.saturating_sub(1);
+            }
+            if self.action_pressed(InputAction::MoveDown) && widget.highlight + 1 < count {
+                widget.highlight += 1;
+            }
+
+            let clicked = (0..count).find(|index| {
+                self.signal_id(state.layout.id_index(label, *index as u32 + 1))
+                    .flags
+                    .contains(SignalFlags::LEFT_CLICKED)
+            });
+
+            if let Some(index) = clicked {
+                *selected = index;
+                widget.open = false;
+            } else if signal.flags.contains(SignalFlags::KEYBOARD_PRESSED) {
+                if count > 0 {
+                    *selected = widget.highlight;
+                }
+                widget.open = false;
+            } else if signal.flags.contains(SignalFlags::LEFT_CLICKED)
+                || (self.is_focused(id) && self.action_pressed(InputAction::Cancel))
+                || !self.is_focused(id)
+            {
+                widget.open = false;
+            }
+        } else if signal.clicked() && !options.is_empty() {
+            widget.open = true;
+            widget.highlight = (*selected).min(options.len() - 1);
+        }
+
+        let is_open = widget.open;
+        drop(widget);
+
+        if *selected != old {
+            signal.flags.insert(SignalFlags::CHANGED);
+        }
+
+        self.access_node(id, || {
+            let node = AccessNode::new(Role::ComboBox, label).expanded(is_open);
+            match options.get(*selected) {
+                Some(option) => node.value(*option),
+                None => node,
+            }
+        });
+
+        signal
     }
-    /*
-        #[inline]
-        pub fn is_left_dragging(&self) -> bool {
-            self.flags.contains(SignalFlags::LEFT_DRAGGING)
+
+    /// Scrollable list showing `visible_rows` items at a time. While focused up/down move the
+    /// selection and the focus only leaves the list when moving past the first or last item.
+    #[rustfmt::skip]
+    pub fn list(&self, label: &str, items: &[&str], selected: &mut usize, visible_rows: usize) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let visible_rows = visible_rows.max(1);
+        let count = items.len();
+        let scroll = WidgetState::get(&state.widget_states, id, state.current_frame.get()).scroll.min(count.saturating_sub(visible_rows));
+        let focused = self.is_focused(id);
+
+        self.focusable(id, BoxFlags::DEFAULT_FOCUS_NAV);
+
+        state.layout.with(Declaration::new()
+            .id(id)
+            .layout()
+                .width(grow!())
+                .direction(LayoutDirection::TopToBottom)
+                .padding(Padding::all(theme.spacing(Spacing::Small)))
+                .child_gap(theme.spacing(Spacing::Small) / 2)
+            .end()
+            .corner_radius().all(theme.radius(Radius::Medium)).end()
+            .background_color(theme.color(if focused { ThemeColor::SurfaceFocused } else { ThemeColor::Surface })), |_| {
+                self.widget_text(label, theme.color(ThemeColor::TextDim));
+
+                for (index, item) in items.iter().enumerate().skip(scroll).take(visible_rows) {
+                    let color = theme.color(if index == *selected { ThemeColor::Accent } else { ThemeColor::Control });
+
+                    state.layout.with(Declaration::new()
+                        .id(state.layout.id_index(label, index as u32 + 1))
+                        .layout()
+                            .width(grow!())
+                            .height(fixed!(theme.size(Size::RowHeight) - self.scaled(8.0)))
+                            .padding(Padding::horizontal(theme.spacing(Spacing::Small)))
+                            .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Center))
+                        .end()
+                        .corner_radius().all(theme.radius(Radius::Small)).end()
+                        .background_color(color), |_| {
+                            self.widget_text(item, theme.color(ThemeColor::Text));
+                        });
+                }
+            });
+
+        let mut signal = self.signal_id(id);
+
+        if count == 0 {
+            self.access_list(id, label, items, *selected, 0..0);
+            return signal;
+        }
+
+        let old = (*selected).min(count - 1);
+        let mut new = old;
+
+        if focused {
+            let up = self.action_pressed(InputAction::MoveUp);
+            let down = self.action_pressed(InputAction::MoveDown);
+
+            if up {
+                new = new.saturating_sub(1);
+            }
+            if down {
+                new = (new + 1).min(count - 1);
+            }
+            if self.action_pressed(InputAction::PageUp) {
+                new = new.saturating_sub(visible_rows);
+            }
+            if self.action_pressed(InputAction::PageDown) {
+                new = (new + visible_rows).min(count - 1);
+            }
+
+            // Moving past the ends lets the focus leave the list
+            let at_edge = (up && old == 0) || (down && old == count - 1);
+            self.capture_navigation(id, false, !at_edge);
+        }
+
+        for index in scroll..(scroll + visible_rows).min(count) {
+            let row = self.signal_id(state.layout.id_index(label, index as u32 + 1));
+
+            if row.flags.contains(SignalFlags::LEFT_CLICKED) {
+                new = index;
+                self.set_focus_id(id);
+            }
         }
 
-        #[inline]
-        pub fn is_middle_dragging(&self) -> bool {
-            self.flags.contains(SignalFlags::MIDDLE_DRAGGING)
+        let mut widget = WidgetState::get(&state.widget_states, id, state.current_frame.get());
+        widget.scroll = scroll;
+
+        if signal.scrolled() && !focused {
+            let rows = -signal.scroll.y.signum() as isize;
+            widget.scroll = (scroll as isize + rows).clamp(0, count.saturating_sub(visible_rows) as isize) as usize;
         }
 
-        #[inline]
-        pub fn is_right_dragging(&self) -> bool {
-            self.flags.contains(SignalFlags::RIGHT_DRAGGING)
+        if new != old || new != *selected {
+            *selected = new;
+            signal.flags.insert(SignalFlags::CHANGED);
+
+            // Keep the selection visible
+            if new < widget.scroll {
+                widget.scroll = new;
+            } else if new >= widget.scroll + visible_rows {
+                widget.scroll = new + 1 - visible_rows;
+            }
         }
-    */
 
-    #[inline]
-    pub fn is_left_clicked(&self) -> bool {
-        self.flags.contains(SignalFlags::LEFT_CLICKED)
+        let visible = widget.scroll..(widget.scroll + visible_rows).min(count);
+        drop(widget);
+        self.access_list(id, label, items, *selected, visible);
+        signal
+    }
+
+    /// List node with the items that are scrolled into view as children
+    fn access_list(&self, id: Id, label: &str, items: &[&str], selected: usize, visible: Range<usize>) {
+        self.with_access_group(id, || AccessNode::new(Role::List, label), |_| {
+            for index in visible {
+                let item_id = self.state.layout.id_index(label, index as u32 + 1);
+                self.access_node(item_id, || AccessNode::new(Role::ListItem, items[index]).selected(index == selected));
+            }
+        });
+    }
+
+    /// Non-interactive bar showing `fraction` (0.0 - 1.0) of a task as done
+    #[rustfmt::skip]
+    pub fn progress_bar(&self, label: &str, fraction: f32) {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let fraction = fraction.clamp(0.0, 1.0);
+        let track_height = self.scaled(TRACK_HEIGHT);
+        let percent_text = self.frame_str(&format!("{}%", (fraction * 100.0).round()));
+
+        self.widget_row(id, label, false, |_| {
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fixed!(theme.size(Size::ControlWidth)))
+                    .height(fixed!(track_height * 2.0))
+                .end()
+                .corner_radius().all(track_height).end()
+                .background_color(theme.color(ThemeColor::Control)), |_| {
+                    state.layout.with(Declaration::new()
+                        .layout().width(fixed!(theme.size(Size::ControlWidth) * fraction)).height(grow!()).end()
+                        .corner_radius().all(track_height).end()
+                        .background_color(theme.color(ThemeColor::Accent)), |_| {});
+                });
+
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fixed!(self.scaled(72.0)))
+                    .child_alignment(Alignment::new(LayoutAlignmentX::Right, LayoutAlignmentY::Center))
+                .end(), |_| {
+                    self.widget_text(percent_text, theme.color(ThemeColor::TextDim));
+                });
+        });
+
+        self.access_node(id, || AccessNode::new(Role::ProgressBar, label).value(percent_text));
     }
 
-    #[inline]
-    pub fn is_hovering(&self) -> bool {
-        self.flags.contains(SignalFlags::HOVERING)
+    /// Single line text input. While focused it takes the text input and editing keys, with
+    /// shift extending the selection and ctrl+a selecting everything. Left/right move the
+    /// caret instead of the focus.
+    #[rustfmt::skip]
+    pub fn text_field(&self, label: &str, text: &mut String) -> Signal {
+        let state = &self.state;
+        let theme = self.theme();
+        let id = state.layout.id(label);
+        let focused = self.is_focused(id);
+        let mut widget = WidgetState::get(&state.widget_states, id, state.current_frame.get());
+        let mut changed = false;
+
+        if focused {
+            for command in edit_commands(&state.input.borrow()) {
+                changed |= apply_edit(text, &mut widget.cursor, command);
+            }
+        }
+
+        widget.cursor.caret = widget.cursor.caret.min(text.len());
+        widget.cursor.anchor = widget.cursor.anchor.min(text.len());
+
+        let cursor = widget.cursor;
+        drop(widget);
+        let (start, end) = cursor.selection();
+        let before = self.frame_str(&text[..start]);
+        let selection = self.frame_str(&text[start..end]);
+        let after = self.frame_str(&text[end..]);
+
+        let mut signal = self.widget_row(id, label, true, |_| {
+            state.layout.with(Declaration::new()
+                .layout()
+                    .width(fit!(theme.size(Size::ControlWidth)))
+                    .height(fixed!(theme.size(Size::RowHeight) - self.scaled(12.0)))
+                    .padding(Padding::horizontal(theme.spacing(Spacing::Small)))
+                    .child_alignment(Alignment::new(LayoutAlignmentX::Left, LayoutAlignmentY::Center))
+                .end()
+                .corner_radius().all(theme.radius(Radius::Small)).end()
+                .border().all_directions(if focused { 2 } else { 0 }).color(theme.color(ThemeColor::Accent)).end()
+                .background_color(theme.color(ThemeColor::Control)), |_| {
+                    self.widget_text(before, theme.color(ThemeColor::Text));
+
+                    if !focused {
+                        self.widget_text(selection, theme.color(ThemeColor::Text));
+                    } else if cursor.has_selection() {
+                        state.layout.with(Declaration::new().background_color(theme.color(ThemeColor::Accent)), |_| {
+                            self.widget_text(selection, theme.color(ThemeColor::Text));
+                        });
+                    } else {
+                        state.layout.with(Declaration::new()
+                            .layout().width(fixed!(self.scaled(CARET_WIDTH))).height(fixed!(theme.font_size(FontSize::Body) as f32)).end()
+                            .background_color(theme.color(ThemeColor::Text)), |_| {});
+                    }
+
+                    self.widget_text(after, theme.color(ThemeColor::Text));
+                });
+        });
+
+        self.capture_navigation(id, true, false);
+
+        if changed {
+            signal.flags.insert(SignalFlags::CHANGED);
+        }
+
+        self.access_node(id, || AccessNode::new(Role::TextInput, label).value(text.as_str()));
+        signal
     }
 }
 
-impl Ui<'_> {
-    /*
-    pub fn button(&mut self, text: &str) {
-        //let box = build_box_from_string(
-        let b = BoxFlags::DRAW_BACKGROUND | BoxFlags::DRAW_BORDER | BoxFlags::DRAW_TEXT;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(text: &str, cursor: (usize, usize), commands: &[EditCommand]) -> (String, (usize, usize)) {
+        let mut text = text.to_string();
+        let mut cursor = TextCursor {
+            caret: cursor.0,
+            anchor: cursor.1,
+        };
+
+        for command in commands {
+            apply_edit(&mut text, &mut cursor, *command);
+        }
+
+        (text, (cursor.caret, cursor.anchor))
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        use EditCommand::*;
+
+        assert_eq!(edit("ac", (1, 1), &[Insert('b')]), ("abc".to_string(), (2, 2)));
+        assert_eq!(edit("abc", (3, 3), &[Backspace, Backspace]), ("a".to_string(), (1, 1)));
+        assert_eq!(edit("abc", (0, 0), &[Delete]), ("bc".to_string(), (0, 0)));
+        assert_eq!(edit("abc", (0, 0), &[Backspace]), ("abc".to_string(), (0, 0)));
+        // Multi-byte characters are removed as a whole
+        assert_eq!(edit("aå", (3, 3), &[Backspace]), ("a".to_string(), (1, 1)));
+    }
+
+    #[test]
+    fn test_selection() {
+        use EditCommand::*;
+
+        let select = [Home { select: false }, Right { select: true }, Right { select: true }];
+        assert_eq!(edit("abcd", (4, 4), &select), ("abcd".to_string(), (2, 0)));
+
+        // Typing replaces the selection
+        let (text, cursor) = edit("abcd", (4, 4), &[SelectAll, Insert('x')]);
+        assert_eq!((text.as_str(), cursor), ("x", (1, 1)));
+
+        // Moving without shift collapses the selection to its edge
+        assert_eq!(edit("abcd", (3, 1), &[Left { select: false }]), ("abcd".to_string(), (1, 1)));
+        assert_eq!(edit("abcd", (1, 3), &[Backspace]), ("ad".to_string(), (1, 1)));
+    }
+
+    #[test]
+    fn test_snap_to_step() {
+        assert!((snap_to_step(0.26, &(0.0..=1.0), 0.1) - 0.3).abs() < 1e-6);
+        assert_eq!(snap_to_step(12.0, &(0.0..=10.0), 1.0), 10.0);
+        assert_eq!(step_decimals(0.1), 1);
+        assert_eq!(step_decimals(0.25), 2);
+        assert_eq!(step_decimals(5.0), 0);
     }
-    */
 }
@@ -0,0 +1,241 @@
+use crate::TileInfo;
+use flowi_core::primitives::Color16;
+use simd::*;
+
+struct Clip {
+    /// Intersection with the parent clip in tile local coordinates
+    rect: f32x4,
+    /// Unclipped rect in screen coordinates, used for the rounded corners
+    bounds: [f32; 4],
+    corners: [f32; 4],
+    /// Index into the saved tile buffers if a rounded corner is inside the tile
+    saved: Option<usize>,
+}
+
+/// Stack of the active scissors for the tile being rendered. The raster functions clip
+/// against a single rect so the top of the stack is the intersection of all active
+/// scissors. Rounded corners are applied when a scissor ends by restoring the pixels
+/// outside of the corners from a copy of the tile taken when it started.
+pub(crate) struct ClipStack {
+    clips: Vec<Clip>,
+    saved: Vec<Vec<Color16>>,
+    saved_count: usize,
+    tile_rect: f32x4,
+}
+
+impl ClipStack {
+    pub(crate) fn new() -> Self {
+        Self {
+            clips: Vec::new(),
+            saved: Vec::new(),
+            saved_count: 0,
+            tile_rect: f32x4::new_splat(0.0),
+        }
+    }
+
+    pub(crate) fn begin_tile(&mut self, tile_info: &TileInfo) {
+        self.clips.clear();
+        self.saved_count = 0;
+        self.tile_rect = f32x4::new(0.0, 0.0, tile_info.width as _, tile_info._height as _);
+    }
+
+    /// The rect to clip against in tile local coordinates
+    pub(crate) fn current(&self) -> f32x4 {
+        self.clips.last().map_or(self.tile_rect, |clip| clip.rect)
+    }
+
+    /// True if nothing can be drawn inside the current clip rect
+    pub(crate) fn is_empty(&self) -> bool {
+        let rect = self.current();
+        rect.extract::<2>() <= rect.extract::<0>() || rect.extract::<3>() <= rect.extract::<1>()
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        tile_buffer: &[Color16],
+        tile_info: &TileInfo,
+        bounds: &[f32; 4],
+        corners: &[f32; 4],
+    ) {
+        // Same pixel snapping as the raster functions
+        let local = ((f32x4::load_unaligned(bounds) - tile_info.offsets) + f32x4::new_splat(0.5)).floor();
+        let rect = intersect(self.current(), local);
+
+        let has_corners = corner_squares(bounds, corners)
+            .iter()
+            .any(|corner| corner.radius > 0.0 && f32x4::test_intersect(local_rect(&corner.rect, tile_info), rect));
+
+        let saved = if has_corners && !self.is_empty() {
+            let len = (tile_info.width * tile_info._height) as usize;
+
+            if self.saved.len() == self.saved_count {
+                self.saved.push(Vec::new());
+            }
+
+            let buffer = &mut self.saved[self.saved_count];
+            buffer.clear();
+            buffer.extend_from_slice(&tile_buffer[..len]);
+            self.saved_count += 1;
+
+            Some(self.saved_count - 1)
+        } else {
+            None
+        };
+
+        self.clips.push(Clip {
+            rect,
+            bounds: *bounds,
+            corners: *corners,
+            saved,
+        });
+    }
+
+    pub(crate) fn pop(&mut self, tile_buffer: &mut [Color16], tile_info: &TileInfo) {
+        let Some(clip) = self.clips.pop() else {
+            return;
+        };
+
+        let Some(saved) = clip.saved else {
+            return;
+        };
+
+        let saved_buffer = &self.saved[saved];
+        let tile_width = tile_info.width as usize;
+        let offset_x = tile_info.offsets.extract::<0>();
+        let offset_y = tile_info.offsets.extract::<1>();
+
+        for corner in corner_squares(&clip.bounds, &clip.corners) {
+            if corner.radius <= 0.0 {
+                continue;
+            }
+
+            let rect = intersect(clip.rect, local_rect(&corner.rect, tile_info));
+            let x0 = rect.extract::<0>().max(0.0) as usize;
+            let y0 = rect.extract::<1>().max(0.0) as usize;
+            let x1 = rect.extract::<2>().max(0.0) as usize;
+            let y1 = rect.extract::<3>().max(0.0) as usize;
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let dx = x as f32 + offset_x + 0.5 - corner.center.0;
+                    let dy = y as f32 + offset_y + 0.5 - corner.center.1;
+                    let coverage = (corner.radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+
+                    if coverage < 1.0 {
+                        let index = y * tile_width + x;
+                        tile_buffer[index] = lerp_color(saved_buffer[index], tile_buffer[index], coverage);
+                    }
+                }
+            }
+        }
+
+        self.saved_count = saved;
+    }
+}
+
+struct CornerSquare {
+    rect: [f32; 4],
+    center: (f32, f32),
+    radius: f32,
+}
+
+/// Squares covering the rounded corners in screen coordinates. `corners` is ordered top left,
+/// top right, bottom left and bottom right.
+fn corner_squares(bounds: &[f32; 4], corners: &[f32; 4]) -> [CornerSquare; 4] {
+    let [x0, y0, x1, y1] = *bounds;
+    let max_radius = ((x1 - x0) * 0.5).min((y1 - y0) * 0.5).max(0.0);
+    let r = corners.map(|radius| radius.min(max_radius));
+
+    [
+        CornerSquare { rect: [x0, y0, x0 + r[0], y0 + r[0]], center: (x0 + r[0], y0 + r[0]), radius: r[0] },
+        CornerSquare { rect: [x1 - r[1], y0, x1, y0 + r[1]], center: (x1 - r[1], y0 + r[1]), radius: r[1] },
+        CornerSquare { rect: [x0, y1 - r[2], x0 + r[2], y1], center: (x0 + r[2], y1 - r[2]), radius: r[2] },
+        CornerSquare { rect: [x1 - r[3], y1 - r[3], x1, y1], center: (x1 - r[3], y1 - r[3]), radius: r[3] },
+    ]
+}
+
+fn local_rect(rect: &[f32; 4], tile_info: &TileInfo) -> f32x4 {
+    (f32x4::load_unaligned(rect) - tile_info.offsets).floor()
+}
+
+fn intersect(a: f32x4, b: f32x4) -> f32x4 {
+    f32x4::new(
+        a.extract::<0>().max(b.extract::<0>()),
+        a.extract::<1>().max(b.extract::<1>()),
+        a.extract::<2>().min(b.extract::<2>()),
+        a.extract::<3>().min(b.extract::<3>()),
+    )
+}
+
+fn lerp_color(a: Color16, b: Color16, t: f32) -> Color16 {
+    let lerp = |a: i16, b: i16| (a as f32 + (b as f32 - a as f32) * t) as i16;
+
+    Color16 {
+        r: lerp(a.r, b.r),
+        g: lerp(a.g, b.g),
+        b: lerp(a.b, b.b),
+        a: lerp(a.a, b.a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_info() -> TileInfo {
+        TileInfo {
+            offsets: f32x4::new(128.0, 0.0, 128.0, 0.0),
+            width: 128,
+            _height: 128,
+        }
+    }
+
+    fn rect(r: f32x4) -> [f32; 4] {
+        [r.extract::<0>(), r.extract::<1>(), r.extract::<2>(), r.extract::<3>()]
+    }
+
+    #[test]
+    fn test_nested_clips_intersect() {
+        let tile_info = tile_info();
+        let buffer = vec![Color16::default(); 128 * 128];
+        let mut clips = ClipStack::new();
+        clips.begin_tile(&tile_info);
+
+        clips.push(&buffer, &tile_info, &[100.0, 10.0, 200.0, 300.0], &[0.0; 4]);
+        assert_eq!(rect(clips.current()), [0.0, 10.0, 72.0, 128.0]);
+
+        clips.push(&buffer, &tile_info, &[150.0, 50.0, 400.0, 60.0], &[0.0; 4]);
+        assert_eq!(rect(clips.current()), [22.0, 50.0, 72.0, 60.0]);
+
+        // Outside of the parent clip
+        clips.push(&buffer, &tile_info, &[300.0, 0.0, 400.0, 100.0], &[0.0; 4]);
+        assert!(clips.is_empty());
+
+        clips.pop(&mut buffer.clone(), &tile_info);
+        clips.pop(&mut buffer.clone(), &tile_info);
+        assert_eq!(rect(clips.current()), [0.0, 10.0, 72.0, 128.0]);
+
+        // Unbalanced ends are ignored
+        clips.pop(&mut buffer.clone(), &tile_info);
+        clips.pop(&mut buffer.clone(), &tile_info);
+        assert_eq!(rect(clips.current()), [0.0, 0.0, 128.0, 128.0]);
+    }
+
+    #[test]
+    fn test_rounded_clip_restores_corners() {
+        let tile_info = tile_info();
+        let mut buffer = vec![Color16::new_splat(100); 128 * 128];
+        let mut clips = ClipStack::new();
+        clips.begin_tile(&tile_info);
+
+        clips.push(&buffer, &tile_info, &[128.0, 0.0, 192.0, 64.0], &[16.0, 0.0, 0.0, 0.0]);
+        buffer.fill(Color16::new_splat(200));
+        clips.pop(&mut buffer, &tile_info);
+
+        // Outside the rounded top left corner, on its edge and inside the clip
+        assert_eq!(buffer[0].r, 100);
+        assert!(buffer[4 * 128 + 4].r > 100 && buffer[4 * 128 + 4].r < 200);
+        assert_eq!(buffer[63].r, 200);
+        assert_eq!(buffer[32 * 128 + 32].r, 200);
+    }
+}
@@ -0,0 +1,385 @@
+use crate::TileInfo;
+use flowi_core::primitives::Color16;
+use flowi_core::render_api::{GradientKind, ShadowData};
+use simd::*;
+
+/// Largest backdrop blur radius supported. Tiles are rendered with a margin of the blur
+/// radius so the blur can read pixels from the neighbouring tiles.
+pub const MAX_BLUR_RADIUS: f32 = 32.0;
+
+/// Pixels covered by `coords` in tile local coordinates, clipped to the scissor rect.
+/// Uses the same pixel snapping as the raster functions.
+fn pixel_range(tile_info: &TileInfo, scissor_rect: f32x4, coords: &[f32; 4]) -> Option<[usize; 4]> {
+    let local = ((f32x4::load_unaligned(coords) - tile_info.offsets) + f32x4::new_splat(0.5)).floor();
+
+    let x0 = local.extract::<0>().max(scissor_rect.extract::<0>()).max(0.0);
+    let y0 = local.extract::<1>().max(scissor_rect.extract::<1>()).max(0.0);
+    let x1 = local.extract::<2>().min(scissor_rect.extract::<2>()).min(tile_info.width as f32);
+    let y1 = local.extract::<3>().min(scissor_rect.extract::<3>()).min(tile_info._height as f32);
+
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+
+    Some([x0 as usize, y0 as usize, x1 as usize, y1 as usize])
+}
+
+/// Signed distance from a point to a rect with rounded corners, negative inside. `corners`
+/// is ordered top left, top right, bottom left and bottom right.
+pub(crate) fn rounded_rect_distance(x: f32, y: f32, rect: &[f32; 4], corners: &[f32; 4]) -> f32 {
+    let half_w = (rect[2] - rect[0]) * 0.5;
+    let half_h = (rect[3] - rect[1]) * 0.5;
+    let px = x - (rect[0] + half_w);
+    let py = y - (rect[1] + half_h);
+
+    let radius = match (px < 0.0, py < 0.0) {
+        (true, true) => corners[0],
+        (false, true) => corners[1],
+        (true, false) => corners[2],
+        (false, false) => corners[3],
+    };
+
+    let radius = radius.min(half_w).min(half_h).max(0.0);
+    let qx = px.abs() - half_w + radius;
+    let qy = py.abs() - half_h + radius;
+
+    let outside = (qx.max(0.0) * qx.max(0.0) + qy.max(0.0) * qy.max(0.0)).sqrt();
+    outside + qx.max(qy).min(0.0) - radius
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn to_floats(color: i16x8) -> [f32; 4] {
+    [
+        color.extract::<0>() as f32,
+        color.extract::<1>() as f32,
+        color.extract::<2>() as f32,
+        color.extract::<3>() as f32,
+    ]
+}
+
+/// Blends a straight alpha color over the pixel. `coverage` scales the alpha of the color.
+#[inline(always)]
+fn blend_pixel(pixel: &mut Color16, color: &[f32; 4], coverage: f32) {
+    let t = (color[3] / 32767.0) * coverage;
+
+    if t <= 0.0 {
+        return;
+    }
+
+    let blend = |dest: i16, src: f32| (dest as f32 + (src - dest as f32) * t) as i16;
+
+    pixel.r = blend(pixel.r, color[0]);
+    pixel.g = blend(pixel.g, color[1]);
+    pixel.b = blend(pixel.b, color[2]);
+}
+
+/// Position along the gradient for a pixel center, 0 at the start color and 1 at the end
+fn gradient_position(kind: &GradientKind, coords: &[f32; 4], x: f32, y: f32) -> f32 {
+    match *kind {
+        GradientKind::Linear { direction } => {
+            // The gradient spans the rect along the direction, starting at the corner
+            // furthest against it
+            let half_w = (coords[2] - coords[0]) * 0.5;
+            let half_h = (coords[3] - coords[1]) * 0.5;
+            let half_len = (half_w * direction[0]).abs() + (half_h * direction[1]).abs();
+
+            if half_len <= 0.0 {
+                return 0.0;
+            }
+
+            let dx = x - (coords[0] + half_w);
+            let dy = y - (coords[1] + half_h);
+            (dx * direction[0] + dy * direction[1]) / (half_len * 2.0) + 0.5
+        }
+
+        GradientKind::Radial { center, radius } => {
+            if radius <= 0.0 {
+                return 1.0;
+            }
+
+            let dx = x - center[0];
+            let dy = y - center[1];
+            (dx * dx + dy * dy).sqrt() / radius
+        }
+    }
+}
+
+/// Renders a linear or radial gradient with optional rounded corners. Slower than
+/// `Raster::render_gradient_quad` which only handles vertical gradients without corners.
+#[allow(clippy::too_many_arguments)]
+pub fn render_gradient(
+    output: &mut [Color16],
+    tile_info: &TileInfo,
+    scissor_rect: f32x4,
+    coords: &[f32; 4],
+    start_color: i16x8,
+    end_color: i16x8,
+    kind: &GradientKind,
+    corners: &[f32; 4],
+) {
+    let Some([x0, y0, x1, y1]) = pixel_range(tile_info, scissor_rect, coords) else {
+        return;
+    };
+
+    let start = to_floats(start_color);
+    let end = to_floats(end_color);
+    let rounded = corners.iter().any(|&radius| radius > 0.0);
+    let tile_width = tile_info.width as usize;
+    let offset_x = tile_info.offsets.extract::<0>();
+    let offset_y = tile_info.offsets.extract::<1>();
+
+    for y in y0..y1 {
+        let py = y as f32 + offset_y + 0.5;
+
+        for x in x0..x1 {
+            let px = x as f32 + offset_x + 0.5;
+
+            let coverage = if rounded {
+                (0.5 - rounded_rect_distance(px, py, coords, corners)).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+
+            let t = gradient_position(kind, coords, px, py).clamp(0.0, 1.0);
+            let color = [0, 1, 2, 3].map(|i| start[i] + (end[i] - start[i]) * t);
+
+            blend_pixel(&mut output[y * tile_width + x], &color, coverage);
+        }
+    }
+}
+
+/// Renders a soft shadow. `coords` includes the blur so the shape of the shadow is `coords`
+/// inset by the blur distance.
+pub fn render_shadow(
+    output: &mut [Color16],
+    tile_info: &TileInfo,
+    scissor_rect: f32x4,
+    coords: &[f32; 4],
+    color: i16x8,
+    shadow: &ShadowData,
+) {
+    let Some([x0, y0, x1, y1]) = pixel_range(tile_info, scissor_rect, coords) else {
+        return;
+    };
+
+    let blur = shadow.blur.max(0.0);
+    let shape = [coords[0] + blur, coords[1] + blur, coords[2] - blur, coords[3] - blur];
+    let color = to_floats(color);
+    let tile_width = tile_info.width as usize;
+    let offset_x = tile_info.offsets.extract::<0>();
+    let offset_y = tile_info.offsets.extract::<1>();
+
+    for y in y0..y1 {
+        let py = y as f32 + offset_y + 0.5;
+
+        for x in x0..x1 {
+            let px = x as f32 + offset_x + 0.5;
+            let distance = rounded_rect_distance(px, py, &shape, &shadow.corners);
+
+            let coverage = if blur > 0.0 {
+                1.0 - smoothstep(-blur, blur, distance)
+            } else {
+                (0.5 - distance).clamp(0.0, 1.0)
+            };
+
+            blend_pixel(&mut output[y * tile_width + x], &color, coverage);
+        }
+    }
+}
+
+/// Box filters the pixels `start..end` of a row or column of `count` pixels with a window of
+/// `2 * radius + 1` pixels and passes them to `write`. The window sum is updated as it slides
+/// so the cost doesn't depend on the radius. Samples outside of `0..count` are clamped to the
+/// edge.
+#[inline(always)]
+fn box_blur_line(
+    pixels: impl Fn(usize) -> Color16,
+    count: usize,
+    start: usize,
+    end: usize,
+    radius: i32,
+    mut write: impl FnMut(usize, Color16),
+) {
+    let sample = |index: i32| pixels(index.clamp(0, count as i32 - 1) as usize);
+    let size = 2 * radius + 1;
+    let mut sum = [0i32; 3];
+
+    for offset in -radius..=radius {
+        let pixel = sample(start as i32 + offset);
+        sum[0] += pixel.r as i32;
+        sum[1] += pixel.g as i32;
+        sum[2] += pixel.b as i32;
+    }
+
+    for index in start..end {
+        write(index, Color16::new((sum[0] / size) as i16, (sum[1] / size) as i16, (sum[2] / size) as i16, 0x7fff));
+
+        let added = sample(index as i32 + radius + 1);
+        let removed = sample(index as i32 - radius);
+        sum[0] += added.r as i32 - removed.r as i32;
+        sum[1] += added.g as i32 - removed.g as i32;
+        sum[2] += added.b as i32 - removed.b as i32;
+    }
+}
+
+/// Blurs what has been rendered below `coords`. Pixels are read up to `radius` outside of the
+/// rect, clamped to the tile, so the tile has to be rendered with a margin of the radius for
+/// the blur to match across tiles. `temp` is scratch space reused between calls.
+#[allow(clippy::too_many_arguments)]
+pub fn backdrop_blur(
+    output: &mut [Color16],
+    temp: &mut Vec<Color16>,
+    tile_info: &TileInfo,
+    scissor_rect: f32x4,
+    coords: &[f32; 4],
+    radius: f32,
+    corners: &[f32; 4],
+) {
+    let radius = radius.min(MAX_BLUR_RADIUS).round() as i32;
+
+    if radius <= 0 {
+        return;
+    }
+
+    let Some([x0, y0, x1, y1]) = pixel_range(tile_info, scissor_rect, coords) else {
+        return;
+    };
+
+    let tile_width = tile_info.width as usize;
+    let tile_height = tile_info._height as usize;
+
+    // The horizontal pass covers the rows the vertical pass reads
+    let ty0 = y0.saturating_sub(radius as usize);
+    let ty1 = (y1 + radius as usize).min(tile_height);
+    let width = x1 - x0;
+
+    temp.clear();
+    temp.reserve(width * (ty1 - ty0));
+
+    for y in ty0..ty1 {
+        let row = &output[y * tile_width..(y + 1) * tile_width];
+        box_blur_line(|i| row[i], tile_width, x0, x1, radius, |_, pixel| temp.push(pixel));
+    }
+
+    let rounded = corners.iter().any(|&r| r > 0.0);
+    let offset_x = tile_info.offsets.extract::<0>();
+    let offset_y = tile_info.offsets.extract::<1>();
+    let rows = ty1 - ty0;
+
+    for x in x0..x1 {
+        let column = x - x0;
+        let px = x as f32 + offset_x + 0.5;
+
+        box_blur_line(|i| temp[i * width + column], rows, y0 - ty0, y1 - ty0, radius, |row, blurred| {
+            let y = row + ty0;
+
+            let coverage = if rounded {
+                let py = y as f32 + offset_y + 0.5;
+                (0.5 - rounded_rect_distance(px, py, coords, corners)).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+
+            let color = [blurred.r as f32, blurred.g as f32, blurred.b as f32, 32767.0];
+            blend_pixel(&mut output[y * tile_width + x], &color, coverage);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_info(width: i32, height: i32) -> TileInfo {
+        TileInfo {
+            offsets: f32x4::new(0.0, 0.0, 0.0, 0.0),
+            width,
+            _height: height,
+        }
+    }
+
+    #[test]
+    fn test_rounded_rect_distance() {
+        let rect = [0.0, 0.0, 100.0, 50.0];
+
+        assert_eq!(rounded_rect_distance(50.0, 25.0, &rect, &[0.0; 4]), -25.0);
+        assert_eq!(rounded_rect_distance(110.0, 25.0, &rect, &[0.0; 4]), 10.0);
+
+        // The corner is cut by the radius
+        let d = rounded_rect_distance(0.0, 0.0, &rect, &[10.0, 0.0, 0.0, 0.0]);
+        assert!((d - (200.0f32.sqrt() - 10.0)).abs() < 1e-4);
+        assert_eq!(rounded_rect_distance(100.0, 0.0, &rect, &[10.0, 0.0, 0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_gradient_directions() {
+        let coords = [0.0, 0.0, 100.0, 50.0];
+        let right = GradientKind::Linear { direction: [1.0, 0.0] };
+        let up = GradientKind::Linear { direction: [0.0, -1.0] };
+
+        assert_eq!(gradient_position(&right, &coords, 0.0, 10.0), 0.0);
+        assert_eq!(gradient_position(&right, &coords, 100.0, 10.0), 1.0);
+        assert_eq!(gradient_position(&up, &coords, 20.0, 50.0), 0.0);
+
+        let radial = GradientKind::Radial { center: [50.0, 25.0], radius: 20.0 };
+        assert_eq!(gradient_position(&radial, &coords, 50.0, 35.0), 0.5);
+    }
+
+    #[test]
+    fn test_box_blur_line_matches_window_average() {
+        let pixels: Vec<Color16> = [0, 300, 900, 90, 3000, 60].iter().map(|&v| Color16::new_splat(v)).collect();
+        let radius = 2;
+        let mut blurred = Vec::new();
+
+        box_blur_line(|i| pixels[i], pixels.len(), 1, 5, radius, |i, pixel| blurred.push((i, pixel.r)));
+
+        for (index, value) in blurred {
+            let sum: i32 = (-radius..=radius)
+                .map(|offset| pixels[(index as i32 + offset).clamp(0, 5) as usize].r as i32)
+                .sum();
+            assert_eq!(value as i32, sum / (2 * radius + 1));
+        }
+    }
+
+    #[test]
+    fn test_blur_keeps_flat_color_and_smooths_edges() {
+        let tile_info = tile_info(16, 16);
+        let scissor = f32x4::new(0.0, 0.0, 16.0, 16.0);
+        let mut temp = Vec::new();
+
+        // Left half dark, right half bright
+        let mut buffer: Vec<Color16> = (0..16 * 16)
+            .map(|i| Color16::new_splat(if i % 16 < 8 { 0 } else { 16000 }))
+            .collect();
+
+        backdrop_blur(&mut buffer, &mut temp, &tile_info, scissor, &[0.0, 0.0, 16.0, 16.0], 3.0, &[0.0; 4]);
+
+        assert_eq!(buffer[8 * 16].r, 0);
+        assert_eq!(buffer[8 * 16 + 15].r, 16000);
+        assert!(buffer[8 * 16 + 7].r > 0 && buffer[8 * 16 + 8].r < 16000);
+        // Vertical neighbours are equal so each column stays flat
+        assert_eq!(buffer[8 * 16 + 7].r, buffer[2 * 16 + 7].r);
+    }
+
+    #[test]
+    fn test_shadow_fades_out() {
+        let tile_info = tile_info(64, 64);
+        let scissor = f32x4::new(0.0, 0.0, 64.0, 64.0);
+        let mut buffer = vec![Color16::new_splat(0); 64 * 64];
+        let color = i16x8::new(16000, 16000, 16000, 0x7f80, 16000, 16000, 16000, 0x7f80);
+        let shadow = ShadowData { corners: [0.0; 4], blur: 8.0 };
+
+        render_shadow(&mut buffer, &tile_info, scissor, &[0.0, 0.0, 64.0, 64.0], color, &shadow);
+
+        let center = buffer[32 * 64 + 32].r;
+        let edge = buffer[32 * 64 + 8].r;
+        let outside = buffer[32 * 64].r;
+        assert!(center > 15000);
+        assert!(edge > outside && edge < center);
+        assert!(outside < 500);
+    }
+}
@@ -1,6 +1,8 @@
 use simd::*;
 use tracy_client::span;
 
+mod clip;
+pub mod effects;
 pub mod raster;
 pub mod sharp_bilinear;
 
@@ -8,10 +10,11 @@ pub use flowi_core::image::image::ImageInfo;
 pub use flowi_core::primitives::Color16;
 pub use flowi_core::Color;
 
+use clip::ClipStack;
 pub use raster::{BlendMode, Corner, Raster};
 use raw_window_handle::RawWindowHandle;
 
-use flowi_core::render_api::{RenderCommand, RenderType, SoftwareRenderData};
+use flowi_core::render_api::{GradientKind, RenderCommand, RenderType, SoftwareRenderData};
 use std::hash::{Hash, Hasher};
 
 pub struct TileInfo {
@@ -31,11 +34,14 @@ const LINEAR_TO_SRGB_SHIFT: i32 = LINEAR_BIT_COUNT - SRGB_BIT_COUNT;
 
 pub struct Renderer {
     raster: Raster,
+    clip_stack: ClipStack,
     linear_to_srgb_table: [u8; 1 << SRGB_BIT_COUNT],
     srgb_to_linear_table: [u16; 1 << 8],
     // TODO: Arena
     tiles: Vec<Tile>,
     tile_buffer: Vec<Color16>,
+    /// Scratch space for the backdrop blur
+    blur_buffer: Vec<Color16>,
     output: Vec<u8>,
     //tile_size: (usize, usize),
     screen_size: (usize, usize),
@@ -85,6 +91,8 @@ pub fn build_linear_to_srgb_table() -> [u8; 1 << SRGB_BIT_COUNT] {
 
 pub struct Tile {
     aabb: f32x4,
+    /// `aabb` grown by the margin needed for backdrop blurs in the tile, clamped to the screen
+    render_aabb: f32x4,
     data: Vec<usize>,
     prev_hash: u64,
     current_hash: u64,
@@ -106,6 +114,7 @@ pub fn copy_tile_linear_to_srgb(
     linear_to_srgb_table: &[u8; 2048],
     output: &mut [u8],
     tile: &[Color16],
+    tile_stride: usize,
     tile_info: &Tile,
     width: usize,
 ) {
@@ -120,11 +129,12 @@ pub fn copy_tile_linear_to_srgb(
     let tile_width = x1 - x0;
     let tile_height = y1 - y0;
 
-    let mut tile_ptr = tile.as_ptr();
+    let mut row_ptr = tile.as_ptr();
     let mut output_index = ((y0 * width) + x0) * 3;
     let and_mask = i16x8::new_splat(0xfff);
 
     for _y in 0..tile_height {
+        let mut tile_ptr = row_ptr;
         let mut current_index = output_index;
         for _x in 0..(tile_width >> 1) {
             let rgba_rgba = i16x8::load_unaligned_ptr(tile_ptr as _, 0);
@@ -161,10 +171,29 @@ pub fn copy_tile_linear_to_srgb(
             current_index += 6;
         }
 
+        row_ptr = unsafe { row_ptr.add(tile_stride) };
         output_index += width * 3;
     }
 }
 
+/// Selects the sub pixel variant of a text buffer closest to the fractional x position.
+/// Returns the whole pixel x position to draw at and the variant to use.
+fn sub_pixel_phase(x: f32, sub_pixel_steps: usize) -> (f32, usize) {
+    if sub_pixel_steps <= 1 {
+        return (x, 0);
+    }
+
+    let x_floor = x.floor();
+    let phase = ((x - x_floor) * sub_pixel_steps as f32).round() as usize;
+
+    // Rounded up to the next whole pixel
+    if phase == sub_pixel_steps {
+        (x_floor + 1.0, 0)
+    } else {
+        (x_floor, phase)
+    }
+}
+
 #[inline(never)]
 fn clear_tile_buffer(tile_buffer: &mut [Color16]) {
     let clear_tile = span!("clear tile");
@@ -201,18 +230,22 @@ fn render_tiles(renderer: &mut Renderer, commands: &[RenderCommand]) {
 
          */
 
-        let tile_width = tile_aabb.extract::<2>() - tile_aabb.extract::<0>();
-        let tile_height = tile_aabb.extract::<3>() - tile_aabb.extract::<1>();
+        // Rendered with a margin around the tile when it has backdrop blurs
+        let render_aabb = tile.render_aabb;
+        let tile_width = render_aabb.extract::<2>() - render_aabb.extract::<0>();
+        let tile_height = render_aabb.extract::<3>() - render_aabb.extract::<1>();
 
         let tile_info = TileInfo {
-            offsets: tile_aabb.shuffle_0101(),
+            offsets: render_aabb.shuffle_0101(),
             width: tile_width as _,
             _height: tile_height as _,
         };
 
-        renderer.raster.scissor_rect = f32x4::new(0.0, 0.0, tile_width as _, tile_height as _);
+        renderer.clip_stack.begin_tile(&tile_info);
+        renderer.raster.scissor_rect = renderer.clip_stack.current();
 
         let tile_buffer = &mut renderer.tile_buffer;
+        assert!(tile_width as usize * tile_height as usize <= tile_buffer.len());
 
         clear_tile_buffer(tile_buffer);
 
@@ -221,6 +254,32 @@ fn render_tiles(renderer: &mut Renderer, commands: &[RenderCommand]) {
             command.emit_color(0x00FF00);
 
             let render_cmd = &commands[*index];
+
+            match &render_cmd.render_type {
+                RenderType::ScissorStart(scissor) => {
+                    renderer.clip_stack.push(
+                        tile_buffer,
+                        &tile_info,
+                        &render_cmd.bounding_box,
+                        &scissor.corners,
+                    );
+                    renderer.raster.scissor_rect = renderer.clip_stack.current();
+                    continue;
+                }
+
+                RenderType::ScissorEnd => {
+                    renderer.clip_stack.pop(tile_buffer, &tile_info);
+                    renderer.raster.scissor_rect = renderer.clip_stack.current();
+                    continue;
+                }
+
+                _ => {
+                    if renderer.clip_stack.is_empty() {
+                        continue;
+                    }
+                }
+            }
+
             let blend_mode = if render_cmd.color.a == 255.0 {
                 BlendMode::None
             } else {
@@ -264,16 +323,46 @@ fn render_tiles(renderer: &mut Renderer, commands: &[RenderCommand]) {
                         continue;
                     }
 
-                    let coords = [
+                    let (x, phase) = sub_pixel_phase(
                         render_cmd.bounding_box[0],
+                        buffer.sub_pixel_steps_x as usize,
+                    );
+
+                    let coords = [
+                        x,
                         render_cmd.bounding_box[1],
-                        render_cmd.bounding_box[0] + buffer.width as f32,
+                        x + buffer.width as f32,
                         render_cmd.bounding_box[1] + buffer.height as f32,
                     ];
 
+                    let phase_size = buffer.width as usize * buffer.height as usize;
+
+                    // Shadow, glow and outline are drawn below the text in order
+                    for effect in buffer.effects.iter() {
+                        if effect.data.0.is_null() {
+                            continue;
+                        }
+
+                        let effect_color = get_color_from_floats_0_255(
+                            effect.color,
+                            &renderer.srgb_to_linear_table,
+                        );
+
+                        renderer.raster.render_text_texture(
+                            tile_buffer,
+                            unsafe { (effect.data.0 as *const i16).add(phase * phase_size) },
+                            &tile_info,
+                            buffer.width as _,
+                            &coords,
+                            effect_color,
+                        );
+                    }
+
+                    let text_data = unsafe { (buffer.data.0 as *const i16).add(phase * phase_size) };
+
                     renderer.raster.render_text_texture(
                         tile_buffer,
-                        buffer.data.0 as _,
+                        text_data,
                         &tile_info,
                         buffer.width as _,
                         &coords,
@@ -296,6 +385,19 @@ fn render_tiles(renderer: &mut Renderer, commands: &[RenderCommand]) {
 
                     //let uv = [0.0, 0.0, buffer.width as _, buffer.height as _];
 
+                    // Rounded corners are drawn as a clip around the image
+                    let rounded = buffer.rounded_corners.iter().any(|&radius| radius > 0.0);
+
+                    if rounded {
+                        renderer.clip_stack.push(
+                            tile_buffer,
+                            &tile_info,
+                            &render_cmd.bounding_box,
+                            &buffer.rounded_corners,
+                        );
+                        renderer.raster.scissor_rect = renderer.clip_stack.current();
+                    }
+
                     sharp_bilinear::render_sharp_bilinear(
                         tile_buffer,
                         renderer.raster.scissor_rect,
@@ -308,6 +410,11 @@ fn render_tiles(renderer: &mut Renderer, commands: &[RenderCommand]) {
                         buffer.stride as _,
                         &texture_sizes);
 
+                    if rounded {
+                        renderer.clip_stack.pop(tile_buffer, &tile_info);
+                        renderer.raster.scissor_rect = renderer.clip_stack.current();
+                    }
+
                     //dbg!("DrawImage {:?}", render_cmd.bounding_box);
                     //dbg!("DrawImage {:?}", buffer.handle);
 
@@ -328,24 +435,98 @@ fn render_tiles(renderer: &mut Renderer, commands: &[RenderCommand]) {
                     let zone = span!("DrawBackground");
                     zone.emit_color(0xFF00FF);
 
+                    // The handle points to the loaded image which is kept alive by the io handler
                     renderer.raster.draw_background(
                         tile_buffer,
                         &tile_info,
                         &render_cmd.bounding_box,
-                        buffer.width as _,
+                        buffer.stride as _,
                         buffer.handle as _,
                     );
                 }
 
+                RenderType::DrawGradient(gradient) => {
+                    let zone = span!("DrawGradient");
+                    zone.emit_color(0xFF00FF);
+
+                    let end_color =
+                        get_color_from_floats_0_255(gradient.end_color, &renderer.srgb_to_linear_table);
+
+                    let is_vertical = gradient.kind == GradientKind::Linear { direction: [0.0, 1.0] };
+
+                    if is_vertical && gradient.corners.iter().all(|&radius| radius == 0.0) {
+                        let blend_mode = if gradient.end_color.a == 255.0 {
+                            blend_mode
+                        } else {
+                            BlendMode::WithBackground
+                        };
+
+                        renderer.raster.render_gradient_quad(
+                            tile_buffer,
+                            &tile_info,
+                            &render_cmd.bounding_box,
+                            color,
+                            end_color,
+                            blend_mode,
+                        );
+                    } else {
+                        effects::render_gradient(
+                            tile_buffer,
+                            &tile_info,
+                            renderer.raster.scissor_rect,
+                            &render_cmd.bounding_box,
+                            color,
+                            end_color,
+                            &gradient.kind,
+                            &gradient.corners,
+                        );
+                    }
+                }
+
+                RenderType::DrawShadow(shadow) => {
+                    let zone = span!("DrawShadow");
+                    zone.emit_color(0xFF00FF);
+
+                    effects::render_shadow(
+                        tile_buffer,
+                        &tile_info,
+                        renderer.raster.scissor_rect,
+                        &render_cmd.bounding_box,
+                        color,
+                        shadow,
+                    );
+                }
+
+                RenderType::BackdropBlur(blur) => {
+                    let zone = span!("BackdropBlur");
+                    zone.emit_color(0xFF00FF);
+
+                    effects::backdrop_blur(
+                        tile_buffer,
+                        &mut renderer.blur_buffer,
+                        &tile_info,
+                        renderer.raster.scissor_rect,
+                        &render_cmd.bounding_box,
+                        blur.radius,
+                        &blur.corners,
+                    );
+                }
+
                 _ => {}
             }
         }
 
+        // Skip the margin when copying the tile to the output
+        let margin_x = (tile_aabb.extract::<0>() - render_aabb.extract::<0>()) as usize;
+        let margin_y = (tile_aabb.extract::<1>() - render_aabb.extract::<1>()) as usize;
+        let tile_stride = tile_width as usize;
+
         // Rasterize the primitives for this tile
         copy_tile_linear_to_srgb(
             &renderer.linear_to_srgb_table,
             &mut renderer.output,
-            tile_buffer,
+            &tile_buffer[margin_y * tile_stride + margin_x..],
+            tile_stride,
             tile,
             renderer.screen_size.0,
         );
@@ -360,38 +541,54 @@ fn get_tile_size(pos: usize, max_size: usize, tile_size: usize) -> usize {
     }
 }
 
-impl flowi_core::Renderer for Renderer {
-    fn new(screen_size: (usize, usize), _window: Option<&RawWindowHandle>) -> Self {
-        let tile_size = (128, 128);
-
-        let mut tiles = Vec::new();
-
-        for y in (0..screen_size.1).step_by(tile_size.1) {
-            for x in (0..screen_size.0).step_by(tile_size.0) {
-                let tile_width = get_tile_size(x, screen_size.0, tile_size.0);
-                let tile_height = get_tile_size(y, screen_size.1, tile_size.1);
-
-                tiles.push(Tile {
-                    aabb: f32x4::new(
-                        x as f32,
-                        y as f32,
-                        (x + tile_width) as f32,
-                        (y + tile_height) as f32,
-                    ),
-                    data: Vec::with_capacity(8192),
-                    prev_hash: 1,
-                    current_hash: 0,
-                });
-            }
+const TILE_SIZE: (usize, usize) = (128, 128);
+
+/// Pixels in the tile buffer, enough for a tile with the largest blur margin on all sides
+const TILE_BUFFER_SIZE: usize = (TILE_SIZE.0 + 2 * effects::MAX_BLUR_RADIUS as usize)
+    * (TILE_SIZE.1 + 2 * effects::MAX_BLUR_RADIUS as usize);
+
+/// Splits the screen into tiles, the tiles on the right and bottom edges may be smaller
+fn build_tiles(screen_size: (usize, usize)) -> Vec<Tile> {
+    let tile_size = TILE_SIZE;
+    let mut tiles = Vec::new();
+
+    for y in (0..screen_size.1).step_by(tile_size.1) {
+        for x in (0..screen_size.0).step_by(tile_size.0) {
+            let tile_width = get_tile_size(x, screen_size.0, tile_size.0);
+            let tile_height = get_tile_size(y, screen_size.1, tile_size.1);
+
+            let aabb = f32x4::new(
+                x as f32,
+                y as f32,
+                (x + tile_width) as f32,
+                (y + tile_height) as f32,
+            );
+
+            tiles.push(Tile {
+                aabb,
+                render_aabb: aabb,
+                data: Vec::with_capacity(8192),
+                prev_hash: 1,
+                current_hash: 0,
+            });
         }
+    }
+
+    tiles
+}
 
-        let tile_buffer = vec![Color16::default(); tile_size.0 * tile_size.1 * 8];
+impl flowi_core::Renderer for Renderer {
+    fn new(screen_size: (usize, usize), _window: Option<&RawWindowHandle>) -> Self {
+        let tiles = build_tiles(screen_size);
+        let tile_buffer = vec![Color16::default(); TILE_BUFFER_SIZE];
 
         Self {
             linear_to_srgb_table: build_linear_to_srgb_table(),
             srgb_to_linear_table: build_srgb_to_linear_table(),
             raster: Raster::new(),
+            clip_stack: ClipStack::new(),
             tile_buffer,
+            blur_buffer: Vec::new(),
             tiles,
             screen_size,
             output: vec![0; screen_size.0 * screen_size.1 * 3],
@@ -401,13 +598,28 @@ impl flowi_core::Renderer for Renderer {
     fn software_renderer_info(&self) -> Option<SoftwareRenderData<'_>> {
         Some(SoftwareRenderData {
             buffer: self.output.as_slice(),
-            width: 1280,
-            height: 720,
+            width: self.screen_size.0 as _,
+            height: self.screen_size.1 as _,
         })
     }
 
+    /// Rebuilds the tiles and the output buffer for the new size. All tiles are rendered
+    /// again on the next frame.
+    fn set_window_size(&mut self, width: u32, height: u32) {
+        let screen_size = (width as usize, height as usize);
+
+        if screen_size == self.screen_size {
+            return;
+        }
+
+        self.tiles = build_tiles(screen_size);
+        self.screen_size = screen_size;
+        self.output.clear();
+        self.output.resize(screen_size.0 * screen_size.1 * 3, 0);
+    }
+
     fn render(&mut self, commands: &[RenderCommand]) {
-        Self::bin_primitives(&mut self.tiles, commands);
+        Self::bin_primitives(&mut self.tiles, commands, self.screen_size);
         Self::hash_all_tiles(&mut self.tiles, commands);
         render_tiles(self, commands);
     }
@@ -420,13 +632,39 @@ impl Renderer {
     ///
     /// This function iterates over the provided render primitives and checks if the
     /// primitive's (AABB) intersects with the tile's AABB. If there is an intersection,
-    /// the index of the primitive is added to the tile's data.
+    /// the index of the primitive is added to the tile's data. Scissor commands are added to
+    /// all tiles so each tile sees a balanced clip stack. When there are backdrop blurs the tiles
+    /// are rendered with a margin of the largest blur radius and get the primitives in the
+    /// margin as well.
     ///
-    fn bin_primitives(tiles: &mut [Tile], commands: &[RenderCommand]) {
+    fn bin_primitives(tiles: &mut [Tile], commands: &[RenderCommand], screen_size: (usize, usize)) {
+        // The margin for the largest blur on screen is used for all tiles
+        let margin = commands
+            .iter()
+            .filter_map(|command| match &command.render_type {
+                RenderType::BackdropBlur(blur) => Some(blur.radius),
+                _ => None,
+            })
+            .fold(0.0f32, f32::max)
+            .min(effects::MAX_BLUR_RADIUS)
+            .round();
+
         for tile in tiles.iter_mut() {
-            let tile_aabb = tile.aabb;
+            tile.render_aabb = f32x4::new(
+                (tile.aabb.extract::<0>() - margin).max(0.0),
+                (tile.aabb.extract::<1>() - margin).max(0.0),
+                (tile.aabb.extract::<2>() + margin).min(screen_size.0 as f32),
+                (tile.aabb.extract::<3>() + margin).min(screen_size.1 as f32),
+            );
+
+            let tile_aabb = tile.render_aabb;
             tile.data.clear();
             for (i, command) in commands.iter().enumerate() {
+                if matches!(command.render_type, RenderType::ScissorStart(_) | RenderType::ScissorEnd) {
+                    tile.data.push(i);
+                    continue;
+                }
+
                 let prim_aabb = f32x4::load_unaligned(&command.bounding_box);
                 if f32x4::test_intersect(tile_aabb, prim_aabb) {
                     tile.data.push(i);
@@ -452,3 +690,24 @@ impl Renderer {
         hasher.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flowi_core::Renderer as _;
+
+    #[test]
+    fn test_set_window_size() {
+        let mut renderer = Renderer::new((256, 128), None);
+        assert_eq!(renderer.tiles.len(), 2);
+
+        renderer.set_window_size(300, 200);
+        assert_eq!(renderer.tiles.len(), 3 * 2);
+        assert_eq!(renderer.tiles[5].aabb.extract::<2>(), 300.0);
+        assert_eq!(renderer.tiles[5].aabb.extract::<3>(), 200.0);
+
+        let info = renderer.software_renderer_info().unwrap();
+        assert_eq!((info.width, info.height), (300, 200));
+        assert_eq!(info.buffer.len(), 300 * 200 * 3);
+    }
+}
@@ -736,6 +736,9 @@ impl Raster {
     }
 
     // TODO: Unify the setup for these functions as they are very similar
+    /// Copies an unscaled image to the tile. `texture_data` has to point to an image with
+    /// `texture_width` pixels per row that covers `coords`.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn draw_background(
         &self,
         output: &mut [Color16],
@@ -760,34 +763,42 @@ impl Raster {
 
         //let texture_width = texture_width + 1;
 
-        let mut text_data = unsafe { texture_data.add((rp.clip_y * texture_width) + rp.clip_x) };
-
         let tile_width = tile_info.width as usize;
         let output = &mut output[(y0 as usize * tile_width + x0 as usize)..];
-        let mut output_ptr = output.as_mut_ptr();
 
-        let mut tile_line_ptr = output_ptr;
-        let mut text_line_ptr = text_data;
+        // The last row ends xlen pixels after its start
+        debug_assert!(
+            ylen <= 0 || (ylen as usize - 1) * tile_width + xlen as usize <= output.len(),
+            "background doesn't fit in the tile buffer"
+        );
 
-        for _y in 0..ylen {
-            for _x in 0..(xlen >> 1) {
-                let pixel_01 = i16x8::load_unaligned_ptr(text_line_ptr as _, 0);
-                pixel_01.store_unaligned_ptr(tile_line_ptr as _);
+        unsafe {
+            let mut text_data = texture_data.add((rp.clip_y * texture_width) + rp.clip_x);
+            let mut output_ptr = output.as_mut_ptr();
 
-                tile_line_ptr = unsafe { tile_line_ptr.add(2) };
-                text_line_ptr = unsafe { text_line_ptr.add(2) };
-            }
+            let mut tile_line_ptr = output_ptr;
+            let mut text_line_ptr = text_data;
 
-            if (xlen & 1) == 1 {
-                let pixel_0 = i16x8::load_unaligned_ptr(text_line_ptr as _, 0);
-                pixel_0.store_unaligned_ptr_lower(tile_line_ptr as _);
-            }
+            for _y in 0..ylen {
+                for _x in 0..(xlen >> 1) {
+                    let pixel_01 = i16x8::load_unaligned_ptr(text_line_ptr as _, 0);
+                    pixel_01.store_unaligned_ptr(tile_line_ptr as _);
 
-            output_ptr = unsafe { output_ptr.add(tile_width) };
-            text_data = unsafe { text_data.add(texture_width) };
+                    tile_line_ptr = tile_line_ptr.add(2);
+                    text_line_ptr = text_line_ptr.add(2);
+                }
 
-            tile_line_ptr = output_ptr;
-            text_line_ptr = text_data;
+                if (xlen & 1) == 1 {
+                    let pixel_0 = i16x8::load_unaligned_ptr(text_line_ptr as _, 0);
+                    pixel_0.store_unaligned_ptr_lower(tile_line_ptr as _);
+                }
+
+                output_ptr = output_ptr.add(tile_width);
+                text_data = text_data.add(texture_width);
+
+                tile_line_ptr = output_ptr;
+                text_line_ptr = text_data;
+            }
         }
     }
 
@@ -7,8 +7,8 @@ use flowi_core::content_selector::ContentSelector;
 /// selectors for many streaming services works. The user can scroll through a list of items and
 /// select one of them. The selected item will be displayed in a larger size than the other items.
 /// THe backend uses the Demozoo API to fetch the metadata along with screenshots from it's db.
-use flowi_core::{Alignment, Declaration, LayoutAlignmentX, LayoutAlignmentY, LayoutDirection, Padding, Ui, fixed, grow, FontStyle};
-use flowi_core::{IoHandle, LoadPriority, LoadState};
+use flowi_core::{Alignment, Declaration, LayoutAlignmentX, LayoutAlignmentY, LayoutDirection, Padding, Ui, fixed, grow, FontStyle, TextEffects, TextSpan};
+use flowi_core::{Date, ImageLoadState, IoHandle, LoadPriority, LoadState};
 use log::error;
 //use log::*;
 use nanoserde::DeJson;
@@ -30,6 +30,10 @@ pub struct OnlineDemoDisplay {
     production_items: HashMap<i32, Item>,
     jobs: Vec<QueuedJob>,
     selected_item: Option<(u64, u64)>,
+    /// Screenshots cycled through while a production is selected, loaded one at a time
+    previews: HashMap<i32, Vec<IoHandle>>,
+    /// Production the previews were last requested for
+    preview_id: Option<i32>,
 }
 
 impl OnlineDemoDisplay {
@@ -41,6 +45,8 @@ impl OnlineDemoDisplay {
             productions_loaded: HashMap::new(),
             production_items: HashMap::new(),
             selected_item: None,
+            previews: HashMap::new(),
+            preview_id: None,
         }
     }
 
@@ -64,20 +70,22 @@ impl OnlineDemoDisplay {
         self.jobs.push(QueuedJob::Party(handle));
     }
 
+    /// Index of the screenshot used as background image
+    fn background_screenshot(entry: &ProductionEntry) -> usize {
+        if entry.screenshots.len() > 1 { 1 } else { 0 }
+    }
+
     /// Queues the screenshots for loading. If there are no screenshots, it will return a pair of
     /// (0, 0) IoHandles.
     /// TODO: We should have a default image here instead of null handles
     fn queue_screenshots(entry: &ProductionEntry, ui: &Ui) -> (IoHandle, IoHandle) {
         if entry.screenshots.is_empty() {
             (IoHandle(0), IoHandle(0))
-        } else if entry.screenshots.len() == 1 {
+        } else {
+            let background = &entry.screenshots[Self::background_screenshot(entry)];
             let handle = ui.load_image(&entry.screenshots[0].thumbnail_url, None);
-            let bgi = ui.load_background_image(&entry.screenshots[0].original_url);
+            let bgi = ui.load_background_image(&background.original_url);
             (handle, bgi)
-        } else {
-            let h0 = ui.load_image(&entry.screenshots[0].thumbnail_url, None);
-            let bgi = ui.load_background_image(&entry.screenshots[1].original_url);
-            (h0, bgi)
         }
     }
 
@@ -116,20 +124,41 @@ impl OnlineDemoDisplay {
     }
 }
 
+impl OnlineDemoDisplay {
+    /// The release at the position, `None` if the party hasn't been loaded or the position is
+    /// outside the competitions
+    fn release(&self, row: u64, col: u64) -> Option<&Release> {
+        let competition = self.parties.first()?.competitions.get(row as usize)?;
+        competition.results.get(col as usize).map(|result| &result.production)
+    }
+
+    /// Drops the previews of productions that aren't in the rows around `row`, which are the
+    /// only ones the selector shows
+    fn prune_previews(&mut self, ui: &Ui, row: u64) {
+        let mut visible = Vec::new();
+
+        for row in row.saturating_sub(1)..row + 3 {
+            for col in 0..self.get_column_count(ui, row) {
+                visible.push(self.get_item_id(row, col) as i32);
+            }
+        }
+
+        self.previews.retain(|id, _| visible.contains(id));
+    }
+}
+
 impl ContentProvider for OnlineDemoDisplay {
     fn get_item_id(&mut self, row: u64, col: u64) -> u64 {
-        if self.parties.is_empty() {
+        let Some(release) = self.release(row, col) else {
             return 0;
-        }
+        };
 
-        let party = &self.parties[0];
-        let release = &party.competitions[row as usize].results[col as usize].production;
         // in order to make the ids unique wi include row / column in the idea
         let id = release.id as u64;
         id ^ ((row as u64) << 16) ^ (row as u64)
     }
     fn get_item(&mut self, ui: &Ui, visibility: ItemVisibility, row: u64, col: u64) -> Item {
-        if self.parties.is_empty() {
+        if self.release(row, col).is_none() {
             return Item {
                 image: IoHandle(0),
                 background_image: IoHandle(0),
@@ -138,8 +167,6 @@ impl ContentProvider for OnlineDemoDisplay {
         }
 
         let id = self.get_item_id(row, col) as i32;
-        let party = &self.parties[0];
-        let release = &party.competitions[row as usize].results[col as usize].production;
 
         // First we check if we have loaded the production entry
         if let Some(entry) = self.production_items.get(&id) {
@@ -169,8 +196,9 @@ impl ContentProvider for OnlineDemoDisplay {
             None => {
                 debug!("Loading production {} with priority {:?}", id, priority);
 
+                let url = &self.release(row, col).unwrap().url;
                 let handle = ui.load_with_callback(
-                    &release.url,
+                    url,
                     priority,
                     Box::new(|data| {
                         let json_data = std::str::from_utf8(data).expect("Failed to parse string");
@@ -194,13 +222,15 @@ impl ContentProvider for OnlineDemoDisplay {
     }
 
     fn get_column_count(&mut self, _ui: &Ui, row: u64) -> u64 {
-        if self.parties.is_empty() {
-            return 0;
-        }
-
         // TODO: Filtering and stuff goes here
-        let party = &self.parties[0];
-        party.competitions[row as usize].results.len() as u64
+        self.parties
+            .first()
+            .and_then(|party| party.competitions.get(row as usize))
+            .map_or(0, |competition| competition.results.len() as u64)
+    }
+
+    fn get_row_count(&mut self, _ui: &Ui) -> u64 {
+        self.parties.first().map_or(0, |party| party.competitions.len() as u64)
     }
 
     fn get_row_name(&mut self, _ui: &Ui, row: u64) -> &str {
@@ -211,6 +241,76 @@ impl ContentProvider for OnlineDemoDisplay {
         let party = &self.parties[0];
         &party.competitions[row as usize].name
     }
+
+    fn get_item_label(&mut self, ui: &Ui, row: u64, col: u64) -> Option<String> {
+        self.release(row, col)?;
+
+        let id = self.get_item_id(row, col) as i32;
+        let entry = self.productions_loaded.get(&id)?;
+
+        match entry.author_nicks.first() {
+            Some(author) => Some(ui.localize_args("entry.title_by", &[
+                ("title", &entry.title),
+                ("author", &author.name),
+            ]).to_string()),
+            None => Some(entry.title.clone()),
+        }
+    }
+
+    fn get_preview_images(&mut self, ui: &Ui, row: u64, col: u64) -> &[IoHandle] {
+        if self.parties.is_empty() {
+            return &[];
+        }
+
+        let id = self.get_item_id(row, col) as i32;
+
+        // Screenshots still queued for the previously selected production shouldn't hold up
+        // the loads of the items that are visible now
+        if self.preview_id != Some(id) {
+            if let Some(handles) = self.preview_id.and_then(|prev_id| self.previews.get(&prev_id)) {
+                for handle in handles {
+                    ui.hint_load_priority(*handle, LoadPriority::Low);
+                }
+            }
+
+            self.preview_id = Some(id);
+            self.prune_previews(ui, row);
+        }
+
+        let (Some(entry), Some(item)) = (self.productions_loaded.get(&id), self.production_items.get(&id)) else {
+            return &[];
+        };
+
+        // Productions without screenshots have a null background and nothing to preview
+        if item.background_image.0 == 0 {
+            return &[];
+        }
+
+        let previews = self.previews.entry(id).or_insert_with(|| vec![item.background_image]);
+
+        // Load the next screenshot once the previous one is done so the first ones show up
+        // as soon as possible
+        if let Some(&last) = previews.last() {
+            if ui.image_load_state(last) == ImageLoadState::Loading {
+                ui.hint_load_priority(last, LoadPriority::Highest);
+            } else {
+                let background = Self::background_screenshot(entry);
+                let next = entry.screenshots
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| *index != background)
+                    .nth(previews.len() - 1);
+
+                if let Some((_, screenshot)) = next {
+                    let handle = ui.load_background_image(&screenshot.original_url);
+                    ui.hint_load_priority(handle, LoadPriority::Highest);
+                    previews.push(handle);
+                }
+            }
+        }
+
+        previews
+    }
 }
 
 pub struct OnlineDemoSelector {
@@ -254,9 +354,10 @@ impl OnlineDemoSelector {
                 };
 
                 if let Some((row, col)) = self.content_provider.selected_item {
-                    let party = &self.content_provider.parties[0];
-                    let release = &party.competitions[row as usize].results[col as usize].production;
-                    if let Some(entry) = self.content_provider.productions_loaded.get(&selected_id) {
+                    let release = self.content_provider.release(row, col);
+                    let entry = self.content_provider.productions_loaded.get(&selected_id);
+
+                    if let (Some(release), Some(entry)) = (release, entry) {
                         display_entry(ui, release, entry);
                     }
                 }
@@ -266,6 +367,9 @@ impl OnlineDemoSelector {
         );
 
         self.content_provider.update(ui);
+
+        // The row names of the selector are drawn in bold
+        ui.select_font(FontStyle::Bold);
         update(ui, &mut self.content_selector, &mut self.content_provider);
     }
 }
@@ -293,17 +397,18 @@ fn display_entry(ui: &Ui, release: &Release, entry: &ProductionEntry) {
         {
             ui.select_font(FontStyle::Thin);
 
-            let text_size = ui.text_size(&entry.title, 78);
-
-            ui.text_with_layout(&entry.title, 78, (255.0, 255.0, 255.0, 255.0).into(),
+            // The title is drawn on top of the background screenshot so it needs a shadow
+            // to be readable on bright images
+            ui.text_with_effects_with_layout(&entry.title, 78, (255.0, 255.0, 255.0, 255.0).into(),
+                &TextEffects::new().shadow((2, 3), 4, (0.0, 0.0, 0.0, 200.0).into()),
                 &Declaration::new()
                     .layout()
-                        .width(fixed!(text_size.width))
-                        .height(fixed!(text_size.height))
                         .padding(Padding::horizontal(32))
                         .end());
 
-            ui.text_with_layout("1992", 78, (128.0, 128.0, 128.0, 255.0).into(),
+            let release_date = Date::parse(&entry.release_date).map_or("", |date| ui.format_date(date));
+
+            ui.text_with_layout(release_date, 78, (128.0, 128.0, 128.0, 255.0).into(),
                 &Declaration::new()
                     .layout()
                         .width(grow!())
@@ -322,23 +427,26 @@ fn display_entry(ui: &Ui, release: &Release, entry: &ProductionEntry) {
         {
             ui.select_font(FontStyle::Default);
 
-            if release.types.len() > 0 {
-                ui.button(&release.types[0].name);
-            }
+            // "Type Platform by Author" shaped as one run, skipping the parts the entry doesn't have
+            let mut spans = Vec::with_capacity(7);
 
-            if entry.platforms.len() > 0 {
-                ui.button(&entry.platforms[0].name);
-            }
+            let type_name = release.types.first().map(|t| t.name.as_str());
+            let platform = entry.platforms.first().map(|p| p.name.as_str());
 
-            ui.text_with_layout("by", 36, (255.0, 255.0, 255.0, 255.0).into(),
-                &Declaration::new()
-                    .layout()
-                        .width(fixed!(44.0))
-                        .end());
+            for name in [type_name, platform].into_iter().flatten() {
+                spans.push(TextSpan::new(name));
+                spans.push(TextSpan::new(" "));
+            }
 
-            ui.select_font(FontStyle::Bold);
+            if let Some(author) = entry.author_nicks.first() {
+                spans.push(TextSpan::new(ui.localize("entry.by")));
+                spans.push(TextSpan::new(" "));
+                spans.push(TextSpan::new(&author.name)
+                    .font(FontStyle::Bold)
+                    .color((201.0, 22.0, 38.0, 255.0).into()));
+            }
 
-            ui.text_with_layout(&entry.author_nicks[0].name, 36, (201.0, 22.0, 38.0, 255.0).into(),
+            ui.rich_text_with_layout(&spans,
                 &Declaration::new()
                     .layout()
                         .width(grow!())
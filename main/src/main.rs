@@ -56,8 +56,7 @@ fn main() {
     let fonts = Fonts {
         _default: flowi_app
             .ui
-            .load_font("../data/fonts/roboto/Roboto-Regular.ttf")
-            .unwrap(),
+            .load_font("../data/fonts/roboto/Roboto-Regular.ttf"),
     };
 
     let app = Box::new(App {
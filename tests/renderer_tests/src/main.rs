@@ -184,9 +184,7 @@ fn main() {
     let scale_image = load_image("target/cache/a46adb333155e42d.png", &srgb_to_linear_table);
 
     let mut core = flowi_core::Ui::new(Box::new(SoftwareRenderer::new((WIDTH, HEIGHT), None)));
-    let font = core
-        .load_font("data/fonts/roboto/Roboto-Regular.ttf")
-        .unwrap();
+    let font = core.load_font("data/fonts/roboto/Roboto-Regular.ttf");
 
     let text_to_render = "Hello";
 
@@ -52,12 +52,10 @@ fn main() {
     //ui.set_background_image(image, BackgroundMode::AlignTopRight);
 
     let fonts = Fonts {
-        bold: ui.load_font("data/fonts/roboto/Roboto-Bold.ttf").unwrap(),
-        default: ui
-            .load_font("data/fonts/roboto/Roboto-Regular.ttf")
-            .unwrap(),
-        thin: ui.load_font("data/fonts/roboto/Roboto-Thin.ttf").unwrap(),
-        light: ui.load_font("data/fonts/roboto/Roboto-Light.ttf").unwrap(),
+        bold: ui.load_font("data/fonts/roboto/Roboto-Bold.ttf"),
+        default: ui.load_font("data/fonts/roboto/Roboto-Regular.ttf"),
+        thin: ui.load_font("data/fonts/roboto/Roboto-Thin.ttf"),
+        light: ui.load_font("data/fonts/roboto/Roboto-Light.ttf"),
     };
 
     ui.register_font(fonts.default, FontStyle::Default);